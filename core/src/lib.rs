@@ -0,0 +1,343 @@
+//! Verification of signit envelopes for firmware, without `std` or an allocator
+//!
+//! JSON envelopes need an allocator to parse, so devices get a binary
+//! envelope instead, as written by `signit sign --postcard`. It holds the
+//! message, the namespace and the signature, laid out the way postcard
+//! serializes this struct, so firmware that already uses postcard can decode
+//! it with serde just as well:
+//!
+//! ```text
+//! struct Envelope<'a> {
+//!     message: &'a [u8],
+//!     namespace: Option<&'a str>,
+//!     signature: [u8; 64],
+//! }
+//! ```
+//!
+//! The signature covers the same bytes as it does in the JSON envelope, so the
+//! two are interchangeable: the raw message when there is no namespace, and
+//! the `SIGNIT-PAYLOAD-V1` JSON payload otherwise, or when the message itself
+//! starts with that prefix. The payload is streamed
+//! into the verifier rather than built in memory. Keys are raw 32 byte
+//! ed25519 public keys.
+
+#![no_std]
+
+use ed25519_compact::{PublicKey, Signature, VerifyingState};
+
+/// Prefix of the payload signed when the envelope carries more than a message
+pub const PAYLOAD_PREFIX: &[u8] = b"SIGNIT-PAYLOAD-V1\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The envelope ended early
+    Truncated,
+
+    /// Bytes were left over after the envelope
+    TrailingBytes,
+
+    /// A length did not fit the envelope or a `usize`
+    InvalidLength,
+
+    /// The namespace, or a message signed in the payload form, is not UTF-8
+    InvalidUtf8,
+
+    /// The output buffer is too small for the envelope
+    BufferTooSmall,
+
+    /// The signature was not made by the key over this envelope
+    BadSignature,
+}
+
+/// A binary signit envelope, borrowing from the bytes it was decoded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<'a> {
+    pub message: &'a [u8],
+
+    /// Covered by the signature
+    pub namespace: Option<&'a str>,
+    pub signature: [u8; 64],
+}
+
+impl<'a> Envelope<'a> {
+    /// Decode an envelope, which must span all of `bytes`
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut r = Reader(bytes);
+        let message = r.bytes()?;
+        let namespace = match r.take(1)?[0] {
+            0 => None,
+            1 => Some(core::str::from_utf8(r.bytes()?).map_err(|_| Error::InvalidUtf8)?),
+            _ => return Err(Error::InvalidLength),
+        };
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(r.take(64)?);
+
+        if !r.0.is_empty() {
+            return Err(Error::TrailingBytes);
+        }
+        Ok(Envelope { message, namespace, signature })
+    }
+
+    /// Number of bytes `encode` writes
+    pub fn encoded_len(&self) -> usize {
+        let namespace = self.namespace.map_or(0, |n| varint_len(n.len()) + n.len());
+        varint_len(self.message.len()) + self.message.len() + 1 + namespace + 64
+    }
+
+    /// Encode into `buf`, returning the part of it that was written
+    pub fn encode<'b>(&self, buf: &'b mut [u8]) -> Result<&'b [u8], Error> {
+        let len = self.encoded_len();
+        let out = buf.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+
+        let mut pos = put_bytes(out, 0, self.message);
+        match self.namespace {
+            None => {
+                out[pos] = 0;
+                pos += 1;
+            }
+            Some(namespace) => {
+                out[pos] = 1;
+                pos = put_bytes(out, pos + 1, namespace.as_bytes());
+            }
+        }
+        out[pos..].copy_from_slice(&self.signature);
+        Ok(out)
+    }
+
+    /// Check the signature against a raw ed25519 public key
+    pub fn verify(&self, public_key: &[u8; 32]) -> Result<(), Error> {
+        let key = PublicKey::new(*public_key);
+        let sig = Signature::new(self.signature);
+        let mut state = key.verify_incremental(&sig).map_err(|_| Error::BadSignature)?;
+
+        if self.namespace.is_none() && !self.message.starts_with(PAYLOAD_PREFIX) {
+            state.absorb(self.message);
+        } else {
+            let message = core::str::from_utf8(self.message).map_err(|_| Error::InvalidUtf8)?;
+
+            // Compact JSON with sorted keys, escaped the way serde_json escapes
+            state.absorb(PAYLOAD_PREFIX);
+            state.absorb(b"{\"message\":\"");
+            absorb_escaped(&mut state, message);
+            if let Some(namespace) = self.namespace {
+                state.absorb(b"\",\"namespace\":\"");
+                absorb_escaped(&mut state, namespace);
+            }
+            state.absorb(b"\"}");
+        }
+        state.verify().map_err(|_| Error::BadSignature)
+    }
+}
+
+fn absorb_escaped(state: &mut VerifyingState, text: &str) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let bytes = text.as_bytes();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let short = match b {
+            b'"' => b'"',
+            b'\\' => b'\\',
+            0x08 => b'b',
+            0x0c => b'f',
+            b'\n' => b'n',
+            b'\r' => b'r',
+            b'\t' => b't',
+            0x00..=0x1f => b'u',
+            _ => continue,
+        };
+
+        state.absorb(&bytes[start..i]);
+        if short == b'u' {
+            state.absorb([b'\\', b'u', b'0', b'0', HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]);
+        } else {
+            state.absorb([b'\\', short]);
+        }
+        start = i + 1;
+    }
+    state.absorb(&bytes[start..]);
+}
+
+fn varint_len(mut n: usize) -> usize {
+    let mut len = 1;
+    while n >= 0x80 {
+        n >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// Write a varint length followed by the bytes, returning the new position
+fn put_bytes(out: &mut [u8], mut pos: usize, bytes: &[u8]) -> usize {
+    let mut n = bytes.len();
+    while n >= 0x80 {
+        out[pos] = (n as u8) | 0x80;
+        n >>= 7;
+        pos += 1;
+    }
+    out[pos] = n as u8;
+    pos += 1;
+
+    out[pos..pos + bytes.len()].copy_from_slice(bytes);
+    pos + bytes.len()
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// A varint length followed by that many bytes, as postcard writes slices
+    fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let mut len = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.take(1)?[0];
+            let bits = (byte & 0x7f) as usize;
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(Error::InvalidLength);
+            }
+            len |= bits << shift;
+            if byte & 0x80 == 0 {
+                return self.take(len);
+            }
+            shift += 7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made with `signit sign` by the key printed by `signit pubkey -f raw`
+    const PUBLIC_KEY: [u8; 32] = [
+        0xfb, 0xa2, 0x4c, 0xba, 0xc0, 0x8e, 0xaa, 0x3f, 0xb4, 0xdf, 0x58, 0x4a, 0xa9, 0x66, 0xbf, 0xe9,
+        0x8c, 0x96, 0xf2, 0x3b, 0xc5, 0x15, 0x06, 0x00, 0x8a, 0x40, 0x77, 0x55, 0xa2, 0xca, 0xe5, 0x03,
+    ];
+
+    /// `sign --postcard -m "boot image v1"`, signed in the legacy, raw message form
+    const LEGACY: [u8; 79] = [
+        0x0d, 0x62, 0x6f, 0x6f, 0x74, 0x20, 0x69, 0x6d, 0x61, 0x67, 0x65, 0x20, 0x76, 0x31, 0x00, 0xb1,
+        0xb5, 0xe9, 0x2e, 0xe6, 0x38, 0xb8, 0x18, 0x0d, 0x0c, 0x6e, 0x62, 0x5f, 0x3c, 0x9f, 0xa6, 0xb0,
+        0x86, 0x11, 0x29, 0x42, 0x96, 0x97, 0xa2, 0xbe, 0x89, 0x32, 0x68, 0xc4, 0xbd, 0x13, 0x05, 0x3e,
+        0xbc, 0x7a, 0x73, 0x57, 0xd6, 0x27, 0x74, 0x88, 0x91, 0x86, 0x93, 0x54, 0xe8, 0x37, 0x59, 0x9a,
+        0x26, 0x1f, 0x6c, 0x9e, 0x57, 0xe4, 0xd7, 0x18, 0x2d, 0x05, 0x81, 0x9d, 0xcb, 0xe1, 0x08,
+    ];
+
+    /// `sign --postcard -n firmware -m 'boot "image" v2'`, signed in the payload form
+    const NAMESPACED: [u8; 90] = [
+        0x0f, 0x62, 0x6f, 0x6f, 0x74, 0x20, 0x22, 0x69, 0x6d, 0x61, 0x67, 0x65, 0x22, 0x20, 0x76, 0x32,
+        0x01, 0x08, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65, 0x99, 0x96, 0x80, 0x76, 0x3a, 0x55,
+        0xea, 0x3f, 0xdc, 0xfe, 0x9d, 0x16, 0xec, 0xca, 0x7d, 0x99, 0xe8, 0x92, 0x92, 0x09, 0x13, 0x4c,
+        0x01, 0x65, 0xf8, 0xa2, 0xe4, 0x49, 0x95, 0xbd, 0xa7, 0x4e, 0xbb, 0xc4, 0x6c, 0x34, 0xee, 0x9f,
+        0x80, 0xca, 0x46, 0xce, 0xc7, 0x36, 0x12, 0x29, 0xcf, 0xe0, 0x9a, 0x2e, 0x19, 0x24, 0x1d, 0x2f,
+        0xe7, 0x6a, 0x19, 0xcc, 0x0f, 0xa4, 0x42, 0x98, 0xe6, 0x0f,
+    ];
+
+    /// `sign --postcard` of a message starting with `SIGNIT-PAYLOAD-V1\n`, signed in the payload form
+    const PREFIXED: [u8; 97] = [
+        0x1f, 0x53, 0x49, 0x47, 0x4e, 0x49, 0x54, 0x2d, 0x50, 0x41, 0x59, 0x4c, 0x4f, 0x41, 0x44, 0x2d,
+        0x56, 0x31, 0x0a, 0x62, 0x6f, 0x6f, 0x74, 0x20, 0x69, 0x6d, 0x61, 0x67, 0x65, 0x20, 0x76, 0x33,
+        0x00, 0x75, 0xae, 0xab, 0x1c, 0x66, 0xcd, 0xa9, 0xe7, 0x9f, 0x37, 0xd8, 0x01, 0x2b, 0xa0, 0x57,
+        0x80, 0x2f, 0x8b, 0x51, 0x51, 0x12, 0xc3, 0x6e, 0x15, 0x96, 0xe8, 0x74, 0xa3, 0x1b, 0x86, 0xca,
+        0x7d, 0xf8, 0x48, 0x81, 0x3d, 0x06, 0xab, 0x20, 0x9a, 0x21, 0xb3, 0xc9, 0x8b, 0x93, 0x19, 0x92,
+        0x12, 0x2e, 0x4d, 0x8e, 0xaf, 0x65, 0x11, 0x64, 0xd4, 0x62, 0x43, 0x45, 0xd4, 0x86, 0x48, 0x5c,
+        0x0e,
+    ];
+
+    /// Signature of the JSON envelope from `sign -n firmware -m "boot image v4"`
+    const JSON_SIGNATURE: [u8; 64] = [
+        0x63, 0xda, 0x42, 0xeb, 0x0a, 0x9e, 0x4c, 0xd4, 0x1f, 0x97, 0x74, 0x6b, 0x59, 0xfe, 0x51, 0xbd,
+        0x71, 0x4f, 0x6d, 0x6e, 0x3f, 0x13, 0x21, 0x8f, 0xaf, 0xe6, 0xe8, 0x39, 0x6f, 0x4c, 0x42, 0xea,
+        0xd0, 0x22, 0x21, 0x07, 0x76, 0x74, 0x13, 0xaa, 0x24, 0x48, 0xc9, 0x33, 0xd8, 0xa5, 0x4b, 0x55,
+        0x02, 0x3c, 0x50, 0x59, 0xa5, 0x7f, 0x02, 0x07, 0x76, 0x9e, 0x43, 0x21, 0x82, 0xeb, 0x33, 0x04,
+    ];
+
+    /// Detached signature from `sign --raw base64 -m "boot image v5"`
+    const RAW_SIGNATURE: [u8; 64] = [
+        0x7f, 0x44, 0xf9, 0xfb, 0xb2, 0xe4, 0x02, 0xf4, 0x00, 0x7c, 0xce, 0xa7, 0x16, 0xc8, 0x70, 0xbb,
+        0x5f, 0x03, 0xe0, 0xcd, 0xc6, 0xa1, 0x90, 0xa3, 0x70, 0x73, 0x71, 0xf2, 0x2c, 0x8a, 0x86, 0xab,
+        0xb0, 0xbb, 0x19, 0x3c, 0xa6, 0x2a, 0x3e, 0x80, 0x3e, 0x40, 0x7c, 0xf1, 0xd5, 0xe8, 0x44, 0xa7,
+        0x67, 0x36, 0x27, 0xb3, 0x04, 0x5f, 0x14, 0x25, 0x3c, 0x29, 0x84, 0xfd, 0x74, 0x85, 0xa1, 0x02,
+    ];
+
+    #[test]
+    fn verifies_legacy_envelope() {
+        let envelope = Envelope::decode(&LEGACY).unwrap();
+        assert_eq!(envelope.message, b"boot image v1");
+        assert_eq!(envelope.namespace, None);
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Ok(()));
+    }
+
+    #[test]
+    fn verifies_namespaced_envelope() {
+        let envelope = Envelope::decode(&NAMESPACED).unwrap();
+        assert_eq!(envelope.message, b"boot \"image\" v2");
+        assert_eq!(envelope.namespace, Some("firmware"));
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Ok(()));
+    }
+
+    #[test]
+    fn verifies_prefixed_message_in_payload_form() {
+        let envelope = Envelope::decode(&PREFIXED).unwrap();
+        assert_eq!(envelope.message, b"SIGNIT-PAYLOAD-V1\nboot image v3");
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Ok(()));
+    }
+
+    #[test]
+    fn verifies_json_envelope_signature() {
+        let envelope = Envelope { message: b"boot image v4", namespace: Some("firmware"), signature: JSON_SIGNATURE };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Ok(()));
+    }
+
+    #[test]
+    fn verifies_raw_detached_signature() {
+        let envelope = Envelope { message: b"boot image v5", namespace: None, signature: RAW_SIGNATURE };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Ok(()));
+    }
+
+    #[test]
+    fn rejects_tampered_namespace() {
+        let envelope = Envelope { namespace: Some("bootloader"), ..Envelope::decode(&NAMESPACED).unwrap() };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Err(Error::BadSignature));
+
+        let envelope = Envelope { namespace: None, ..Envelope::decode(&NAMESPACED).unwrap() };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Err(Error::BadSignature));
+
+        let envelope = Envelope { namespace: Some("firmware"), ..Envelope::decode(&LEGACY).unwrap() };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Err(Error::BadSignature));
+    }
+
+    #[test]
+    fn rejects_tampered_message() {
+        let envelope = Envelope { message: b"boot image v6", ..Envelope::decode(&LEGACY).unwrap() };
+        assert_eq!(envelope.verify(&PUBLIC_KEY), Err(Error::BadSignature));
+    }
+
+    #[test]
+    fn encodes_what_it_decodes() {
+        let mut buf = [0u8; 128];
+        for bytes in [&LEGACY[..], &NAMESPACED[..], &PREFIXED[..]] {
+            let envelope = Envelope::decode(bytes).unwrap();
+            assert_eq!(envelope.encoded_len(), bytes.len());
+            assert_eq!(envelope.encode(&mut buf), Ok(bytes));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_envelopes() {
+        assert_eq!(Envelope::decode(&LEGACY[..LEGACY.len() - 1]), Err(Error::Truncated));
+
+        let mut long = [0u8; LEGACY.len() + 1];
+        long[..LEGACY.len()].copy_from_slice(&LEGACY);
+        assert_eq!(Envelope::decode(&long), Err(Error::TrailingBytes));
+    }
+}
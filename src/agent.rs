@@ -0,0 +1,159 @@
+//! A small ssh-agent client, for `signit agent` and `sign --agent-key`
+//!
+//! The agent is reached at `$SSH_AUTH_SOCK`, or on Windows at the named pipe
+//! of the OpenSSH agent service. Messages are a big-endian `u32` length
+//! followed by a type byte and the body, as in the draft-miller-ssh-agent
+//! protocol.
+
+use std::io::{self, Read, Write};
+
+use signit::keys;
+
+/// SSH_AGENTC_REQUEST_IDENTITIES
+const REQUEST_IDENTITIES: u8 = 11;
+
+/// SSH_AGENT_IDENTITIES_ANSWER
+const IDENTITIES_ANSWER: u8 = 12;
+
+/// SSH_AGENTC_SIGN_REQUEST
+const SIGN_REQUEST: u8 = 13;
+
+/// SSH_AGENT_SIGN_RESPONSE
+const SIGN_RESPONSE: u8 = 14;
+
+/// A key held by the agent
+pub struct Identity {
+    /// The public key in the SSH wire encoding
+    pub blob: Vec<u8>,
+    pub comment: String,
+}
+
+impl Identity {
+    /// The key type, such as `ssh-ed25519` or `ssh-rsa`
+    pub fn key_type(&self) -> String {
+        let mut blob = &self.blob[..];
+        read_string(&mut blob).map(|t| String::from_utf8_lossy(t).into_owned()).unwrap_or_default()
+    }
+
+    pub fn fingerprint(&self) -> String {
+        keys::blob_fingerprint(&self.blob)
+    }
+}
+
+/// Every key the agent currently holds, in the agent's order
+pub fn identities() -> Result<Vec<Identity>, String> {
+    let answer = request(&[REQUEST_IDENTITIES])?;
+    let mut body = match answer.split_first() {
+        Some((&IDENTITIES_ANSWER, body)) => body,
+        _ => return Err("ssh-agent refused to list its keys".to_string()),
+    };
+
+    let count = read_u32(&mut body).ok_or(TRUNCATED)?;
+    let mut found = vec![];
+    for _ in 0..count {
+        let blob = read_string(&mut body).ok_or(TRUNCATED)?.to_vec();
+        let comment = String::from_utf8_lossy(read_string(&mut body).ok_or(TRUNCATED)?).into_owned();
+        found.push(Identity { blob, comment });
+    }
+    Ok(found)
+}
+
+/// The agent's key with this fingerprint, with or without `SHA256:`, or this comment
+pub fn find(query: &str) -> Result<Identity, String> {
+    let wanted = if query.starts_with("SHA256:") { query.to_string() } else { format!("SHA256:{}", query) };
+    identities()?
+        .into_iter()
+        .find(|identity| identity.fingerprint() == wanted || identity.comment == query)
+        .ok_or(format!("ssh-agent holds no key {:?}, see `signit agent list`", query))
+}
+
+/// Have the agent make an ed25519 signature over `data`
+pub fn sign(identity: &Identity, data: &[u8]) -> Result<[u8; 64], String> {
+    if identity.key_type() != "ssh-ed25519" {
+        return Err(format!("Agent key {} is not an ed25519 key!", identity.fingerprint()));
+    }
+    let mut message = vec![SIGN_REQUEST];
+    write_string(&mut message, &identity.blob);
+    write_string(&mut message, data);
+    message.extend_from_slice(&0u32.to_be_bytes());
+
+    let answer = request(&message)?;
+    let mut body = match answer.split_first() {
+        Some((&SIGN_RESPONSE, body)) => body,
+        _ => return Err(format!("ssh-agent refused to sign with {}", identity.fingerprint())),
+    };
+
+    // The signature is itself encoded as its type and the signature bytes
+    let mut signature = read_string(&mut body).ok_or(TRUNCATED)?;
+    let kind = read_string(&mut signature).ok_or(TRUNCATED)?;
+    let raw = read_string(&mut signature).ok_or(TRUNCATED)?;
+    if kind != b"ssh-ed25519" || raw.len() != 64 {
+        return Err("ssh-agent sent an unexpected signature".to_string());
+    }
+    let mut out = [0u8; 64];
+    out.copy_from_slice(raw);
+    Ok(out)
+}
+
+const TRUNCATED: &str = "ssh-agent sent a truncated answer";
+
+fn request(message: &[u8]) -> Result<Vec<u8>, String> {
+    let mut stream = connect()?;
+    let exchange = |stream: &mut dyn ReadWrite| -> io::Result<Vec<u8>> {
+        stream.write_all(&(message.len() as u32).to_be_bytes())?;
+        stream.write_all(message)?;
+
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let mut answer = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut answer)?;
+        Ok(answer)
+    };
+    exchange(&mut stream).map_err(|e| format!("Failed to talk to ssh-agent\nError: {:?}", e))
+}
+
+trait ReadWrite: Read + Write {}
+impl<T: Read + Write> ReadWrite for T {}
+
+#[cfg(unix)]
+fn connect() -> Result<impl ReadWrite, String> {
+    let path = std::env::var_os("SSH_AUTH_SOCK").ok_or("SSH_AUTH_SOCK is not set, is ssh-agent running?")?;
+    std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|e| format!("Failed to connect to ssh-agent at {:?}\nError: {:?}", path, e))
+}
+
+#[cfg(not(unix))]
+fn connect() -> Result<impl ReadWrite, String> {
+    const PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+
+    let path = std::env::var_os("SSH_AUTH_SOCK").unwrap_or_else(|| PIPE.into());
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to connect to ssh-agent at {:?}\nError: {:?}", path, e))
+}
+
+fn read_u32(buf: &mut &[u8]) -> Option<u32> {
+    if buf.len() < 4 {
+        return None;
+    }
+    let (n, rest) = buf.split_at(4);
+    *buf = rest;
+    Some(u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+}
+
+fn read_string<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = read_u32(buf)? as usize;
+    if buf.len() < len {
+        return None;
+    }
+    let (s, rest) = buf.split_at(len);
+    *buf = rest;
+    Some(s)
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s);
+}
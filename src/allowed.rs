@@ -0,0 +1,148 @@
+//! OpenSSH `allowed_signers` files, for `signit who-signed`
+//!
+//! Each line lists the principals a key belongs to, then options, then the
+//! key, as `ssh-keygen -Y` reads them:
+//!
+//! ```text
+//! alice@example.com,alice namespaces="git,release" ssh-ed25519 AAAA...
+//! *@example.com cert-authority ssh-ed25519 AAAA...
+//! ```
+//!
+//! `namespaces` limits what the key may sign, `valid-after` and `valid-before`
+//! when, and `cert-authority` makes the key a CA for the principals, which
+//! may then contain `*` and `?` wildcards. Times are read as UTC.
+
+use thrussh_keys::key::PublicKey;
+use thrussh_keys::parse_public_key_base64;
+
+use crate::cert;
+use crate::envelope::Signer;
+use crate::usage::glob_match;
+use signit::keys;
+
+pub struct Entry {
+    pub principals: Vec<String>,
+    pub key: PublicKey,
+    pub cert_authority: bool,
+    namespaces: Option<Vec<String>>,
+    valid_after: Option<time::Tm>,
+    valid_before: Option<time::Tm>,
+}
+
+/// Every ed25519 entry of an `allowed_signers` file, skipping other key types
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let fields = keys::authorized_key_fields(line.trim());
+        let invalid = |reason: &str| format!("Line {} of the allowed signers is invalid, {}", number + 1, reason);
+        let key_at = match fields.iter().position(|f| f.starts_with("ssh-") || f.starts_with("ecdsa-") || f.starts_with("sk-")) {
+            Some(at) if at > 0 => at,
+            Some(_) => return Err(invalid("it names no principals")),
+            None if fields.is_empty() => continue,
+            None => return Err(invalid("it holds no key")),
+        };
+        if fields[key_at] != "ssh-ed25519" {
+            continue;
+        }
+        let key = fields
+            .get(key_at + 1)
+            .and_then(|b64| parse_public_key_base64(b64).ok())
+            .ok_or_else(|| invalid("its key does not parse"))?;
+
+        let mut entry = Entry {
+            principals: unquote(fields[0]).split(',').map(str::to_string).collect(),
+            key,
+            cert_authority: false,
+            namespaces: None,
+            valid_after: None,
+            valid_before: None,
+        };
+        for option in fields[1..key_at].iter().flat_map(|options| split_options(options)) {
+            let (name, value) = match option.find('=') {
+                Some(split) => (&option[..split], Some(unquote(&option[split + 1..]))),
+                None => (option, None),
+            };
+            match (name.to_lowercase().as_str(), value) {
+                ("cert-authority", None) => entry.cert_authority = true,
+                ("namespaces", Some(value)) => entry.namespaces = Some(value.split(',').map(str::to_string).collect()),
+                ("valid-after", Some(value)) => entry.valid_after = Some(parse_time(value).map_err(|e| invalid(&e))?),
+                ("valid-before", Some(value)) => entry.valid_before = Some(parse_time(value).map_err(|e| invalid(&e))?),
+                _ => return Err(invalid(&format!("{:?} is not an option it knows", option))),
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+impl Entry {
+    /// The principals that made the ed25519 signature `sig` over `signed`, according to this entry
+    ///
+    /// CA entries name the principals of the signer's certificate that they
+    /// match, if this CA issued it and it is valid now.
+    pub fn principals_for(&self, signed: &[u8], sig: &[u8], signer: &Signer) -> Vec<String> {
+        let now = time::now_utc();
+        let namespace = signer.namespace.as_deref().unwrap_or("");
+        let allowed = self.namespaces.as_ref().map_or(true, |namespaces| namespaces.iter().any(|n| glob_match(n, namespace)))
+            && self.valid_after.map_or(true, |after| now >= after)
+            && self.valid_before.map_or(true, |before| now < before);
+        if !allowed {
+            return vec![];
+        }
+        if !self.cert_authority {
+            if !self.key.verify_detached(signed, sig) {
+                return vec![];
+            }
+            return self.principals.clone();
+        }
+
+        let certificate = match signer.certificate.as_deref().map(cert::Certificate::parse) {
+            Some(Ok(certificate)) => certificate,
+            _ => return vec![],
+        };
+        let trusted = std::slice::from_ref(&self.key);
+        if certificate.check(trusted, cert::USER, None, cert::now(), 0).is_err() || !certificate.key.verify_detached(signed, sig) {
+            return vec![];
+        }
+        certificate
+            .principals
+            .into_iter()
+            .filter(|principal| self.principals.iter().any(|pattern| glob_match(pattern, principal)))
+            .collect()
+    }
+}
+
+/// Split options at the commas outside of quotes
+fn split_options(options: &str) -> Vec<&str> {
+    let mut split = vec![];
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in options.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                split.push(&options[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    split.push(&options[start..]);
+    split
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text)
+}
+
+/// Parse `YYYYMMDD`, `YYYYMMDDHHMM` or `YYYYMMDDHHMMSS`, with or without a trailing `Z`
+fn parse_time(text: &str) -> Result<time::Tm, String> {
+    let digits = text.trim_end_matches(|c| c == 'Z' || c == 'z');
+    let format = match digits.len() {
+        8 => "%Y%m%d",
+        12 => "%Y%m%d%H%M",
+        14 => "%Y%m%d%H%M%S",
+        _ => "",
+    };
+    time::strptime(digits, format).map_err(|_| format!("{:?} is not a time like 20240131 or 20240131120000", text))
+}
+
@@ -0,0 +1,72 @@
+//! Key files encrypted at rest with age or SOPS, decrypted as they are loaded
+//!
+//! Decryption is left to the `age` and `sops` commands, so whatever identities
+//! and providers a team already has set up for them keep working: plugins and
+//! hardware tokens for age, and cloud KMS, PGP or age recipients for SOPS.
+//!
+//! age uses the identity file in `SIGNIT_AGE_IDENTITY`, or else the one SOPS
+//! keeps its age keys in. Without either, age asks for a passphrase, which is
+//! right for files encrypted with `age -p`. SOPS files must have been
+//! encrypted from the key file as a whole, with `--input-type binary`.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use zeroize::Zeroizing;
+
+const AGE_BINARY: &[u8] = b"age-encryption.org/v1\n";
+const AGE_ARMORED: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// The decrypted key file, or `None` if `contents` is not encrypted with age or SOPS
+pub fn decrypt(contents: &[u8]) -> Result<Option<Zeroizing<Vec<u8>>>, String> {
+    let text = std::str::from_utf8(contents).unwrap_or("").trim_start();
+    let decrypted = if contents.starts_with(AGE_BINARY) || text.as_bytes().starts_with(AGE_ARMORED) {
+        let mut args = vec!["--decrypt".to_string()];
+        if let Some(identity) = age_identity() {
+            args.push("--identity".to_string());
+            args.push(identity.display().to_string());
+        }
+        run("age", &args, contents)?
+    } else if is_sops(text) {
+        let input_type = if text.starts_with('{') { "json" } else { "yaml" };
+        let args = ["--decrypt", "--input-type", input_type, "--output-type", "binary", "/dev/stdin"];
+        run("sops", &args.iter().map(|a| a.to_string()).collect::<Vec<_>>(), contents)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(decrypted))
+}
+
+/// Whether `text` is a SOPS document, which keeps its metadata under a top level `sops` key
+fn is_sops(text: &str) -> bool {
+    if text.starts_with('{') {
+        let document: Option<serde_json::Value> = serde_json::from_str(text).ok();
+        return document.and_then(|d| d.get("sops").map(|sops| sops.get("mac").is_some())).unwrap_or(false);
+    }
+    text.lines().any(|line| line == "sops:") && text.contains("ENC[")
+}
+
+fn age_identity() -> Option<PathBuf> {
+    let configured = std::env::var_os("SIGNIT_AGE_IDENTITY").or_else(|| std::env::var_os("SOPS_AGE_KEY_FILE"));
+    configured
+        .map(PathBuf::from)
+        .or_else(|| dirs::config_dir().map(|dir| dir.join("sops/age/keys.txt")).filter(|path| path.exists()))
+}
+
+/// Run `program`, which may prompt on the terminal, with `input` on stdin
+fn run(program: &str, args: &[String], input: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Key file is encrypted, but {} could not be run\nError: {:?}", program, e))?;
+
+    child.stdin.take().unwrap().write_all(input).map_err(|e| format!("Failed to pass the key file to {}\nError: {:?}", program, e))?;
+    let output = child.wait_with_output().map_err(|e| format!("Key file is encrypted, but {} failed\nError: {:?}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("Key file is encrypted, but {} could not decrypt it", program));
+    }
+    Ok(Zeroizing::new(output.stdout))
+}
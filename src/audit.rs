@@ -0,0 +1,103 @@
+use hex::encode as hex_encode;
+use openssl::sha::sha256;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::{eject, syslog};
+
+/// A single line of the audit log
+#[derive(Debug, Serialize)]
+pub struct Record<'a> {
+    pub timestamp: String,
+    pub operation: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_fingerprint: Option<String>,
+
+    pub input_digest: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_user: Option<&'a str>,
+
+    pub result: &'a str,
+
+    /// Milliseconds since the operation started, filled in by `Sink::write`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// Where records go: appended to `--audit-log`, streamed to stderr with `--log-format ndjson`, sent to `--log-to`
+pub struct Sink {
+    path: Option<PathBuf>,
+    stderr: bool,
+    system: Option<String>,
+    started: Instant,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(operation: &'a str, input: &[u8], result: &'a str) -> Self {
+        Self::with_digest(operation, format!("sha256:{}", hex_encode(sha256(input))), result)
+    }
+
+    /// A record for input that was hashed as it streamed past, rather than held in memory
+    pub fn with_digest(operation: &'a str, input_digest: String, result: &'a str) -> Self {
+        Record {
+            timestamp: time::now_utc().rfc3339().to_string(),
+            operation,
+            key_fingerprint: None,
+            input_digest,
+            github_user: None,
+            result,
+            duration_ms: None,
+        }
+    }
+}
+
+impl Sink {
+    /// Start timing an operation, logged as `sign` and `verify`'s flags ask
+    pub fn new(path: Option<PathBuf>, log_format: Option<String>, log_to: Option<String>) -> Self {
+        Sink { path, stderr: log_format.is_some(), system: log_to, started: Instant::now() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some() || self.stderr || self.system.is_some()
+    }
+
+    pub fn write(&self, mut record: Record) {
+        record.duration_ms = Some(self.started.elapsed().as_millis() as u64);
+        if let Some(path) = &self.path {
+            append(path, &record);
+        }
+        if self.stderr {
+            eprintln!("{}", serde_json::to_string(&record).unwrap());
+        }
+        if let Some(target) = &self.system {
+            syslog::send(target, &record).unwrap_or_else(|e| eject(&e));
+        }
+    }
+}
+
+/// Append a record to the audit log, creating the log if necessary
+///
+/// Existing contents of the log are never truncated or rewritten.
+pub fn append(path: &Path, record: &Record) {
+    let mut line = serde_json::to_string(record).unwrap();
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to open audit log: {:?}\nError: {:?}", path, e));
+        });
+
+    // A single write keeps concurrent writers from interleaving lines
+    file.write_all(line.as_bytes())
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to write audit log: {:?}\nError: {:?}", path, e));
+        });
+}
@@ -0,0 +1,124 @@
+//! `signit-verify`, a verify-only build of `signit`
+//!
+//! It can parse and verify envelopes, bundles and detached signatures against
+//! a public key given on the command line, and nothing else: no signing, no
+//! private keys, and no network access to fetch github keys. That keeps it
+//! small enough to bake into an initramfs or container that validates
+//! artifacts at boot.
+
+use std::fs::read;
+use std::io::Read;
+use std::path::PathBuf;
+
+use base64::decode;
+use structopt::StructOpt;
+use thrussh_keys::key::PublicKey;
+
+use signit::envelope::{self, Detached, SignIt};
+use signit::{keys, merkle};
+
+/// Verify signit signatures with an ed25519 public key
+#[derive(StructOpt)]
+#[structopt(name = "signit-verify")]
+struct Opt {
+    /// Path to the signer's ed25519 public key, in OpenSSH format or as raw 32 bytes
+    #[structopt(short = "k", parse(from_os_str))]
+    public_key: PathBuf,
+
+    /// Envelope or bundle to verify, or the signed data with -s, defaults to stdin
+    #[structopt(short = "i", parse(from_os_str))]
+    input: Option<PathBuf>,
+
+    /// Detached signature to verify -i against, from `signit watch` or a raw 64-byte signature
+    #[structopt(short = "s", parse(from_os_str))]
+    signature: Option<PathBuf>,
+
+    /// Require the signature to be made for this namespace
+    #[structopt(short = "n")]
+    namespace: Option<String>,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let contents = read(&opt.public_key)
+        .unwrap_or_else(|e| eject(&format!("Failed to load key at {:?}\nError: {:?}", opt.public_key, e)));
+    let key = keys::parse_public_key(&contents)
+        .unwrap_or_else(|| eject(&format!("Failed to load key at {:?}\nNot an ed25519 public key", opt.public_key)));
+    let keys = [key];
+
+    let input = match &opt.input {
+        Some(path) => read(path)
+            .unwrap_or_else(|e| eject(&format!("Failed to read file {:?}\nError: {:?}", path, e))),
+        None => {
+            let mut buffer = vec![];
+            std::io::stdin().read_to_end(&mut buffer)
+                .unwrap_or_else(|e| eject(&format!("Failed to read stdin\nError: {:?}", e)));
+            buffer
+        }
+    };
+
+    let verified = match &opt.signature {
+        Some(path) => {
+            let sig = read(path)
+                .unwrap_or_else(|e| eject(&format!("Failed to read signature {:?}\nError: {:?}", path, e)));
+            verify_detached(&sig, &input, &keys, &opt.namespace)
+        }
+        None => {
+            let raw = String::from_utf8(input)
+                .unwrap_or_else(|_| eject("Input is not UTF-8 text, so it is not an envelope!"));
+            let envelopes = envelope::parse_envelopes(&raw)
+                .unwrap_or_else(|e| eject(&format!("Failed to parse message\nError: {:?}", e)));
+            envelopes.iter().any(|env| verify_envelope(env, &keys, &opt.namespace))
+        }
+    };
+
+    if !verified {
+        eject("Verification failed!");
+    }
+    println!("Verified!");
+}
+
+fn verify_envelope(env: &SignIt, keys: &[PublicKey], namespace: &Option<String>) -> bool {
+    if namespace.is_some() && env.signer.namespace != *namespace {
+        return false;
+    }
+    match decode(&env.signer.signature) {
+        Ok(sig) => keys::find_key(&env.signed_bytes(), &sig, keys).is_some(),
+        Err(_) => false,
+    }
+}
+
+fn verify_detached(sig: &[u8], data: &[u8], keys: &[PublicKey], namespace: &Option<String>) -> bool {
+    if let Ok(detached) = serde_json::from_slice::<Detached>(sig) {
+        if namespace.is_some() && detached.signer.namespace != *namespace {
+            return false;
+        }
+        let matches = match &detached.chunks {
+            Some(chunks) => merkle::check(chunks, &detached.digest).and_then(|_| merkle::check_all(chunks, data)).is_ok(),
+            None => envelope::digest(data).unwrap() == detached.digest,
+        };
+        if !matches {
+            return false;
+        }
+        return match decode(&detached.signer.signature) {
+            Ok(sig) => keys::find_key(&detached.signed_bytes(), &sig, keys).is_some(),
+            Err(_) => false,
+        };
+    }
+
+    // A raw signature over the data itself carries no namespace to check
+    let raw = if sig.len() == 64 {
+        sig.to_vec()
+    } else {
+        std::str::from_utf8(sig).ok().and_then(|s| decode(s.trim()).ok()).unwrap_or_default()
+    };
+    if raw.len() != 64 || namespace.is_some() || data.starts_with(envelope::PAYLOAD_PREFIX) {
+        return false;
+    }
+    keys::find_key(data, &raw, keys).is_some()
+}
+
+fn eject(reason: &str) -> ! {
+    eprintln!("{}", reason);
+    std::process::exit(-1);
+}
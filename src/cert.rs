@@ -0,0 +1,193 @@
+//! OpenSSH certificates, for `sign --certificate` and `verify --trusted-ca`
+//!
+//! An `ssh-ed25519-cert-v01@openssh.com` certificate is an ed25519 key signed
+//! by a certificate authority, together with who it is for (its principals)
+//! and when it is valid. Organisations that run an SSH CA can then verify
+//! signatures against the CA key alone, instead of every member's key.
+//!
+//! Only ed25519 CAs are accepted. Certificates with critical options are
+//! refused, as those restrict logins in ways that have no meaning for a
+//! signature, and a verifier that ignores a restriction must not accept it.
+
+use base64::{decode, encode_config, STANDARD};
+use signit::keys;
+use thrussh_keys::key::PublicKey;
+use thrussh_keys::parse_public_key_base64;
+
+pub const TYPE: &str = "ssh-ed25519-cert-v01@openssh.com";
+
+/// Certificate types, for users and for hosts
+pub const USER: u32 = 1;
+pub const HOST: u32 = 2;
+
+pub struct Certificate {
+    /// The certified key, which makes the signatures
+    pub key: PublicKey,
+    pub serial: u64,
+    pub kind: u32,
+    pub key_id: String,
+
+    /// Empty means valid for any principal
+    pub principals: Vec<String>,
+
+    pub valid_after: u64,
+    pub valid_before: u64,
+    pub critical_options: Vec<String>,
+    pub ca: PublicKey,
+
+    /// The certificate up to its signature, which the CA signed
+    signed: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Certificate {
+    /// Parse a `ssh-ed25519-cert-v01@openssh.com AAAA... [comment]` line, as in `id_ed25519-cert.pub`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut fields = line.split_whitespace();
+        let b64 = match (fields.next(), fields.next()) {
+            (Some(TYPE), Some(b64)) => b64,
+            (Some(b64), None) => b64,
+            _ => return Err(format!("Not an {} certificate", TYPE)),
+        };
+        let blob = decode(b64).map_err(|_| "Certificate is not proper base64")?;
+        Self::from_blob(&blob).ok_or_else(|| "Certificate is malformed".to_string())?
+    }
+
+    fn from_blob(blob: &[u8]) -> Option<Result<Self, String>> {
+        let mut reader = Reader(blob);
+        if reader.string()? != TYPE.as_bytes() {
+            return Some(Err(format!("Not an {} certificate", TYPE)));
+        }
+        let _nonce = reader.string()?;
+        let key = keys::from_raw_public(reader.string()?)?;
+        let serial = reader.u64()?;
+        let kind = reader.u32()?;
+        let key_id = String::from_utf8(reader.string()?.to_vec()).ok()?;
+        let principals = names(reader.string()?)?;
+        let valid_after = reader.u64()?;
+        let valid_before = reader.u64()?;
+        let critical_options = options(reader.string()?)?;
+        let _extensions = reader.string()?;
+        let _reserved = reader.string()?;
+        let ca_blob = reader.string()?;
+        let signed = blob[..blob.len() - reader.0.len()].to_vec();
+        let mut signature = Reader(reader.string()?);
+        if !reader.0.is_empty() {
+            return None;
+        }
+
+        if Reader(ca_blob).string()? != b"ssh-ed25519" || signature.string()? != b"ssh-ed25519" {
+            return Some(Err("Certificate is signed by a CA that is not ed25519".to_string()));
+        }
+        let ca = parse_public_key_base64(&encode_config(ca_blob, STANDARD)).ok()?;
+        let signature = signature.string()?.to_vec();
+        Some(Ok(Certificate {
+            key,
+            serial,
+            kind,
+            key_id,
+            principals,
+            valid_after,
+            valid_before,
+            critical_options,
+            ca,
+            signed,
+            signature,
+        }))
+    }
+
+    /// Check that one of `cas` issued this certificate for `principal`, if
+    /// given, and that it is a `kind` certificate valid at `now`, in seconds
+    /// since the epoch, give or take `skew` seconds
+    pub fn check(&self, cas: &[PublicKey], kind: u32, principal: Option<&str>, now: u64, skew: u64) -> Result<(), String> {
+        if !self.ca.verify_detached(&self.signed, &self.signature) {
+            return Err("Certificate signature is invalid!".to_string());
+        }
+        if !cas.contains(&self.ca) {
+            return Err(format!("Certificate was issued by {}, which is not a trusted CA!", keys::fingerprint(&self.ca)));
+        }
+        if self.kind != kind {
+            let name = |kind| if kind == HOST { "host" } else { "user" };
+            return Err(format!("Certificate is a {} certificate, not a {} one!", name(self.kind), name(kind)));
+        }
+        if now.saturating_add(skew) < self.valid_after || now >= self.valid_before.saturating_add(skew) {
+            return Err(format!("Certificate {:?} is not valid now, only {}", self.key_id, self.validity()));
+        }
+        if let Some(option) = self.critical_options.first() {
+            return Err(format!("Certificate has the critical option {:?}, which signatures cannot honour!", option));
+        }
+        match principal {
+            Some(principal) if !self.principals.is_empty() && !self.principals.iter().any(|p| p == principal) => {
+                Err(format!("Certificate {:?} is not valid for {:?}, only {}", self.key_id, principal, self.principals.join(", ")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The validity window, like `from 2024-01-31T12:00:00Z to 2024-02-01T12:00:00Z`
+    pub fn validity(&self) -> String {
+        let time = |secs: u64| match secs {
+            0 => "the beginning".to_string(),
+            u64::MAX => "forever".to_string(),
+            secs => time::at_utc(time::Timespec::new(secs.min(i64::MAX as u64) as i64, 0)).rfc3339().to_string(),
+        };
+        format!("from {} to {}", time(self.valid_after), time(self.valid_before))
+    }
+}
+
+/// The current time, as certificates give their validity
+pub fn now() -> u64 {
+    time::now_utc().to_timespec().sec as u64
+}
+
+/// A packed list of strings, as principals are given
+fn names(packed: &[u8]) -> Option<Vec<String>> {
+    let mut reader = Reader(packed);
+    let mut names = vec![];
+    while !reader.0.is_empty() {
+        names.push(String::from_utf8(reader.string()?.to_vec()).ok()?);
+    }
+    Some(names)
+}
+
+/// The names of a packed list of options, each a name followed by its data
+fn options(packed: &[u8]) -> Option<Vec<String>> {
+    let mut reader = Reader(packed);
+    let mut names = vec![];
+    while !reader.0.is_empty() {
+        names.push(String::from_utf8(reader.string()?.to_vec()).ok()?);
+        reader.string()?;
+    }
+    Some(names)
+}
+
+/// Reads the SSH wire encoding
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut raw = [0u8; 4];
+        raw.copy_from_slice(self.take(4)?);
+        Some(u32::from_be_bytes(raw))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(raw))
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
@@ -0,0 +1,91 @@
+//! Signature chains: a notarization trail appended to a signed envelope
+//!
+//! The first link signs the digest of the envelope's signed payload and
+//! signature. Every later link signs the digest of the link before it, so
+//! links can neither be reordered nor removed from the middle of the chain.
+
+use base64::{decode, encode};
+use hex::encode as hex_encode;
+use openssl::sha::Sha256;
+use thrussh_keys::{key::KeyPair, parse_public_key_base64, signature::Signature, PublicKeyBase64};
+
+use crate::envelope::{ChainLink, SignIt};
+use crate::keys::fingerprint;
+
+const LINK_PREFIX: &[u8] = b"SIGNIT-CHAIN-V1\n";
+
+/// A verified link of the chain
+pub struct Notary {
+    pub fingerprint: String,
+    pub github_user: Option<String>,
+}
+
+/// Append a link signed by `secret` to the end of the envelope's chain
+pub fn append(env: &mut SignIt, secret: &KeyPair, github_user: Option<String>) -> Result<(), String> {
+    let mut digest = root_digest(env)?;
+    for link in &env.chain {
+        digest = link_digest(&digest, link)?;
+    }
+
+    let sig = match secret.sign_detached(&link_payload(&digest)) {
+        Ok(Signature::Ed25519(sig)) => sig,
+        _ => return Err("Specified or detected key was not an Ed25519 key!".into()),
+    };
+
+    env.chain.push(ChainLink {
+        public_key: secret.public_key_base64().replace(|c: char| c.is_whitespace(), ""),
+        signature: encode(&sig.0[..]),
+        github_user,
+    });
+    Ok(())
+}
+
+/// Check every link of the chain, returning the notaries in order
+///
+/// This only proves the integrity of the chain. Whether the embedded keys are
+/// trusted is up to the caller.
+pub fn verify(env: &SignIt) -> Result<Vec<Notary>, String> {
+    let mut digest = root_digest(env)?;
+    let mut notaries = vec![];
+
+    for (i, link) in env.chain.iter().enumerate() {
+        let key = parse_public_key_base64(&link.public_key)
+            .map_err(|e| format!("Chain link {} has an invalid public key\nError: {:?}", i + 1, e))?;
+        let sig = decode(&link.signature)
+            .map_err(|_| format!("Chain link {} signature not proper base64!", i + 1))?;
+
+        if !key.verify_detached(&link_payload(&digest), &sig) {
+            return Err(format!("Chain link {} failed verification!", i + 1));
+        }
+
+        notaries.push(Notary {
+            fingerprint: fingerprint(&key),
+            github_user: link.github_user.clone(),
+        });
+        digest = link_digest(&digest, link)?;
+    }
+
+    Ok(notaries)
+}
+
+fn root_digest(env: &SignIt) -> Result<[u8; 32], String> {
+    let sig = decode(&env.signer.signature).map_err(|_| "Signature not proper base64!")?;
+    let mut hasher = Sha256::new();
+    hasher.update(&env.signed_bytes());
+    hasher.update(&sig);
+    Ok(hasher.finish())
+}
+
+fn link_digest(prev: &[u8; 32], link: &ChainLink) -> Result<[u8; 32], String> {
+    let sig = decode(&link.signature).map_err(|_| "Chain signature not proper base64!")?;
+    let mut hasher = Sha256::new();
+    hasher.update(prev);
+    hasher.update(&sig);
+    Ok(hasher.finish())
+}
+
+fn link_payload(prev: &[u8; 32]) -> Vec<u8> {
+    let mut out = LINK_PREFIX.to_vec();
+    out.extend(hex_encode(prev).as_bytes());
+    out
+}
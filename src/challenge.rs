@@ -0,0 +1,76 @@
+//! Logging in with an SSH key, for `signit challenge` and `signit respond`
+//!
+//! A server issues a challenge: a random nonce, the context it is for, such as
+//! `login:example.com`, and when it expires. The client answers with an
+//! envelope over the context in namespace `signit-challenge`, with the nonce
+//! as its nonce, so the answer covers both and is no good for any other
+//! challenge or site. The server checks it against the challenge it kept.
+//!
+//! Accepting each challenge only once is left to the server, which is the
+//! only one that knows which it has seen.
+
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{self, SignIt, Signer};
+
+/// Namespace of responses, so a response cannot pass for any other signature
+pub const NAMESPACE: &str = "signit-challenge";
+
+#[derive(Serialize, Deserialize)]
+pub struct Challenge {
+    pub nonce: String,
+    pub context: String,
+    pub expires: String,
+}
+
+impl Challenge {
+    /// A challenge for `context` that expires in `lifetime` seconds
+    pub fn new(context: &str, lifetime: i64) -> Self {
+        let mut nonce = [0u8; 32];
+        openssl::rand::rand_bytes(&mut nonce).unwrap();
+        let expires = time::now_utc() + time::Duration::seconds(lifetime);
+        Challenge {
+            nonce: hex::encode(nonce),
+            context: context.to_string(),
+            expires: expires.rfc3339().to_string(),
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        serde_json::from_str(raw).map_err(|e| format!("Not a challenge\nError: {:?}", e))
+    }
+
+    pub fn check_expiry(&self) -> Result<(), String> {
+        if time::now_utc() > envelope::parse_timestamp(&self.expires)? {
+            return Err(format!("Challenge expired at {}", self.expires));
+        }
+        Ok(())
+    }
+
+    /// The response to sign
+    pub fn response(&self) -> SignIt {
+        SignIt {
+            message: self.context.clone(),
+            signer: Signer {
+                namespace: Some(NAMESPACE.to_string()),
+                nonce: Some(self.nonce.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Check that `response` answers this challenge, leaving its signature to the caller
+    pub fn check(&self, response: &SignIt) -> Result<(), String> {
+        if response.signer.namespace.as_deref() != Some(NAMESPACE) {
+            return Err(format!("Response is not in the {} namespace", NAMESPACE));
+        }
+        if response.signer.nonce.as_deref() != Some(&self.nonce) {
+            return Err("Response is for another challenge".to_string());
+        }
+        if response.message != self.context {
+            return Err(format!("Response is for {:?}, not {:?}", response.message, self.context));
+        }
+        self.check_expiry()
+    }
+}
@@ -0,0 +1,75 @@
+//! System clipboard access through the platform's command line tools
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Candidate commands to copy with, in order of preference
+fn copy_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![("clip.exe", &[])]
+    } else {
+        vec![
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    }
+}
+
+/// Candidate commands to paste with, in order of preference
+fn paste_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![("powershell.exe", &["-NoProfile", "-Command", "Get-Clipboard -Raw"])]
+    } else {
+        vec![
+            ("wl-paste", &["--no-newline"]),
+            ("xclip", &["-selection", "clipboard", "-out"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    }
+}
+
+/// Place `contents` on the clipboard, using the first tool that works
+pub fn copy(contents: &str) -> Result<(), String> {
+    for (program, args) in copy_commands() {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+
+        let written = child.stdin.take().unwrap().write_all(contents.as_bytes());
+        if written.is_ok() && child.wait().map(|s| s.success()).unwrap_or(false) {
+            return Ok(());
+        }
+    }
+
+    Err(format!("No clipboard tool found, tried: {}", tried(copy_commands())))
+}
+
+/// Read the clipboard, using the first tool that works
+pub fn paste() -> Result<String, String> {
+    for (program, args) in paste_commands() {
+        let output = Command::new(program).args(args).stderr(Stdio::null()).output();
+        if let Ok(output) = output {
+            if output.status.success() {
+                return String::from_utf8(output.stdout).map_err(|_| "Clipboard does not hold text!".to_string());
+            }
+        }
+    }
+
+    Err(format!("No clipboard tool found, tried: {}", tried(paste_commands())))
+}
+
+fn tried(commands: Vec<(&'static str, &'static [&'static str])>) -> String {
+    commands.iter().map(|(program, _)| *program).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,76 @@
+//! Comparing what two signature files cover, for `signit compare`
+//!
+//! Like `show`, nothing here verifies a signature. Signers are listed as they
+//! identify themselves, so approvals gathered from different people can be
+//! reconciled before checking them with `verify`.
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::envelope::{self, Detached, Signer};
+
+/// The signed content of one file, and who claims to have signed it
+pub struct Signed {
+    kind: &'static str,
+
+    /// `sha256:` digest of the message, or the digest a detached signature holds
+    pub digest: String,
+    signers: Vec<String>,
+}
+
+/// Read an envelope, bundle or detached signature
+pub fn load(raw: &str) -> Result<Signed, String> {
+    let value: Value = serde_json::from_str(raw)
+        .map_err(|e| format!("Not a JSON document\nError: {:?}", e))?;
+
+    if value.get("digest").is_some() {
+        let detached: Detached = serde_json::from_value(value)
+            .map_err(|e| format!("Failed to parse detached signature\nError: {:?}", e))?;
+        return Ok(Signed {
+            kind: "detached signature",
+            digest: detached.digest,
+            signers: vec![describe(&detached.signer)],
+        });
+    }
+
+    let kind = if value.get("signatures").is_some() { "bundle" } else { "envelope" };
+    let envelopes = envelope::parse_envelopes(raw)
+        .map_err(|e| format!("Failed to parse envelope\nError: {:?}", e))?;
+    let first = envelopes.first().ok_or("Bundle holds no signatures")?;
+
+    Ok(Signed {
+        kind,
+        digest: envelope::digest(first.message.as_bytes()).unwrap(),
+        signers: envelopes.iter().map(|env| describe(&env.signer)).collect(),
+    })
+}
+
+/// List the signers of both files, and whether they cover the same content
+pub fn report(names: [&str; 2], files: [&Signed; 2]) -> String {
+    let mut out = String::new();
+    for (name, file) in names.iter().zip(files.iter()) {
+        writeln!(out, "{}: {}, {}", name, file.kind, file.digest).unwrap();
+        for signer in &file.signers {
+            writeln!(out, "  {}", signer).unwrap();
+        }
+    }
+    out.trim_end().to_string()
+}
+
+fn describe(signer: &Signer) -> String {
+    let mut parts = vec![];
+    if let Some(user) = &signer.github_user {
+        parts.push(format!("github {}", user));
+    }
+    if let Some(key) = &signer.key {
+        parts.push(format!("key {}", key.fingerprint));
+    }
+    if parts.is_empty() {
+        parts.push("unidentified signer".to_string());
+    }
+    if let Some(namespace) = &signer.namespace {
+        parts.push(format!("namespace {}", namespace));
+    }
+    parts.join(", ")
+}
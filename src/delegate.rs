@@ -0,0 +1,92 @@
+//! Delegating signing to another key, for `signit ca issue`
+//!
+//! A delegation is an envelope over a statement naming the delegated key, the
+//! one namespace it may sign in, and when that ends, in namespace
+//! `signit-delegation`. The delegated key attaches it to its signatures with
+//! `sign --delegation`, and verifiers that trust the delegating key accept them
+//! until it expires, so CI jobs and build machines can sign without holding
+//! a long-lived key.
+
+use base64::decode;
+use thrussh_keys::key::PublicKey;
+
+use crate::envelope::{self, SignIt, Signer};
+use crate::keys;
+
+/// Namespace of delegations, so a delegation cannot pass for any other signature
+pub const NAMESPACE: &str = "signit-delegation";
+
+const HEADER: &str = "signit delegation";
+
+/// What a delegation allows
+pub struct Delegation {
+    pub key: PublicKey,
+    pub namespace: String,
+    pub expires: String,
+}
+
+/// The message of a statement delegating `namespace` to `key` until `expires`
+pub fn statement(key: &PublicKey, namespace: &str, expires: &str) -> String {
+    format!(
+        "{}\nkey: {} {}\nnamespace: {}\nexpires: {}\n",
+        HEADER,
+        keys::fingerprint(key),
+        keys::openssh_line(key),
+        namespace,
+        expires,
+    )
+}
+
+/// The unsigned delegation of `namespace` to `key` for `lifetime` seconds
+pub fn new(key: &PublicKey, namespace: &str, lifetime: i64) -> SignIt {
+    let expires = (time::now_utc() + time::Duration::seconds(lifetime)).rfc3339().to_string();
+    SignIt {
+        message: statement(key, namespace, &expires),
+        signer: Signer {
+            namespace: Some(NAMESPACE.to_string()),
+            timestamp: Some(envelope::timestamp()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+impl Delegation {
+    /// Read what `delegation` allows, leaving its signature to the caller
+    pub fn parse(delegation: &SignIt) -> Result<Self, String> {
+        if delegation.signer.namespace.as_deref() != Some(NAMESPACE) {
+            return Err(format!("Not a delegation, it is not in the {} namespace", NAMESPACE));
+        }
+        let mut lines = delegation.message.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("Not a delegation".to_string());
+        }
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .ok_or_else(|| format!("Delegation has no {}", name))
+        };
+        // The fingerprint is there for people to read, and is checked by rebuilding the statement
+        let key = field("key")?.split_once(' ').and_then(|(_, line)| keys::parse_openssh_line(line)).ok_or("Delegated key is invalid")?;
+        let namespace = field("namespace")?.to_string();
+        let expires = field("expires")?.to_string();
+        let parsed = Delegation { key, namespace, expires };
+        if delegation.message != statement(&parsed.key, &parsed.namespace, &parsed.expires) {
+            return Err("Delegation is malformed".to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// Check that one of `trusted` made `delegation`, and that it has not expired, give or take `skew`
+    pub fn verify(delegation: &SignIt, trusted: &[PublicKey], skew: time::Duration) -> Result<Self, String> {
+        let parsed = Self::parse(delegation)?;
+        let sig = decode(&delegation.signer.signature).map_err(|_| "Delegation signature not proper base64!")?;
+        let delegator = keys::find_key(&delegation.signed_bytes(), &sig, trusted)
+            .ok_or("Delegation is not signed by a trusted key!")?;
+        if time::now_utc() - skew > envelope::parse_timestamp(&parsed.expires)? {
+            return Err(format!("Delegation by {} expired at {}!", keys::fingerprint(delegator), parsed.expires));
+        }
+        Ok(parsed)
+    }
+}
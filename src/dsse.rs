@@ -0,0 +1,56 @@
+//! Dead Simple Signing Envelopes, for `signit dsse`
+//!
+//! An envelope holds the payload in base64 with its `payloadType`, and any
+//! number of signatures over the pre-authentication encoding of the two, so a
+//! payload cannot be passed off as one of another type. in-toto attestations
+//! are DSSE envelopes with type `application/vnd.in-toto+json`.
+
+use base64::{decode, encode};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Envelope {
+    pub payload: String,
+
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+
+    pub signatures: Vec<Signature>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Signature {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub keyid: String,
+    pub sig: String,
+}
+
+impl Envelope {
+    pub fn new(payload_type: &str, payload: &[u8]) -> Self {
+        Envelope {
+            payload: encode(payload),
+            payload_type: payload_type.to_string(),
+            signatures: vec![],
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        serde_json::from_str(raw).map_err(|e| format!("Not a DSSE envelope\nError: {:?}", e))
+    }
+
+    pub fn payload(&self) -> Result<Vec<u8>, String> {
+        decode(&self.payload).map_err(|_| "Envelope payload is not base64".to_string())
+    }
+
+    /// The bytes every signature covers
+    pub fn signed_bytes(&self) -> Result<Vec<u8>, String> {
+        Ok(pae(&self.payload_type, &self.payload()?))
+    }
+}
+
+/// `DSSEv1 <len> <type> <len> <payload>`, lengths in ASCII decimal
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut out = format!("DSSEv1 {} {} {} ", payload_type.len(), payload_type, payload.len()).into_bytes();
+    out.extend_from_slice(payload);
+    out
+}
@@ -0,0 +1,45 @@
+//! Composing the message to sign in the user's editor
+
+use std::fs::read_to_string;
+use std::io::Write;
+use std::process::Command;
+
+/// Open `$VISUAL` or `$EDITOR` on an empty temporary file and return what was saved
+///
+/// The file is only readable by its owner, and is deleted again before returning.
+pub fn compose() -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad".into() } else { "vi".into() });
+
+    let mut file = tempfile::Builder::new()
+        .prefix("signit-")
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| format!("Failed to create temporary file\nError: {:?}", e))?;
+
+    // Editors such as `code --wait` come with arguments of their own
+    let mut words = editor.split_whitespace();
+    let status = Command::new(words.next().unwrap())
+        .args(words)
+        .arg(file.path())
+        .status()
+        .map_err(|e| format!("Failed to start editor {:?}\nError: {:?}", editor, e))?;
+    if !status.success() {
+        return Err(format!("Editor {:?} exited with {}, not signing", editor, status));
+    }
+
+    let message = read_to_string(file.path())
+        .map_err(|e| format!("Failed to read temporary file\nError: {:?}", e))?;
+
+    // Overwrite what was written before the file is unlinked
+    let _ = file.as_file_mut().write_all(&vec![0u8; message.len()]);
+    let _ = file.as_file_mut().sync_all();
+
+    if message.trim().is_empty() {
+        return Err("Message is empty, not signing".into());
+    }
+    Ok(message)
+}
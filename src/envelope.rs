@@ -0,0 +1,377 @@
+use openssl::sha::Sha256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+/// Prefix of the payload signed when the envelope carries more than a message
+///
+/// The prefix is followed by a compact JSON object of every signed field, with
+/// keys in sorted order. Envelopes that only contain a message sign the raw
+/// message bytes, which keeps signatures made by older versions of signit valid,
+/// unless the message itself starts with the prefix. Those sign the payload
+/// form too, so no raw message can pass for the payload of another envelope.
+pub const PAYLOAD_PREFIX: &[u8] = b"SIGNIT-PAYLOAD-V1\n";
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignIt {
+    pub message: String,
+
+    #[serde(flatten)]
+    pub signer: Signer,
+
+    /// Notarizations appended after signing, see `chain.rs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<ChainLink>,
+
+    /// Not covered by any signature, see `Hints`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hints: Hints,
+}
+
+/// Hints for moving an envelope around, such as where to fetch what it signs or its content type
+///
+/// No signature covers them, so tools may change them in transit without
+/// breaking any, and verifiers must not trust them.
+pub type Hints = BTreeMap<String, String>;
+
+/// Everything in an envelope that belongs to a single signature
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signer {
+    /// Covered by the signature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Covered by the signature, lets verifiers reject replayed signatures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+
+    /// Covered by the signature, when it was made, as claimed by the signer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+
+    /// Covered by the signature, who the signer claims to be, as `github:<user>` or `email:<address>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+
+    /// Covered by the signature, the signer's key as given by `sign --embed-key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<EmbeddedKey>,
+
+    /// Covered by the signature, `sign --meta` pairs such as the builder or commit
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+
+    /// Covered by the signature, set when `message` is itself an envelope or bundle
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub countersign: bool,
+
+    pub signature: String,
+
+    /// Not covered by the signature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_user: Option<String>,
+
+    /// Not covered by the signature, but signed by a CA, the key's OpenSSH certificate as given by `sign --certificate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
+
+    /// Not covered by the signature, but signed by the delegating key, as given by `sign --delegation`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegation: Option<Box<SignIt>>,
+}
+
+/// Public key of the signer, embedded in the envelope
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddedKey {
+    /// OpenSSH encoding, e.g. `ssh-ed25519 AAAA...`
+    pub public_key: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+
+    pub fingerprint: String,
+}
+
+/// One entry of a signature chain, signing the digest of the entry before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainLink {
+    /// OpenSSH base64 encoding of the key that made this link
+    pub public_key: String,
+
+    pub signature: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_user: Option<String>,
+}
+
+/// Several signatures over the same message
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Bundle {
+    pub message: String,
+    pub signatures: Vec<Signer>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hints: Hints,
+}
+
+/// A signature over the digest of data kept elsewhere, such as a build artifact
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Detached {
+    /// `sha256:` and the hex digest of the signed data, covered by the signature
+    ///
+    /// With `chunks`, this is `merkle-sha256:` and the root of the chunk hashes.
+    pub digest: String,
+
+    /// Not covered by the signature, but checked against `digest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Chunks>,
+
+    #[serde(flatten)]
+    pub signer: Signer,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hints: Hints,
+}
+
+/// Hashes of the fixed-size chunks of the signed data, see `merkle.rs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunks {
+    pub size: u64,
+
+    /// Length of the whole signed data
+    pub length: u64,
+
+    /// Hex SHA-256 of a zero byte followed by each chunk, in order
+    pub hashes: Vec<String>,
+}
+
+#[derive(PartialEq, Serialize)]
+struct SignedPayload<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<&'a EmbeddedKey>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "is_false")]
+    countersign: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl<'a> SignedPayload<'a> {
+    fn new(message: Option<&'a str>, digest: Option<&'a str>, signer: &'a Signer) -> Self {
+        SignedPayload {
+            message,
+            digest,
+            namespace: signer.namespace.as_deref(),
+            nonce: signer.nonce.as_deref(),
+            timestamp: signer.timestamp.as_deref(),
+            identity: signer.identity.as_deref(),
+            key: signer.key.as_ref(),
+            metadata: &signer.metadata,
+            countersign: signer.countersign,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let payload = serde_json::to_value(self).unwrap();
+        let mut out = PAYLOAD_PREFIX.to_vec();
+        out.extend(serde_json::to_vec(&payload).unwrap());
+        out
+    }
+}
+
+impl SignIt {
+    /// Whether `other` covers the same message and signed fields, apart from when it was made
+    ///
+    /// Either both or neither must have a timestamp, but what it says is ignored.
+    pub fn signs_same_as(&self, other: &SignIt) -> bool {
+        fn payload(env: &SignIt) -> SignedPayload<'_> {
+            let timestamp = env.signer.timestamp.as_ref().map(|_| "");
+            SignedPayload { timestamp, ..SignedPayload::new(Some(&env.message), None, &env.signer) }
+        }
+        payload(self) == payload(other)
+    }
+
+    /// The bytes that `signature` is computed over
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let payload = SignedPayload::new(Some(&self.message), None, &self.signer);
+
+        let legacy = serde_json::to_value(&payload).unwrap().as_object().map(|o| o.len()) == Some(1);
+        if legacy && !self.message.as_bytes().starts_with(PAYLOAD_PREFIX) {
+            return self.message.as_bytes().to_vec();
+        }
+        payload.to_bytes()
+    }
+}
+
+impl Detached {
+    /// The bytes that `signature` is computed over
+    ///
+    /// Unlike envelopes, this always uses the `SIGNIT-PAYLOAD-V1` form. Its
+    /// `digest` field sets it apart from the payload of any envelope, and
+    /// envelopes only sign raw messages that do not start with the prefix.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        SignedPayload::new(None, Some(&self.digest), &self.signer).to_bytes()
+    }
+}
+
+/// The current time, as `sign --timestamp` records it
+pub fn timestamp() -> String {
+    time::now_utc().rfc3339().to_string()
+}
+
+/// Parse a UTC time like `2024-01-31T12:00:00Z`, or a bare date for its midnight
+pub fn parse_timestamp(text: &str) -> Result<time::Tm, String> {
+    time::strptime(text, "%Y-%m-%dT%H:%M:%SZ")
+        .or_else(|_| time::strptime(text, "%Y-%m-%d"))
+        .map_err(|_| format!("{:?} is not a UTC time like 2024-01-31T12:00:00Z", text))
+}
+
+/// Parse a duration like `90d`, `12h`, `30m` or `45s`, or a bare number of seconds
+pub fn parse_duration(text: &str) -> Result<time::Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("{:?} is not a duration like 90d, 12h, 30m or 45s", text))?;
+    let unit = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("{:?} is not a duration like 90d, 12h, 30m or 45s", text)),
+    };
+
+    // `time::Duration` holds milliseconds, and panics on more than fit
+    number
+        .checked_mul(unit)
+        .filter(|seconds| *seconds <= i64::MAX / 1000)
+        .map(time::Duration::seconds)
+        .ok_or_else(|| format!("{:?} is too long a duration", text))
+}
+
+/// Parse a duration like `parse_duration`, except that a bare number is a number of days
+pub fn parse_age(text: &str) -> Result<time::Duration, String> {
+    if !text.trim().is_empty() && text.trim().bytes().all(|b| b.is_ascii_digit()) {
+        return parse_duration(&format!("{}d", text.trim()));
+    }
+    parse_duration(text)
+}
+
+/// Parse a `key=value` pair of `sign --meta` or `--hint`
+pub fn parse_pair(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{:?} is not a pair like key=value", text)),
+    }
+}
+
+/// Split a claimed identity into its source, `github` or `email`, and the name within it
+pub fn parse_identity(text: &str) -> Result<(&str, &str), String> {
+    match text.find(':') {
+        Some(split) if ["github", "email"].contains(&&text[..split]) && split + 1 < text.len() => {
+            Ok((&text[..split], &text[split + 1..]))
+        }
+        _ => Err(format!("{:?} is not an identity like github:<user> or email:<address>", text)),
+    }
+}
+
+/// The `sha256:<hex>` digest of everything `reader` produces
+pub fn digest(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(format!("sha256:{}", hex::encode(hasher.finish())))
+}
+
+impl Bundle {
+    pub fn into_envelopes(self) -> Vec<SignIt> {
+        let (message, hints) = (self.message, self.hints);
+        self.signatures
+            .into_iter()
+            .map(|signer| SignIt { message: message.clone(), signer, hints: hints.clone(), ..Default::default() })
+            .collect()
+    }
+}
+
+/// The canonical form of an envelope, bundle or detached signature
+///
+/// Compact UTF-8 JSON with fields in the order they are declared in this file
+/// and unset fields left out rather than written as `null`, so the same
+/// envelope is always the same bytes, whoever wrote it and wherever.
+pub fn canonical<T: Serialize>(envelope: &T) -> serde_json::Result<String> {
+    serde_json::to_string(envelope)
+}
+
+/// Rewrite an envelope, bundle or detached signature in its canonical form
+///
+/// Unknown fields are dropped, which is safe as they are never signed.
+pub fn canonicalize(raw: &str) -> serde_json::Result<String> {
+    rewrite(raw, |_| ())
+}
+
+/// Rewrite an envelope, bundle or detached signature in its canonical form, once `edit` has changed its hints
+pub fn rewrite(raw: &str, edit: impl FnOnce(&mut Hints)) -> serde_json::Result<String> {
+    let value: Value = serde_json::from_str(raw)?;
+    if value.get("digest").is_some() {
+        let mut detached: Detached = serde_json::from_value(value)?;
+        edit(&mut detached.hints);
+        canonical(&detached)
+    } else if value.get("signatures").is_some() {
+        let mut bundle: Bundle = serde_json::from_value(value)?;
+        edit(&mut bundle.hints);
+        canonical(&bundle)
+    } else {
+        let mut env: SignIt = serde_json::from_value(value)?;
+        edit(&mut env.hints);
+        canonical(&env)
+    }
+}
+
+/// The hints of an envelope, bundle or detached signature
+pub fn hints(raw: &str) -> serde_json::Result<Hints> {
+    let mut value: Value = serde_json::from_str(raw)?;
+    match value.get_mut("hints") {
+        Some(hints) => serde_json::from_value(hints.take()),
+        None => Ok(Hints::new()),
+    }
+}
+
+/// Parse either a single envelope or a bundle, as a list of envelopes
+pub fn parse_envelopes(raw: &str) -> Result<Vec<SignIt>, serde_json::Error> {
+    let value: Value = serde_json::from_str(raw)?;
+    if value.get("signatures").is_some() {
+        Ok(serde_json::from_value::<Bundle>(value)?.into_envelopes())
+    } else {
+        Ok(vec![serde_json::from_value(value)?])
+    }
+}
@@ -0,0 +1,26 @@
+//! Running a command on the verified message, for `signit verify --exec`
+
+use std::io::{ErrorKind, Write};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Run `command` through the platform shell with `input` piped to its stdin
+pub fn run(command: &str, input: &[u8]) -> Result<ExitStatus, String> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {:?}\nError: {:?}", command, e))?;
+
+    // A command may exit without reading all of its input, which is not ours to report
+    let written = child.stdin.take().unwrap().write_all(input);
+    match written {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+            let _ = child.kill();
+            return Err(format!("Failed to write to {:?}\nError: {:?}", command, e));
+        }
+        _ => {}
+    }
+
+    child.wait().map_err(|e| format!("Failed to run {:?}\nError: {:?}", command, e))
+}
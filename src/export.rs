@@ -0,0 +1,65 @@
+//! C and Rust source for verification vectors, for `signit export-c`
+//!
+//! A bootloader compiles in the public key, and checks the signature against
+//! the bytes it was made over. The digest of those bytes is exported as well,
+//! for devices that compare it before running a full ed25519 verification.
+
+use std::fmt::Write;
+
+pub const LANGUAGES: &[&str] = &["c", "rust"];
+
+/// What gets compiled in
+pub struct Vectors<'a> {
+    pub fingerprint: &'a str,
+    pub public_key: &'a [u8; 32],
+    pub signature: &'a [u8],
+    pub digest: &'a [u8; 32],
+    pub signed_len: usize,
+}
+
+/// Source declaring the vectors as constants named `<PREFIX>_...`
+pub fn source(language: &str, prefix: &str, vectors: &Vectors) -> String {
+    let prefix = prefix.to_uppercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let arrays = [
+        ("PUBLIC_KEY", "ed25519 public key", &vectors.public_key[..]),
+        ("SIGNATURE", "ed25519 signature over the signed bytes", vectors.signature),
+        ("DIGEST", "SHA-256 of the signed bytes", &vectors.digest[..]),
+    ];
+
+    let mut out = String::new();
+    match language {
+        "rust" => {
+            writeln!(out, "// Generated by `signit export-c` for {}", vectors.fingerprint).unwrap();
+            for (name, doc, bytes) in arrays.iter() {
+                writeln!(out, "\n/// The {}", doc).unwrap();
+                writeln!(out, "pub const {}_{}: [u8; {}] = [", prefix, name, bytes.len()).unwrap();
+                out.push_str(&hex_rows(bytes));
+                writeln!(out, "];").unwrap();
+            }
+            writeln!(out, "\n/// Length of the signed bytes").unwrap();
+            writeln!(out, "pub const {}_SIGNED_LEN: usize = {};", prefix, vectors.signed_len).unwrap();
+        }
+        _ => {
+            writeln!(out, "/* Generated by `signit export-c` for {} */", vectors.fingerprint).unwrap();
+            writeln!(out, "#include <stddef.h>\n#include <stdint.h>").unwrap();
+            for (name, doc, bytes) in arrays.iter() {
+                writeln!(out, "\n/* The {} */", doc).unwrap();
+                writeln!(out, "static const uint8_t {}_{}[{}] = {{", prefix, name, bytes.len()).unwrap();
+                out.push_str(&hex_rows(bytes));
+                writeln!(out, "}};").unwrap();
+            }
+            writeln!(out, "\n/* Length of the signed bytes */").unwrap();
+            writeln!(out, "static const size_t {}_SIGNED_LEN = {};", prefix, vectors.signed_len).unwrap();
+        }
+    }
+    out.trim_end().to_string()
+}
+
+fn hex_rows(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for row in bytes.chunks(8) {
+        let row: Vec<_> = row.iter().map(|b| format!("0x{:02x},", b)).collect();
+        writeln!(out, "    {}", row.join(" ")).unwrap();
+    }
+    out
+}
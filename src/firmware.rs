@@ -0,0 +1,135 @@
+//! Fixed-layout signature headers for firmware images, for `signit firmware`
+//!
+//! A bootloader cannot parse JSON, so the header is a flat little-endian
+//! struct it can read in place:
+//!
+//! ```text
+//! offset  size  field
+//!      0     8  magic, "SIGNITFW"
+//!      8     2  header version, 1
+//!     10     2  header length, 128
+//!     12     4  image length in bytes
+//!     16    32  SHA-256 of the image
+//!     48     8  key id, the first 8 bytes of the key's SHA256 fingerprint
+//!     56     8  reserved, zero
+//!     64    64  ed25519 signature over bytes 0..64 of the header
+//! ```
+//!
+//! The signature covers the header rather than the image, so a bootloader
+//! hashes the image once, compares it to the digest and then verifies 64
+//! bytes. The header goes before the image, or after it with `--append` for
+//! bootloaders that expect the image at the start of flash.
+
+use openssl::sha::sha256;
+
+pub const MAGIC: &[u8; 8] = b"SIGNITFW";
+pub const VERSION: u16 = 1;
+pub const HEADER_LEN: usize = 128;
+
+/// Bytes of the header covered by the signature
+const SIGNED_LEN: usize = 64;
+
+pub struct Header {
+    pub image_len: u32,
+    pub digest: [u8; 32],
+    pub key_id: [u8; 8],
+    pub signature: [u8; 64],
+}
+
+impl Header {
+    /// An unsigned header for `image`, to be signed by the key with raw public key `public`
+    pub fn new(image: &[u8], public: &[u8]) -> Result<Self, String> {
+        if image.len() > u32::MAX as usize {
+            return Err("Firmware images are limited to 4 GiB!".to_string());
+        }
+        Ok(Header {
+            image_len: image.len() as u32,
+            digest: sha256(image),
+            key_id: key_id(public),
+            signature: [0u8; 64],
+        })
+    }
+
+    /// The bytes of the header the signature is made over
+    pub fn signed_bytes(&self) -> [u8; SIGNED_LEN] {
+        let mut out = [0u8; SIGNED_LEN];
+        out[0..8].copy_from_slice(MAGIC);
+        out[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        out[10..12].copy_from_slice(&(HEADER_LEN as u16).to_le_bytes());
+        out[12..16].copy_from_slice(&self.image_len.to_le_bytes());
+        out[16..48].copy_from_slice(&self.digest);
+        out[48..56].copy_from_slice(&self.key_id);
+        out
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[..SIGNED_LEN].copy_from_slice(&self.signed_bytes());
+        out[SIGNED_LEN..].copy_from_slice(&self.signature);
+        out
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+        if u16_at(8) != VERSION {
+            return Err(format!("Unsupported firmware header version {}", u16_at(8)));
+        }
+        if u16_at(10) as usize != HEADER_LEN {
+            return Err(format!("Unexpected firmware header length {}", u16_at(10)));
+        }
+        if bytes[56..64].iter().any(|&b| b != 0) {
+            return Err("Reserved bytes of the firmware header are not zero".to_string());
+        }
+
+        let mut header = Header {
+            image_len: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            digest: [0u8; 32],
+            key_id: [0u8; 8],
+            signature: [0u8; 64],
+        };
+        header.digest.copy_from_slice(&bytes[16..48]);
+        header.key_id.copy_from_slice(&bytes[48..56]);
+        header.signature.copy_from_slice(&bytes[64..HEADER_LEN]);
+        Ok(header)
+    }
+}
+
+/// Split a signed image into its header and the image, whichever end the header is at
+pub fn split(signed: &[u8]) -> Result<(Header, &[u8]), String> {
+    if signed.len() < HEADER_LEN {
+        return Err("File is too short to hold a firmware header!".to_string());
+    }
+    let (header, image) = if signed.starts_with(MAGIC) {
+        (&signed[..HEADER_LEN], &signed[HEADER_LEN..])
+    } else if signed[signed.len() - HEADER_LEN..].starts_with(MAGIC) {
+        let (image, header) = signed.split_at(signed.len() - HEADER_LEN);
+        (header, image)
+    } else {
+        return Err("File has no firmware header at its start or end!".to_string());
+    };
+
+    let header = Header::parse(header)?;
+    if header.image_len as usize != image.len() {
+        return Err(format!("Header is for a {} byte image, but the image is {} bytes", header.image_len, image.len()));
+    }
+    if header.digest != sha256(image) {
+        return Err("Image does not match the digest in its header".to_string());
+    }
+    Ok((header, image))
+}
+
+/// The first 8 bytes of the SHA-256 of an ed25519 key's OpenSSH wire encoding
+///
+/// These are the first bytes `signit fingerprint` prints, base64 encoded.
+pub fn key_id(public: &[u8]) -> [u8; 8] {
+    let mut blob = vec![];
+    for part in [&b"ssh-ed25519"[..], public].iter() {
+        blob.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        blob.extend_from_slice(part);
+    }
+
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&sha256(&blob)[..8]);
+    id
+}
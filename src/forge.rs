@@ -0,0 +1,154 @@
+//! Keys published online, for `verify -g`, `--github-team`, `--launchpad` and `--keys-url`, and the gists of `signit prove`
+//!
+//! Forges publish each user's SSH keys as a plain list, one OpenSSH line per
+//! key, which is also valid `authorized_keys`. Only the ed25519 keys are
+//! used, and the lists go through `keycache`.
+
+use reqwest::header::{ACCEPT, AUTHORIZATION, USER_AGENT};
+use serde_json::Value;
+use signit::keys;
+use thrussh_keys::key::PublicKey;
+
+use crate::keycache;
+
+/// Environment variable holding the token `--github-team` lists members with
+const GITHUB_TOKEN: &str = "GITHUB_TOKEN";
+
+/// The ed25519 keys of a github user
+pub fn github_keys(user: &str) -> Result<Vec<PublicKey>, String> {
+    let body = keycache::fetch(&format!("https://github.com/{}.keys", user))?;
+    Ok(keys::parse_authorized_keys(&body))
+}
+
+/// The ed25519 keys of every member of a github team, given as `org/team-slug`
+///
+/// Team membership is only visible to organization members, so this needs a
+/// token with the `read:org` scope in `GITHUB_TOKEN`.
+pub fn github_team_keys(team: &str) -> Result<Vec<PublicKey>, String> {
+    let (org, slug) = match team.find('/') {
+        Some(split) => (&team[..split], &team[split + 1..]),
+        None => return Err(format!("{:?} is not a team, expected org/team-slug", team)),
+    };
+    let token = std::env::var(GITHUB_TOKEN)
+        .map_err(|_| format!("Listing team members needs a github token with read:org in {}", GITHUB_TOKEN))?;
+
+    let mut keys = vec![];
+    for member in github_team_members(org, slug, &token)? {
+        keys.extend(github_keys(&member)?);
+    }
+    Ok(keys)
+}
+
+/// The ed25519 keys of a Launchpad user, as used by Ubuntu developers
+pub fn launchpad_keys(user: &str) -> Result<Vec<PublicKey>, String> {
+    let body = keycache::fetch(&format!("https://launchpad.net/~{}/+sshkeys", user))?;
+    Ok(keys::parse_authorized_keys(&body))
+}
+
+/// Every ed25519 key in an `authorized_keys` document served over HTTPS
+pub fn url_keys(url: &str) -> Result<Vec<PublicKey>, String> {
+    if !url.starts_with("https://") {
+        return Err(format!("{:?} is not an https:// URL, keys must not be fetched in the clear", url));
+    }
+    let body = keycache::fetch(url)?;
+    Ok(keys::parse_authorized_keys(&body))
+}
+
+fn github_team_members(org: &str, slug: &str, token: &str) -> Result<Vec<String>, String> {
+    const PER_PAGE: usize = 100;
+
+    let client = reqwest::Client::new();
+    let mut members = vec![];
+    for page in 1.. {
+        let url = format!(
+            "https://api.github.com/orgs/{}/teams/{}/members?per_page={}&page={}",
+            org, slug, PER_PAGE, page
+        );
+        let mut response = client
+            .get(&url)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .header(USER_AGENT, "signit")
+            .send()
+            .map_err(|e| format!("{:?}", e))?;
+        let body: Value = response.json().map_err(|e| format!("{:?}", e))?;
+
+        if !response.status().is_success() {
+            let message = body.get("message").and_then(Value::as_str).unwrap_or("");
+            return Err(format!("Github answered {} for team {}/{}: {}", response.status(), org, slug, message));
+        }
+        let logins: Vec<String> = body
+            .as_array()
+            .ok_or("Github sent an unexpected list of team members")?
+            .iter()
+            .filter_map(|member| member.get("login").and_then(Value::as_str).map(String::from))
+            .collect();
+
+        let last = logins.len() < PER_PAGE;
+        members.extend(logins);
+        if last {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+/// The owner of a gist, given by id or URL, and the name and contents of each of its files
+pub fn gist(gist: &str) -> Result<(String, Vec<(String, String)>), String> {
+    let id = gist.trim_end_matches('/').rsplit('/').next().unwrap_or(gist);
+    let mut response = reqwest::Client::new()
+        .get(&format!("https://api.github.com/gists/{}", id))
+        .header(ACCEPT, "application/vnd.github+json")
+        .header(USER_AGENT, "signit")
+        .send()
+        .map_err(|e| format!("{:?}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Github answered {} for gist {}", response.status(), id));
+    }
+    let body: Value = response.json().map_err(|e| format!("{:?}", e))?;
+
+    let owner = body
+        .get("owner")
+        .and_then(|owner| owner.get("login"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Gist {} has no owner", id))?;
+    let files = body
+        .get("files")
+        .and_then(Value::as_object)
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|(name, file)| Some((name.clone(), file.get("content")?.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((owner.to_string(), files))
+}
+
+/// Publish a public gist of one file, returning its URL
+///
+/// This needs a token with the `gist` scope in `GITHUB_TOKEN`.
+pub fn create_gist(description: &str, name: &str, content: &str) -> Result<String, String> {
+    let token = std::env::var(GITHUB_TOKEN)
+        .map_err(|_| format!("Publishing a gist needs a github token with the gist scope in {}", GITHUB_TOKEN))?;
+    let gist = serde_json::json!({
+        "description": description,
+        "public": true,
+        "files": { name: { "content": content } },
+    });
+    let mut response = reqwest::Client::new()
+        .post("https://api.github.com/gists")
+        .header(ACCEPT, "application/vnd.github+json")
+        .header(AUTHORIZATION, format!("Bearer {}", token))
+        .header(USER_AGENT, "signit")
+        .json(&gist)
+        .send()
+        .map_err(|e| format!("{:?}", e))?;
+    let body: Value = response.json().map_err(|e| format!("{:?}", e))?;
+
+    if !response.status().is_success() {
+        let message = body.get("message").and_then(Value::as_str).unwrap_or("");
+        return Err(format!("Github answered {} when creating a gist: {}", response.status(), message));
+    }
+    body.get("html_url").and_then(Value::as_str).map(String::from).ok_or_else(|| "Github sent no gist URL".to_string())
+}
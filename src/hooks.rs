@@ -0,0 +1,122 @@
+//! Git hooks that sign commits and tags, for `signit hooks install`
+//!
+//! Signatures are stored as git notes under `refs/notes/signit`, attached to
+//! the commit or tag they sign. Each envelope's message is the raw git object,
+//! as printed by `git cat-file`, so it covers the tree, parents and author and
+//! does not rely on SHA-1 object ids.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// First line after the shebang of every hook we write, to recognize them later
+const MARKER: &str = "# Installed by `signit hooks install`";
+
+pub const HOOKS: &[&str] = &["post-commit", "pre-push"];
+
+/// Arguments passed to `signit sign` by the hooks
+pub struct Options {
+    pub private_key: Option<PathBuf>,
+    pub github: Option<String>,
+}
+
+/// Write the named hooks into the hooks directory of the repository at `repo`
+///
+/// Existing hooks that signit did not write are only replaced with `force`.
+pub fn install(repo: &Path, hooks: &[String], options: &Options, force: bool) -> Result<Vec<PathBuf>, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .current_dir(repo)
+        .output()
+        .map_err(|e| format!("Failed to run git\nError: {:?}", e))?;
+    if !output.status.success() {
+        return Err(format!("{:?} is not a git repository", repo));
+    }
+    let dir = repo.join(String::from_utf8_lossy(&output.stdout).trim());
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}\nError: {:?}", dir, e))?;
+
+    let mut written = vec![];
+    for hook in hooks {
+        let script = match hook.as_str() {
+            "post-commit" => post_commit(options),
+            "pre-push" => pre_push(options),
+            _ => return Err(format!("Unknown hook {:?}, expected one of {}", hook, HOOKS.join(", "))),
+        };
+
+        let path = dir.join(hook);
+        if let Ok(existing) = fs::read_to_string(&path) {
+            if !existing.contains(MARKER) && !force {
+                return Err(format!("{:?} already exists, use --force to replace it", path));
+            }
+        }
+
+        fs::write(&path, script).map_err(|e| format!("Failed to write {:?}\nError: {:?}", path, e))?;
+        make_executable(&path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn sign_command(options: &Options, namespace: &str) -> String {
+    let mut command = format!("signit sign -n {}", namespace);
+    if let Some(key) = &options.private_key {
+        command.push_str(&format!(" -k {}", quote(&key.to_string_lossy())));
+    }
+    if let Some(user) = &options.github {
+        command.push_str(&format!(" -g {}", quote(user)));
+    }
+    command
+}
+
+fn post_commit(options: &Options) -> String {
+    format!(
+        r#"#!/bin/sh
+{marker}
+# Signs every new commit, storing the signature as a note in refs/notes/signit
+commit=$(git rev-parse HEAD) || exit 1
+signature=$(git cat-file commit "$commit" | {sign}) || exit 1
+printf '%s\n' "$signature" | git notes --ref=signit add -f -F - "$commit"
+"#,
+        marker = MARKER,
+        sign = sign_command(options, "git-commit"),
+    )
+}
+
+fn pre_push(options: &Options) -> String {
+    format!(
+        r#"#!/bin/sh
+{marker}
+# Signs every tag being pushed, storing the signature as a note in refs/notes/signit
+while read local_ref local_sha remote_ref remote_sha; do
+    case "$local_ref" in
+        refs/tags/*)
+            # Deleted tags are pushed as an all zero object id
+            case "$local_sha" in *[!0]*) ;; *) continue ;; esac
+            kind=$(git cat-file -t "$local_sha") || exit 1
+            signature=$(git cat-file "$kind" "$local_sha" | {sign}) || exit 1
+            printf '%s\n' "$signature" | git notes --ref=signit add -f -F - "$local_sha" || exit 1
+            ;;
+    esac
+done
+"#,
+        marker = MARKER,
+        sign = sign_command(options, "git-tag"),
+    )
+}
+
+/// Quote for a POSIX shell
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make {:?} executable\nError: {:?}", path, e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,319 @@
+//! HTTP Message Signatures (RFC 9421) over requests, for `signit http`
+//!
+//! Requests are read and written as HTTP/1.1 text: the request line, header
+//! lines, a blank line and the body. Signing adds `Signature-Input` and
+//! `Signature` headers, and a `Content-Digest` of the body when it is covered.
+//! Only what ed25519 signatures need is supported: the derived components of
+//! a request, plain header fields, and the `created`, `expires`, `keyid`,
+//! `alg` and `nonce` parameters.
+
+use base64::{decode, encode};
+use openssl::sha::sha256;
+
+/// The `alg` parameter of ed25519 signatures
+pub const ALG: &str = "ed25519";
+
+/// Components signed when none are given, `content-digest` is added for requests with a body
+pub const DEFAULT_COMPONENTS: &[&str] = &["@method", "@authority", "@path", "@query"];
+
+pub struct Request {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+
+    /// Line ending of the request as read, kept when writing it out
+    newline: &'static str,
+}
+
+/// A signature read from a request
+pub struct Signature {
+    pub label: String,
+    pub params: Params,
+
+    /// The signature base, as rebuilt from the request
+    pub base: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// The signature parameters, as in `("@method" "@path");created=1618884473;keyid="..."`
+#[derive(Default)]
+pub struct Params {
+    pub components: Vec<String>,
+    pub created: Option<i64>,
+    pub expires: Option<i64>,
+    pub keyid: Option<String>,
+    pub alg: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl Request {
+    pub fn parse(raw: &[u8]) -> Result<Self, String> {
+        let (head, body, newline) = match find(raw, b"\r\n\r\n") {
+            Some(at) => (&raw[..at], &raw[at + 4..], "\r\n"),
+            None => match find(raw, b"\n\n") {
+                Some(at) => (&raw[..at], &raw[at + 2..], "\n"),
+                None => (raw, &b""[..], if find(raw, b"\r\n").is_some() { "\r\n" } else { "\n" }),
+            },
+        };
+        let head = std::str::from_utf8(head).map_err(|_| "Request head is not UTF-8")?;
+        let mut lines = head.lines();
+
+        let request_line = lines.next().ok_or("Request is empty")?;
+        let fields: Vec<&str> = request_line.split_whitespace().collect();
+        let (method, target, version) = match fields[..] {
+            [method, target, version] if version.starts_with("HTTP/") => (method, target, version),
+            _ => return Err(format!("{:?} is not a request line like GET /path HTTP/1.1", request_line)),
+        };
+
+        let mut headers = vec![];
+        for line in lines {
+            let colon = line.find(':').ok_or_else(|| format!("{:?} is not a header line", line))?;
+            headers.push((line[..colon].trim().to_string(), line[colon + 1..].trim().to_string()));
+        }
+
+        Ok(Request {
+            method: method.to_string(),
+            target: target.to_string(),
+            version: version.to_string(),
+            headers,
+            body: body.to_vec(),
+            newline,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = format!("{} {} {}{}", self.method, self.target, self.version, self.newline);
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{}: {}{}", name, value, self.newline));
+        }
+        out.push_str(self.newline);
+        let mut out = out.into_bytes();
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    pub fn has_body(&self) -> bool {
+        !self.body.is_empty()
+    }
+
+    /// Every value of the header `name`, combined as RFC 9421 section 2.1 asks
+    pub fn header(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self
+            .headers
+            .iter()
+            .filter(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        Some(values.join(", ")).filter(|_| !values.is_empty())
+    }
+
+    pub fn add_header(&mut self, name: &str, value: String) {
+        self.headers.push((name.to_string(), value));
+    }
+
+    /// Add `label` to the dictionary in header `name`, replacing one already there
+    fn set_member(&mut self, name: &str, label: &str, value: String) {
+        let mut members: Vec<String> = self
+            .header(name)
+            .map(|header| split_top_level(&header, ','))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|member| member.split('=').next().map(str::trim) != Some(label))
+            .collect();
+        members.push(format!("{}={}", label, value));
+        self.headers.retain(|(known, _)| !known.eq_ignore_ascii_case(name));
+        self.add_header(name, members.join(", "));
+    }
+
+    /// The `sha-256` `Content-Digest` of the body
+    pub fn content_digest(&self) -> String {
+        format!("sha-256=:{}:", encode(&sha256(&self.body)[..]))
+    }
+
+    /// The value of a component as RFC 9421 section 2 defines it
+    fn component(&self, name: &str) -> Result<String, String> {
+        let (scheme, authority, path_and_query) = self.target_parts()?;
+        let (path, query) = match path_and_query.find('?') {
+            Some(at) => (&path_and_query[..at], &path_and_query[at + 1..]),
+            None => (path_and_query.as_str(), ""),
+        };
+        let value = match name {
+            "@method" => self.method.clone(),
+            "@target-uri" => format!("{}://{}{}", scheme, authority, path_and_query),
+            "@authority" => authority,
+            "@scheme" => scheme,
+            "@request-target" => self.target.clone(),
+            "@path" => if path.is_empty() { "/".to_string() } else { path.to_string() },
+            "@query" => format!("?{}", query),
+            name if name.starts_with('@') => return Err(format!("Component {:?} is not supported", name)),
+            name => self.header(name).ok_or_else(|| format!("Request has no {} header to sign", name))?,
+        };
+        Ok(value)
+    }
+
+    /// The scheme, lower case authority and path with query, of the request target
+    ///
+    /// Origin-form targets take their authority from `Host`, and are taken to be `https`.
+    fn target_parts(&self) -> Result<(String, String, String), String> {
+        for scheme in &["https", "http"] {
+            if let Some(rest) = self.target.strip_prefix(&format!("{}://", scheme)) {
+                let at = rest.find('/').unwrap_or(rest.len());
+                return Ok((scheme.to_string(), rest[..at].to_lowercase(), rest[at..].to_string()));
+            }
+        }
+        let host = self.header("host").ok_or("Request has no Host header")?;
+        Ok(("https".to_string(), host.to_lowercase(), self.target.clone()))
+    }
+}
+
+impl Params {
+    /// The inner list serialization, which is also the value of `@signature-params`
+    pub fn serialize(&self) -> String {
+        let components: Vec<String> = self.components.iter().map(|c| format!("{:?}", c)).collect();
+        let mut out = format!("({})", components.join(" "));
+        if let Some(created) = self.created {
+            out.push_str(&format!(";created={}", created));
+        }
+        if let Some(expires) = self.expires {
+            out.push_str(&format!(";expires={}", expires));
+        }
+        if let Some(keyid) = &self.keyid {
+            out.push_str(&format!(";keyid={:?}", keyid));
+        }
+        if let Some(alg) = &self.alg {
+            out.push_str(&format!(";alg={:?}", alg));
+        }
+        if let Some(nonce) = &self.nonce {
+            out.push_str(&format!(";nonce={:?}", nonce));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let close = text.find(')').filter(|_| text.starts_with('(')).ok_or("Signature-Input is not an inner list")?;
+
+        let mut params = Params::default();
+        for item in text[1..close].split_whitespace() {
+            params.components.push(unquote(item).ok_or_else(|| format!("Component {} is not a plain string", item))?);
+        }
+        for param in text[close + 1..].split(';').skip(1) {
+            let (name, value) = match param.find('=') {
+                Some(at) => (param[..at].trim(), param[at + 1..].trim()),
+                None => return Err(format!("Parameter {:?} has no value", param)),
+            };
+            let number = || value.parse::<i64>().map_err(|_| format!("Parameter {} is not an integer", name));
+            let string = || unquote(value).ok_or_else(|| format!("Parameter {} is not a string", name));
+            match name {
+                "created" => params.created = Some(number()?),
+                "expires" => params.expires = Some(number()?),
+                "keyid" => params.keyid = Some(string()?),
+                "alg" => params.alg = Some(string()?),
+                "nonce" => params.nonce = Some(string()?),
+                // Unknown parameters are signed all the same, so they are only skipped here
+                _ => (),
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// The signature base of RFC 9421 section 2.5, given the serialized parameters
+pub fn signature_base(request: &Request, params: &Params, serialized: &str) -> Result<Vec<u8>, String> {
+    let mut base = String::new();
+    for (i, component) in params.components.iter().enumerate() {
+        if params.components[..i].contains(component) {
+            return Err(format!("Component {:?} is listed twice", component));
+        }
+        base.push_str(&format!("{:?}: {}\n", component, request.component(component)?));
+    }
+    base.push_str(&format!("\"@signature-params\": {}", serialized));
+    Ok(base.into_bytes())
+}
+
+/// Add the signature made by `sign` under `label`, covering `params.components`
+pub fn sign(request: &mut Request, label: &str, params: &Params, sign: impl Fn(&[u8]) -> [u8; 64]) -> Result<(), String> {
+    let serialized = params.serialize();
+    let signature = sign(&signature_base(request, params, &serialized)?);
+    request.set_member("Signature-Input", label, serialized);
+    request.set_member("Signature", label, format!(":{}:", encode(&signature[..])));
+    Ok(())
+}
+
+/// The signature under `label`, or the first one, with the base it should cover
+pub fn signature(request: &Request, label: Option<&str>) -> Result<Signature, String> {
+    let inputs = request.header("Signature-Input").ok_or("Request has no Signature-Input header")?;
+    let signatures = request.header("Signature").ok_or("Request has no Signature header")?;
+
+    let (label, serialized) = members(&inputs)
+        .into_iter()
+        .find(|(known, _)| label.map_or(true, |label| label == known))
+        .ok_or_else(|| format!("Request has no signature labelled {}", label.unwrap_or("")))?;
+    let signature = members(&signatures)
+        .into_iter()
+        .find(|(known, _)| *known == label)
+        .map(|(_, value)| value)
+        .ok_or_else(|| format!("Signature header has no signature labelled {}", label))?;
+    let signature = signature
+        .strip_prefix(':')
+        .and_then(|s| s.strip_suffix(':'))
+        .and_then(|s| decode(s).ok())
+        .ok_or_else(|| format!("Signature {} is not a byte sequence", label))?;
+
+    let params = Params::parse(&serialized)?;
+    if let Some(alg) = params.alg.as_deref().filter(|alg| *alg != ALG) {
+        return Err(format!("Signature {} uses {}, only {} is supported", label, alg, ALG));
+    }
+    if params.components.iter().any(|c| c == "content-digest") && request.header("content-digest") != Some(request.content_digest()) {
+        return Err("Content-Digest does not match the body!".to_string());
+    }
+    let base = signature_base(request, &params, &serialized)?;
+    Ok(Signature { label, params, base, signature })
+}
+
+/// The `label=value` members of a dictionary header, values kept as written
+fn members(header: &str) -> Vec<(String, String)> {
+    split_top_level(header, ',')
+        .into_iter()
+        .filter_map(|member| {
+            let at = member.find('=')?;
+            Some((member[..at].trim().to_string(), member[at + 1..].trim().to_string()))
+        })
+        .collect()
+}
+
+/// Split at `separator` outside of strings and inner lists
+fn split_top_level(text: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let (mut quoted, mut depth) = (false, 0);
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            c if c == separator && !quoted && depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
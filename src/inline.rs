@@ -0,0 +1,251 @@
+//! Signatures carried inside the signed document itself, for `--inline`
+//!
+//! Each format turns a document into the message that is signed, and places
+//! the signer back into the document so that it stays a single file. Verifying
+//! reverses this into an ordinary envelope, so every check `verify` makes on
+//! envelopes applies here as well.
+
+use base64::{decode, encode};
+use serde_json::Value;
+
+use crate::envelope::{SignIt, Signer};
+
+/// Names accepted by `--inline`
+pub const FORMATS: &[&str] = &["json", "comment", "front-matter", "email"];
+
+/// Property of a JSON document that holds its signature
+const JSON_PROPERTY: &str = "signature";
+
+/// Start of the comment line holding the signature, in YAML, TOML and INI files
+const COMMENT_PREFIX: &str = "# signit-signature: ";
+
+/// Key of the YAML front matter holding the signature, as a JSON flow mapping
+const FRONT_MATTER_KEY: &str = "signit-signature: ";
+
+/// Line that opens and closes YAML front matter
+const FRONT_MATTER_FENCE: &str = "---";
+
+/// Header of an RFC 822 message holding the signature, as base64 of the JSON
+///
+/// Base64 leaves no whitespace for mail servers to fold the header at.
+const EMAIL_HEADER: &str = "X-Signit-Signature";
+
+/// The message to sign for a document, leaving out any signature it already carries
+pub fn message(format: &str, document: &str) -> Result<String, String> {
+    match format {
+        "json" => {
+            let mut object = json_object(document)?;
+            object.remove(JSON_PROPERTY);
+            Ok(serde_json::to_string(&Value::Object(object)).unwrap())
+        }
+        "comment" => {
+            // The signature line is appended after a final newline, which has to be signed
+            let mut text = without_comment(document).0;
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            Ok(text)
+        }
+        "front-matter" => Ok(split_front_matter(document).body),
+        "email" => Ok(split_email(document).body),
+        _ => Err(unknown(format)),
+    }
+}
+
+/// The signed document, with the envelope's signer placed inside it
+///
+/// `document` is what `message` was made from, for formats that keep parts of
+/// it out of the signature.
+pub fn embed(format: &str, document: &str, signed: &SignIt, pretty: bool) -> Result<String, String> {
+    match format {
+        "json" => {
+            let mut object = json_object(&signed.message)?;
+            object.insert(JSON_PROPERTY.to_string(), serde_json::to_value(&signed.signer).unwrap());
+            Ok(if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&Value::Object(object)).unwrap())
+        }
+        "comment" => {
+            let signer = serde_json::to_string(&signed.signer).unwrap();
+            Ok(format!("{}{}{}", signed.message, COMMENT_PREFIX, signer))
+        }
+        "front-matter" => {
+            let front_matter = split_front_matter(document).front_matter;
+            let signer = serde_json::to_string(&signed.signer).unwrap();
+            Ok(format!(
+                "{fence}\n{}{}{}\n{fence}\n{}",
+                front_matter,
+                FRONT_MATTER_KEY,
+                signer,
+                signed.message,
+                fence = FRONT_MATTER_FENCE,
+            ))
+        }
+        "email" => {
+            let headers = split_email(document).headers;
+            let signer = encode(&serde_json::to_vec(&signed.signer).unwrap());
+            Ok(format!("{}{}: {}\n\n{}", headers, EMAIL_HEADER, signer, signed.message))
+        }
+        _ => Err(unknown(format)),
+    }
+}
+
+/// Split a signed document back into the envelope it was made from
+pub fn extract(format: &str, document: &str) -> Result<SignIt, String> {
+    match format {
+        "json" => {
+            let mut object = json_object(document)?;
+            let signer = object.remove(JSON_PROPERTY)
+                .ok_or(format!("Document has no {:?} property!", JSON_PROPERTY))?;
+            let signer: Signer = serde_json::from_value(signer)
+                .map_err(|e| format!("Failed to parse {:?} property\nError: {:?}", JSON_PROPERTY, e))?;
+
+            Ok(SignIt {
+                // Keys are sorted and whitespace dropped, as they were when signing
+                message: serde_json::to_string(&Value::Object(object)).unwrap(),
+                signer,
+                ..Default::default()
+            })
+        }
+        "comment" => {
+            let (message, signer) = without_comment(document);
+            let signer = signer.ok_or("Document has no signit-signature comment!")?;
+            let signer: Signer = serde_json::from_str(&signer)
+                .map_err(|e| format!("Failed to parse signit-signature comment\nError: {:?}", e))?;
+
+            Ok(SignIt { message, signer, ..Default::default() })
+        }
+        "front-matter" => {
+            let split = split_front_matter(document);
+            let signer = split.signer.ok_or("Document has no signit-signature in its front matter!")?;
+            let signer: Signer = serde_json::from_str(&signer)
+                .map_err(|e| format!("Failed to parse signit-signature front matter\nError: {:?}", e))?;
+
+            Ok(SignIt { message: split.body, signer, ..Default::default() })
+        }
+        "email" => {
+            let split = split_email(document);
+            let signer = split.signer.ok_or(format!("Message has no {} header!", EMAIL_HEADER))?;
+            let signer: Signer = decode(&signer)
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|json| serde_json::from_slice(&json).map_err(|e| format!("{:?}", e)))
+                .map_err(|e| format!("Failed to parse {} header\nError: {}", EMAIL_HEADER, e))?;
+
+            Ok(SignIt { message: split.body, signer, ..Default::default() })
+        }
+        _ => Err(unknown(format)),
+    }
+}
+
+fn json_object(document: &str) -> Result<serde_json::Map<String, Value>, String> {
+    match serde_json::from_str(document) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err("Only JSON objects can carry an inline signature!".to_string()),
+        Err(e) => Err(format!("Failed to parse JSON document\nError: {:?}", e)),
+    }
+}
+
+/// Split the signature comment line, if any, from the rest of the document
+fn without_comment(document: &str) -> (String, Option<String>) {
+    let mut text = String::new();
+    let mut signer = None;
+    for line in document.split_inclusive('\n') {
+        match line.strip_prefix(COMMENT_PREFIX) {
+            Some(json) => signer = Some(json.trim_end().to_string()),
+            None => text.push_str(line),
+        }
+    }
+    (text, signer)
+}
+
+struct FrontMatter {
+    /// Lines between the fences, other than the signature
+    front_matter: String,
+    signer: Option<String>,
+    body: String,
+}
+
+/// Split a Markdown document into its front matter and body, with `\n` line endings
+///
+/// Line endings are normalized so that a checkout on Windows still verifies.
+fn split_front_matter(document: &str) -> FrontMatter {
+    let document = document.replace("\r\n", "\n");
+    let mut split = FrontMatter { front_matter: String::new(), signer: None, body: String::new() };
+
+    let mut lines = document.split_inclusive('\n');
+    let opened = document.lines().next() == Some(FRONT_MATTER_FENCE);
+    let closed = opened && document.lines().skip(1).any(|l| l == FRONT_MATTER_FENCE);
+    if !closed {
+        split.body = document;
+        return split;
+    }
+
+    lines.next();
+    for line in lines.by_ref() {
+        if line.trim_end() == FRONT_MATTER_FENCE {
+            break;
+        }
+        match line.strip_prefix(FRONT_MATTER_KEY) {
+            Some(json) => split.signer = Some(json.trim_end().to_string()),
+            None => split.front_matter.push_str(line),
+        }
+    }
+    split.body = lines.collect();
+    split
+}
+
+struct Email {
+    /// Header lines other than the signature, each ending in `\n`
+    headers: String,
+    signer: Option<String>,
+    body: String,
+}
+
+/// Split an RFC 822 message into its headers and body, with `\n` line endings
+///
+/// Mail is sent with `\r\n` line endings and often stored with `\n`, so the
+/// body is normalized before it is signed or verified. Folded headers are
+/// unfolded, in case a mail server folds the signature header anyway.
+fn split_email(document: &str) -> Email {
+    let document = document.replace("\r\n", "\n");
+    let mut split = Email { headers: String::new(), signer: None, body: String::new() };
+
+    // A document that does not start with a header is all body
+    let has_headers = document
+        .split(':')
+        .next()
+        .map_or(false, |name| !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic()));
+    if !has_headers {
+        split.body = document;
+        return split;
+    }
+
+    let (head, body) = match document.find("\n\n") {
+        Some(end) => (&document[..end + 1], &document[end + 2..]),
+        None => (&document[..], ""),
+    };
+    split.body = body.to_string();
+
+    let mut fields: Vec<String> = vec![];
+    for line in head.split_inclusive('\n') {
+        match fields.last_mut() {
+            Some(field) if line.starts_with(' ') || line.starts_with('\t') => field.push_str(line),
+            _ => fields.push(line.to_string()),
+        }
+    }
+    for field in fields {
+        let (name, value) = field.split_at(field.find(':').unwrap_or(0));
+        if name.eq_ignore_ascii_case(EMAIL_HEADER) {
+            split.signer = Some(value[1..].split_whitespace().collect());
+        } else {
+            split.headers.push_str(&field);
+        }
+    }
+    split
+}
+
+fn unknown(format: &str) -> String {
+    format!("Unknown inline format {:?}, expected one of {}", format, FORMATS.join(", "))
+}
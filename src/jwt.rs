@@ -0,0 +1,113 @@
+//! JSON Web Tokens signed with EdDSA (RFC 8037), for `signit jwt`
+//!
+//! Tokens use the compact serialization, `header.claims.signature` in
+//! unpadded base64url, with `"alg":"EdDSA"` in the header. Verification only
+//! accepts that algorithm, so a token cannot pick a weaker one or `none`.
+
+use base64::{decode_config, encode_config, URL_SAFE_NO_PAD};
+use serde_json::{json, Map, Value};
+
+pub const ALG: &str = "EdDSA";
+
+pub struct Token {
+    pub header: Map<String, Value>,
+    pub claims: Map<String, Value>,
+
+    /// The `header.claims` part, which the signature covers
+    pub signed: String,
+    pub signature: Vec<u8>,
+}
+
+/// The signing input of a token with `kid` and `claims`, to be completed by `finish`
+pub fn signing_input(kid: &str, claims: &Map<String, Value>) -> String {
+    let header = json!({ "alg": ALG, "typ": "JWT", "kid": kid });
+    format!("{}.{}", encode_part(&header), encode_part(&Value::Object(claims.clone())))
+}
+
+pub fn finish(signing_input: &str, signature: &[u8; 64]) -> String {
+    format!("{}.{}", signing_input, encode_config(&signature[..], URL_SAFE_NO_PAD))
+}
+
+impl Token {
+    pub fn parse(token: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = token.trim().split('.').collect();
+        let (header, claims, signature) = match parts[..] {
+            [header, claims, signature] => (header, claims, signature),
+            _ => return Err("Not a compact JWT of three dot separated parts".to_string()),
+        };
+
+        let header = decode_part(header, "header")?;
+        if header.get("alg").and_then(Value::as_str) != Some(ALG) {
+            return Err(format!("Token header has alg {}, only {} is accepted", header.get("alg").unwrap_or(&Value::Null), ALG));
+        }
+        if let Some(crit) = header.get("crit") {
+            return Err(format!("Token header has critical extensions {}, which are not supported", crit));
+        }
+        let signature = decode_config(signature, URL_SAFE_NO_PAD).map_err(|_| "Token signature is not base64url")?;
+
+        Ok(Token {
+            header,
+            claims: decode_part(claims, "claims")?,
+            signed: token.trim()[..token.trim().rfind('.').unwrap()].to_string(),
+            signature,
+        })
+    }
+
+    /// Check `exp` and `nbf` against `now`, allowing `leeway` seconds of clock skew
+    pub fn check_time(&self, now: i64, leeway: i64) -> Result<(), String> {
+        if let Some(exp) = self.numeric("exp")? {
+            if now >= exp + leeway {
+                return Err(format!("Token expired at {}", exp));
+            }
+        }
+        if let Some(nbf) = self.numeric("nbf")? {
+            if now + leeway < nbf {
+                return Err(format!("Token is not valid before {}", nbf));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the `aud` claim, a string or array of strings, includes `audience`
+    pub fn check_audience(&self, audience: &str) -> Result<(), String> {
+        let matches = match self.claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(format!("Token is not for audience {}", audience))
+        }
+    }
+
+    pub fn check_issuer(&self, issuer: &str) -> Result<(), String> {
+        match self.claims.get("iss").and_then(Value::as_str) {
+            Some(iss) if iss == issuer => Ok(()),
+            _ => Err(format!("Token was not issued by {}", issuer)),
+        }
+    }
+
+    fn numeric(&self, claim: &str) -> Result<Option<i64>, String> {
+        match self.claims.get(claim) {
+            None => Ok(None),
+            Some(value) => value
+                .as_f64()
+                .map(|seconds| Some(seconds as i64))
+                .ok_or_else(|| format!("Token claim {} is not a number of seconds", claim)),
+        }
+    }
+}
+
+fn encode_part(value: &Value) -> String {
+    encode_config(serde_json::to_string(value).unwrap().as_bytes(), URL_SAFE_NO_PAD)
+}
+
+fn decode_part(part: &str, name: &str) -> Result<Map<String, Value>, String> {
+    let raw = decode_config(part, URL_SAFE_NO_PAD).map_err(|_| format!("Token {} is not base64url", name))?;
+    match serde_json::from_slice(&raw) {
+        Ok(Value::Object(object)) => Ok(object),
+        _ => Err(format!("Token {} is not a JSON object", name)),
+    }
+}
@@ -0,0 +1,94 @@
+//! A cache of fetched key lists, revalidated on every use
+//!
+//! Each response is stored with its `ETag` and `Last-Modified` headers, under
+//! `signit/keys` in the user's cache directory. Later fetches send them back as
+//! `If-None-Match` and `If-Modified-Since`, and use the stored body when the
+//! server answers `304 Not Modified`. The server is still asked every time, so
+//! a changed or removed key is picked up by the next verification, but an
+//! unchanged list costs no body and, on GitHub, does not count against the
+//! rate limit.
+//!
+//! The cache is best effort: if it cannot be read or written, keys are
+//! fetched as if it were empty.
+
+use std::fs;
+use std::path::PathBuf;
+
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+
+    body: String,
+}
+
+/// The body at `url`, from the cache if the server says it has not changed
+pub fn fetch(url: &str) -> Result<String, String> {
+    let path = entry_path(url);
+    let cached = path
+        .as_ref()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice::<Entry>(&raw).ok())
+        .filter(|entry| entry.url == url);
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(entry) = &cached {
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+        }
+    }
+
+    let mut response = request.send().map_err(|e| format!("{:?}", e))?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(entry) = cached {
+            return Ok(entry.body);
+        }
+    }
+    let body = response.text().map_err(|e| format!("{:?}", e))?;
+
+    // Errors are not cached, so that they are not served again on a 304
+    if response.status().is_success() {
+        if let Some(path) = path {
+            let entry = Entry {
+                url: url.to_string(),
+                etag: header(response.headers(), ETAG),
+                last_modified: header(response.headers(), LAST_MODIFIED),
+                body: body.clone(),
+            };
+            store(&path, &entry);
+        }
+    }
+    Ok(body)
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(String::from)
+}
+
+/// Entries are named by the SHA-256 of their URL
+fn entry_path(url: &str) -> Option<PathBuf> {
+    let name = hex::encode(openssl::sha::sha256(url.as_bytes()));
+    dirs::cache_dir().map(|dir| dir.join("signit").join("keys").join(format!("{}.json", name)))
+}
+
+fn store(path: &PathBuf, entry: &Entry) {
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, serde_json::to_vec(entry).unwrap()));
+    if let Err(e) = written {
+        eprintln!("Failed to cache keys at {:?}: {}", path, e);
+    }
+}
@@ -0,0 +1,326 @@
+use base64::{decode_config, encode_config, MIME, STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use openssl::sha::sha256;
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+use thrussh_keys::{key::{Named, PublicKey}, parse_public_key_base64, PublicKeyBase64};
+
+use crate::envelope::EmbeddedKey;
+
+/// Multibase encodings accepted by `--key-encoding`, each written after its one character prefix
+pub const MULTIBASES: &[&str] = &["base58btc", "base64url", "base16"];
+
+/// Multicodec prefix of an ed25519 public key, as in Multikey and `did:key`
+const ED25519_MULTICODEC: &[u8] = &[0xed, 0x01];
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// DER of an ed25519 SubjectPublicKeyInfo, up to the key itself
+const SPKI_PREFIX: &[u8] = &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// The OpenSSH style `SHA256:...` fingerprint of a public key
+pub fn fingerprint(key: &PublicKey) -> String {
+    let blob = decode_config(&key.public_key_base64(), MIME)
+        .expect("thrussh produced invalid base64");
+    blob_fingerprint(&blob)
+}
+
+/// The `SHA256:...` fingerprint of a key in the SSH wire encoding, of any type
+pub fn blob_fingerprint(blob: &[u8]) -> String {
+    format!("SHA256:{}", encode_config(&sha256(blob), STANDARD_NO_PAD))
+}
+
+/// The first of `keys` that made the ed25519 signature `sig` over `signed`
+pub fn find_key<'a>(signed: &[u8], sig: &[u8], keys: &'a [PublicKey]) -> Option<&'a PublicKey> {
+    keys.iter().find(|k| k.verify_detached(signed, sig))
+}
+
+/// The OpenSSH "drunken bishop" randomart of an ed25519 key's `SHA256:...` fingerprint
+///
+/// This is the box `ssh-keygen -lv` prints, meant for comparing keys by eye.
+pub fn randomart(fingerprint: &str) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+    let max = SYMBOLS.len() as u8 - 1;
+
+    let digest = decode_config(fingerprint.trim_start_matches("SHA256:"), STANDARD_NO_PAD)
+        .unwrap_or_default();
+    let mut field = [[0u8; HEIGHT]; WIDTH];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+
+    for byte in digest.iter() {
+        for step in 0..4 {
+            let bits = byte >> (step * 2);
+            x = if bits & 1 != 0 { (x + 1).min(WIDTH - 1) } else { x.saturating_sub(1) };
+            y = if bits & 2 != 0 { (y + 1).min(HEIGHT - 1) } else { y.saturating_sub(1) };
+            if field[x][y] < max - 2 {
+                field[x][y] += 1;
+            }
+        }
+    }
+    field[WIDTH / 2][HEIGHT / 2] = max - 1;
+    field[x][y] = max;
+
+    let border = |label: &str| {
+        let left = (WIDTH - label.len()) / 2;
+        format!("+{}{}{}+", "-".repeat(left), label, "-".repeat(WIDTH - left - label.len()))
+    };
+
+    let mut out = border("[ED25519 256]");
+    for row in 0..HEIGHT {
+        out.push_str("\n|");
+        out.extend((0..WIDTH).map(|col| SYMBOLS[field[col][row] as usize] as char));
+        out.push('|');
+    }
+    out.push('\n');
+    out.push_str(&border("[SHA256]"));
+    out
+}
+
+/// The `ssh-ed25519 AAAA...` line for a public key, without a comment
+pub fn openssh_line(key: &PublicKey) -> String {
+    let b64: String = key.public_key_base64().split_whitespace().collect();
+    format!("{} {}", key.name(), b64)
+}
+
+/// Parse a `ssh-ed25519 AAAA... [comment]` line, or bare base64
+pub fn parse_openssh_line(line: &str) -> Option<PublicKey> {
+    let mut split = line.split_whitespace();
+    let key = match (split.next(), split.next()) {
+        (Some(_), Some(key)) => key,
+        (Some(key), None) => key,
+        _ => return None,
+    };
+    parse_public_key_base64(key).ok()
+}
+
+/// Every ed25519 key in an `authorized_keys` style document
+///
+/// Lines may start with options such as `from="10.0.0.0/8",no-pty`, which
+/// are skipped, quotes included. Blank lines, comments, other key types and
+/// keys that fail to parse are ignored.
+pub fn parse_authorized_keys(text: &str) -> Vec<PublicKey> {
+    parse_commented_keys(text).into_iter().map(|(key, _)| key).collect()
+}
+
+/// Every ed25519 key in an `authorized_keys` style document, with the comment that follows it
+pub fn parse_commented_keys(text: &str) -> Vec<(PublicKey, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let fields = authorized_key_fields(line.trim());
+            let at = fields.iter().position(|f| *f == "ssh-ed25519")?;
+            let key = parse_public_key_base64(fields.get(at + 1)?).ok()?;
+            Some((key, fields[at + 2..].join(" ")))
+        })
+        .collect()
+}
+
+/// Split a line at whitespace outside double quotes, stopping at a comment
+pub fn authorized_key_fields(line: &str) -> Vec<&str> {
+    let mut fields = vec![];
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '#' if start.is_none() && !quoted => break,
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if let Some(s) = start.take() {
+                    fields.push(&line[s..i]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        fields.push(&line[s..]);
+    }
+    fields
+}
+
+/// Parse a public key file: an OpenSSH line, bare base64, or a raw 32 byte ed25519 key
+///
+/// Raw keys may be binary, base64 or hex, as micro-controller libraries tend
+/// to keep them.
+pub fn parse_public_key(contents: &[u8]) -> Option<PublicKey> {
+    if contents.len() == 32 {
+        return from_raw_public(contents);
+    }
+    let text = std::str::from_utf8(contents).ok()?.trim();
+    // Hex digits are valid base64 too, so both are tried
+    parse_openssh_line(text)
+        .or_else(|| from_multikey(text.strip_prefix("did:key:").unwrap_or(text)))
+        .or_else(|| from_raw_public(&decode_config(text, STANDARD).ok()?))
+        .or_else(|| from_raw_public(&hex::decode(text).ok()?))
+}
+
+/// Parse a public key file that may hold several keys, one OpenSSH line each
+///
+/// Files without any `ssh-ed25519` lines are read as a single key, in any of
+/// the forms `parse_public_key` accepts.
+pub fn parse_public_keys(contents: &[u8]) -> Vec<PublicKey> {
+    let listed = std::str::from_utf8(contents).map(parse_authorized_keys).unwrap_or_default();
+    if !listed.is_empty() {
+        return listed;
+    }
+    parse_public_key(contents).into_iter().collect()
+}
+
+/// An ed25519 key from its 32 raw bytes
+pub fn from_raw_public(raw: &[u8]) -> Option<PublicKey> {
+    if raw.len() != 32 {
+        return None;
+    }
+
+    // thrussh only builds keys from the SSH wire encoding
+    let mut blob = vec![];
+    for field in [&b"ssh-ed25519"[..], raw] {
+        blob.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        blob.extend_from_slice(field);
+    }
+    parse_public_key_base64(&encode_config(&blob, STANDARD)).ok()
+}
+
+/// Every `*.pub` file in `dir`, and in its subdirectories if `recursive`, sorted by path
+pub fn key_files(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                found.extend(key_files(&path, recursive)?);
+            }
+        } else if path.extension().map_or(false, |ext| ext == "pub") {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// The comment of the `.pub` file next to a private key, if there is one
+pub fn comment_for(private_key: &Path) -> Option<String> {
+    let mut pub_path = private_key.as_os_str().to_owned();
+    pub_path.push(".pub");
+
+    let contents = read_to_string(pub_path).ok()?;
+    let comment = contents.split_whitespace().skip(2).collect::<Vec<_>>().join(" ");
+    Some(comment).filter(|c| !c.is_empty())
+}
+
+/// The raw 32 byte ed25519 public key
+pub fn raw_ed25519(key: &PublicKey) -> Option<[u8; 32]> {
+    match key {
+        PublicKey::Ed25519(ed) => Some(ed.key),
+        _ => None,
+    }
+}
+
+/// The PEM encoded SubjectPublicKeyInfo of an ed25519 key, as written by openssl
+pub fn pem(key: &[u8; 32]) -> String {
+    let mut der = SPKI_PREFIX.to_vec();
+    der.extend_from_slice(key);
+    pem_wrap("PUBLIC KEY", &der)
+}
+
+/// The `did:key` identifier of an ed25519 key
+pub fn did_key(key: &[u8; 32]) -> String {
+    format!("did:key:{}", multikey(key, "base58btc"))
+}
+
+/// The Multikey encoding of an ed25519 key, `z6Mk...` in base58btc as DID documents hold it
+pub fn multikey(key: &[u8; 32], encoding: &str) -> String {
+    multibase(&[ED25519_MULTICODEC, &key[..]].concat(), encoding)
+}
+
+/// `data` in one of `MULTIBASES`, after the prefix naming it
+pub fn multibase(data: &[u8], encoding: &str) -> String {
+    match encoding {
+        "base64url" => format!("u{}", encode_config(data, URL_SAFE_NO_PAD)),
+        "base16" => format!("f{}", hex::encode(data)),
+        _ => format!("z{}", base58(data)),
+    }
+}
+
+/// Decode a multibase string in any of `MULTIBASES`
+pub fn multibase_decode(text: &str) -> Option<Vec<u8>> {
+    let mut chars = text.chars();
+    let prefix = chars.next()?;
+    let rest = chars.as_str();
+    match prefix {
+        'z' => base58_decode(rest),
+        'u' => decode_config(rest, URL_SAFE_NO_PAD).ok(),
+        'f' | 'F' => hex::decode(rest).ok(),
+        _ => None,
+    }
+}
+
+fn from_multikey(text: &str) -> Option<PublicKey> {
+    from_raw_public(multibase_decode(text)?.strip_prefix(ED25519_MULTICODEC)?)
+}
+
+/// Describe a key for embedding in an envelope
+pub fn embed(key: &PublicKey, comment: Option<String>) -> EmbeddedKey {
+    EmbeddedKey {
+        public_key: openssh_line(key),
+        comment,
+        fingerprint: fingerprint(key),
+    }
+}
+
+fn pem_wrap(label: &str, der: &[u8]) -> String {
+    let b64 = encode_config(der, STANDARD);
+    let mut out = format!("-----BEGIN {}-----\n", label);
+    for line in b64.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {}-----", label));
+    out
+}
+
+fn base58(data: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for &byte in data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat(b'1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    for c in text.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let zeros = text.bytes().take_while(|&c| c == b'1').count();
+    Some(std::iter::repeat(0).take(zeros).chain(bytes.into_iter().rev()).collect())
+}
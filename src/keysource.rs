@@ -0,0 +1,66 @@
+//! Where key files are read from, for `-k`
+//!
+//! Besides a path, a key may be given as `file:///path`, as mounted secrets
+//! are usually named, `fd:<n>` to read it from an inherited file descriptor,
+//! or `systemd-creds:<name>` for a credential a systemd unit passes with
+//! `LoadCredential=` or `LoadCredentialEncrypted=`. The last two never put
+//! the key at a path another process could guess.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+pub fn read(path: &Path) -> io::Result<Zeroizing<Vec<u8>>> {
+    let text = path.to_str().unwrap_or("");
+    if let Some(local) = text.strip_prefix("file://") {
+        return fs::read(local).map(Zeroizing::new);
+    }
+    if let Some(fd) = text.strip_prefix("fd:") {
+        let fd = match fd.parse::<i32>() {
+            // stdin, stdout and stderr would be closed once read
+            Ok(0..=2) => return Err(invalid(format!("fd:{} is stdin, stdout or stderr, pass the key on descriptor 3 or above", fd))),
+            Ok(n) if n > 2 => n,
+            _ => return Err(invalid(format!("{:?} is not a file descriptor number", fd))),
+        };
+        return read_fd(fd);
+    }
+    if let Some(name) = text.strip_prefix("systemd-creds:") {
+        // systemd decrypts credentials into a directory only the unit can read
+        if name.is_empty() || name.contains('/') {
+            return Err(invalid(format!("{:?} is not a credential name", name)));
+        }
+        let dir = std::env::var_os("CREDENTIALS_DIRECTORY")
+            .ok_or_else(|| invalid("CREDENTIALS_DIRECTORY is not set, is signit running as a systemd unit with LoadCredential=?".to_string()))?;
+        return fs::read(Path::new(&dir).join(name)).map(Zeroizing::new);
+    }
+    fs::read(path).map(Zeroizing::new)
+}
+
+#[cfg(unix)]
+fn read_fd(fd: i32) -> io::Result<Zeroizing<Vec<u8>>> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    // Only take ownership of a descriptor that is open, so closing it is ours to do
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        let e = io::Error::last_os_error();
+        return Err(io::Error::new(e.kind(), format!("fd:{} is not an open file descriptor: {}", fd, e)));
+    }
+
+    // The descriptor is closed once read, so the key cannot be read from it again
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    let mut contents = Zeroizing::new(vec![]);
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> io::Result<Zeroizing<Vec<u8>>> {
+    Err(io::Error::new(io::ErrorKind::Other, "Reading keys from a file descriptor is only available on unix"))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,90 @@
+//! A set of public keys parsed once, for verifying many envelopes against it
+//!
+//! Services that check thousands of envelopes a second against the same
+//! signers should not read and parse the keys for each one. A `KeyStore`
+//! holds them parsed, indexed by fingerprint, so an envelope that embeds its
+//! key is checked against that key first rather than against every key in
+//! turn, which keeps the cost of a lookup flat as the store grows.
+//!
+//! `signit verify-stream` is the command line path to the same thing, for
+//! services that would rather pipe envelopes through it.
+
+use std::collections::HashMap;
+
+use base64::decode;
+use thrussh_keys::key::PublicKey;
+
+use crate::envelope::SignIt;
+use crate::keys;
+
+pub struct KeyStore {
+    keys: Vec<PublicKey>,
+
+    /// Position of each key in `keys`, by fingerprint
+    index: HashMap<String, usize>,
+}
+
+impl KeyStore {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        let index = keys.iter().enumerate().map(|(at, key)| (keys::fingerprint(key), at)).collect();
+        KeyStore { keys, index }
+    }
+
+    /// A store of the keys in a public key file, in any form `keys::parse_public_keys` accepts
+    pub fn parse(contents: &[u8]) -> Self {
+        Self::new(keys::parse_public_keys(contents))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// The key with this `SHA256:...` fingerprint
+    pub fn get(&self, fingerprint: &str) -> Option<&PublicKey> {
+        self.index.get(fingerprint).map(|&at| &self.keys[at])
+    }
+
+    /// The key that made the ed25519 signature `sig` over `signed`, trying the key named by `hint` first
+    ///
+    /// The hint only orders the search, so a wrong or forged one costs time,
+    /// never a false match.
+    pub fn find(&self, signed: &[u8], sig: &[u8], hint: Option<&str>) -> Option<&PublicKey> {
+        if let Some(key) = hint.and_then(|fingerprint| self.get(fingerprint)) {
+            if key.verify_detached(signed, sig) {
+                return Some(key);
+            }
+        }
+        keys::find_key(signed, sig, &self.keys)
+    }
+
+    /// The key that signed `env`, if it is in the store
+    pub fn verify(&self, env: &SignIt) -> Option<&PublicKey> {
+        let sig = decode(&env.signer.signature).ok()?;
+        let hint = env.signer.key.as_ref().map(|key| key.fingerprint.as_str());
+        self.find(&env.signed_bytes(), &sig, hint)
+    }
+
+    /// The key that signed each of `envs`, verifying them across all cores
+    pub fn verify_batch<'a>(&'a self, envs: &[SignIt]) -> Vec<Option<&'a PublicKey>> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads == 1 || envs.len() < 2 * threads {
+            return envs.iter().map(|env| self.verify(env)).collect();
+        }
+        let chunk = (envs.len() + threads - 1) / threads;
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = envs
+                .chunks(chunk)
+                .map(|envs| scope.spawn(move || envs.iter().map(|env| self.verify(env)).collect::<Vec<_>>()))
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! Host keys, for `sign --host-key` and `verify --known-hosts`
+//!
+//! Machines attest to what they produced by signing with their SSH host key,
+//! which anyone who has logged into them already has in `known_hosts`. Lines
+//! marked `@revoked` withdraw a key from every host listed with it, and
+//! `@cert-authority` lines are ignored, as they list CAs rather than hosts.
+//!
+//! `--host` picks the entries of one host, matched as OpenSSH does: against
+//! comma separated patterns, which may use `*`, `?` and `!` to negate, or
+//! against the HMAC-SHA1 of the name that `HashKnownHosts` leaves instead.
+
+use base64::decode;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use thrussh_keys::key::PublicKey;
+use thrussh_keys::parse_public_key_base64;
+
+use crate::usage::glob_match;
+use signit::keys;
+
+/// Where OpenSSH keeps the ed25519 host key, readable only by root
+pub const HOST_KEY: &str = "/etc/ssh/ssh_host_ed25519_key";
+
+pub enum Marker {
+    CertAuthority,
+    Revoked,
+}
+
+pub struct Entry {
+    pub marker: Option<Marker>,
+
+    /// The hosts field as written, comma separated patterns or a `|1|salt|hash` hash
+    pub hosts: String,
+    pub key: PublicKey,
+}
+
+/// Every ed25519 line of a `known_hosts` file, skipping other key types
+pub fn parse(text: &str) -> Vec<Entry> {
+    text.lines()
+        .filter_map(|line| {
+            let fields = keys::authorized_key_fields(line.trim());
+            let (marker, fields) = match fields.first() {
+                Some(&"@cert-authority") => (Some(Marker::CertAuthority), &fields[1..]),
+                Some(&"@revoked") => (Some(Marker::Revoked), &fields[1..]),
+                _ => (None, &fields[..]),
+            };
+            match fields {
+                [hosts, "ssh-ed25519", key, ..] => Some(Entry {
+                    marker,
+                    hosts: hosts.to_string(),
+                    key: parse_public_key_base64(key).ok()?,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The entries of hosts, without those whose key is revoked
+pub fn host_keys(entries: Vec<Entry>) -> Vec<Entry> {
+    let revoked: Vec<String> = entries
+        .iter()
+        .filter(|entry| matches!(entry.marker, Some(Marker::Revoked)))
+        .map(|entry| keys::fingerprint(&entry.key))
+        .collect();
+    entries
+        .into_iter()
+        .filter(|entry| entry.marker.is_none() && !revoked.contains(&keys::fingerprint(&entry.key)))
+        .collect()
+}
+
+impl Entry {
+    /// Whether the entry is for `host`, written as `name` or `[name]:port` for other ports than 22
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        if let Some(hashed) = self.hosts.strip_prefix("|1|") {
+            let mut parts = hashed.splitn(2, '|').map(decode);
+            return match (parts.next(), parts.next()) {
+                (Some(Ok(salt)), Some(Ok(hash))) => hmac_sha1(&salt, host.as_bytes()).map_or(false, |mac| {
+                    mac.len() == hash.len() && memcmp::eq(&mac, &hash)
+                }),
+                _ => false,
+            };
+        }
+
+        // A negated pattern rules the host out, whatever else matches it
+        let mut matched = false;
+        for pattern in self.hosts.split(',').map(str::to_lowercase) {
+            match pattern.strip_prefix('!') {
+                Some(negated) if glob_match(negated, &host) => return false,
+                Some(_) => (),
+                None => matched |= glob_match(&pattern, &host),
+            }
+        }
+        matched
+    }
+
+    /// The hosts of the entry to show, which a hash does not give away
+    pub fn describe(&self) -> &str {
+        if self.hosts.starts_with("|1|") {
+            "a hashed host"
+        } else {
+            &self.hosts
+        }
+    }
+}
+
+fn hmac_sha1(key: &[u8], data: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+    signer.update(data)?;
+    signer.sign_to_vec()
+}
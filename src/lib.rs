@@ -0,0 +1,7 @@
+//! Reusable pieces of the `signit` command line tool
+
+pub mod envelope;
+pub mod keys;
+pub mod keystore;
+pub mod merkle;
+pub mod x25519;
@@ -0,0 +1,118 @@
+//! Limits on what `verify` reads, for `--max-envelope-size` and friends
+//!
+//! Once verify runs inside a server, the files it is handed can come from
+//! anyone. Envelopes and signature files are read up to a size limit and no
+//! further. Their JSON is checked for how deeply it nests before it is parsed.
+//! So a huge or deeply nested file fails verification instead of using up
+//! memory or the stack.
+
+use std::io::{self, BufRead, Read};
+
+use crate::progress;
+
+/// Default of `--max-envelope-size`, far more than any envelope of a message a person wrote
+pub const ENVELOPE_SIZE: &str = "64MiB";
+
+/// Default of `--max-depth`, which envelopes, nesting a few levels deep, never come near
+pub const DEPTH: &str = "64";
+
+/// Read all of `reader`, failing rather than reading more than `limit` bytes
+pub fn read(reader: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut contents = vec![];
+    reader.take(limit.saturating_add(1)).read_to_end(&mut contents)?;
+    if contents.len() as u64 > limit {
+        return Err(too_large(limit));
+    }
+    Ok(contents)
+}
+
+pub fn too_large(limit: u64) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("it is larger than {}", progress::size(limit)))
+}
+
+/// Fail if the JSON in `raw` nests arrays and objects more than `max` deep
+///
+/// This only counts brackets outside of strings, so it is cheap enough to
+/// run before parsing, and does not need `raw` to be valid JSON.
+pub fn check_depth(raw: &[u8], max: usize) -> Result<(), String> {
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for &byte in raw {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max {
+                    return Err(format!("JSON nests more than {} deep", max));
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The lines of `reader`, each at most `limit` bytes long
+///
+/// Unlike `BufRead::lines`, a line that is too long or not UTF-8 is an
+/// `InvalidData` error for that line alone, and the next line is read after it.
+pub struct Lines<R> {
+    reader: R,
+    limit: u64,
+}
+
+impl<R: BufRead> Lines<R> {
+    pub fn new(reader: R, limit: u64) -> Self {
+        Lines { reader, limit }
+    }
+
+    /// Skip the rest of a line that was too long, without holding it
+    fn skip_line(&mut self) -> io::Result<()> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            match buf.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    self.reader.consume(end + 1);
+                    return Ok(());
+                }
+                None if buf.is_empty() => return Ok(()),
+                None => {
+                    let len = buf.len();
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = vec![];
+        match (&mut self.reader).take(self.limit.saturating_add(1)).read_until(b'\n', &mut line) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        if line.len() as u64 > self.limit {
+            return Some(self.skip_line().and(Err(too_large(self.limit))));
+        }
+        Some(String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
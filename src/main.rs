@@ -1,156 +1,3756 @@
+mod agent;
+mod allowed;
+mod atrest;
+mod audit;
+mod cert;
+mod chain;
+mod challenge;
+mod clipboard;
+mod compare;
+mod delegate;
+mod dsse;
+mod editor;
+mod exec;
+mod export;
+mod firmware;
+mod forge;
+mod hooks;
+mod httpsig;
+mod inline;
+mod jwt;
+mod keycache;
+mod keyfile;
+mod keysource;
+mod knownhosts;
+mod limits;
+mod mmap;
+mod objstore;
+mod oci;
+mod paseto;
+mod pgp;
+mod policy;
+mod progress;
+mod proof;
+mod qr;
+mod revoke;
+mod rotate;
+mod sbom;
+mod seal;
+mod secret;
+mod show;
+mod syslog;
+mod usage;
+mod validate;
+mod warning;
+mod watch;
+
 use dirs::home_dir;
 use thrussh_keys::{
-    load_secret_key,
-    load_public_key,
-    parse_public_key_base64,
     signature::Signature,
     key::{KeyPair, PublicKey},
 };
+use zeroize::Zeroizing;
 use base64::{encode, decode};
 use structopt::StructOpt;
-use std::path::PathBuf;
-use std::fs::read_to_string;
-use serde_json;
-use serde::{Serialize, Deserialize};
-use reqwest;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::fs::read;
+use crate::envelope::{Bundle, Detached, SignIt, Signer};
+use crate::secret::SecretKey;
+use crate::usage::UsagePolicy;
+use signit::keystore::KeyStore;
+use signit::{envelope, keys, merkle, x25519};
+
+// Parsed once from the command line, so the size of its largest variant costs nothing
+#[allow(clippy::large_enum_variant)]
+#[derive(StructOpt)]
+enum Commands {
+    /// Sign a message using an ed25519 private key
+    #[structopt(name = "sign")]
+    Sign {
+        /// File to sign, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of signature, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Message to sign (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Compose the message to sign in $VISUAL or $EDITOR
+        #[structopt(long = "edit", raw(conflicts_with_all = r#"&["input", "message"]"#))]
+        edit: bool,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519", may be repeated to output a bundle
+        ///
+        /// Also accepts file:///path, fd:<n> and systemd-creds:<name>.
+        #[structopt(short = "k", long = "key", parse(from_os_str), number_of_values = 1)]
+        private_key: Vec<PathBuf>,
+
+        /// Read the private key from this inherited file descriptor, like -k fd:<n>
+        #[structopt(long = "key-fd", conflicts_with = "private_key")]
+        key_fd: Option<u32>,
+
+        /// Sign with this ssh-agent key, by fingerprint or comment, may be repeated
+        #[structopt(long = "agent-key", number_of_values = 1)]
+        agent_key: Vec<String>,
+
+        /// Sign with this machine's SSH host key, /etc/ssh/ssh_host_ed25519_key, which needs root
+        #[structopt(long = "host-key", raw(conflicts_with_all = r#"&["private_key", "key_fd", "agent_key"]"#))]
+        host_key: bool,
+
+        /// Github username to couple with json output
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+
+        /// Namespace the signature is valid for, covered by the signature
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Nonce to include in the signature, such as a login challenge
+        #[structopt(long = "nonce")]
+        nonce: Option<String>,
+
+        /// Include a randomly generated nonce in the signature
+        #[structopt(long = "random-nonce", conflicts_with = "nonce")]
+        random_nonce: bool,
+
+        /// Record the current time in the signature, so revoking the key later spares it
+        #[structopt(long = "timestamp")]
+        timestamp: bool,
+
+        /// Claim an identity in the signature, as github:<user> or email:<address>, for verify --require-claimed-identity
+        #[structopt(long = "identity")]
+        identity: Option<String>,
+
+        /// Add a key=value pair to the signed metadata, such as builder=ci or commit=<sha>, may be given more than once
+        #[structopt(long = "meta", number_of_values = 1, parse(try_from_str = "envelope::parse_pair"))]
+        meta: Vec<(String, String)>,
+
+        /// Add a key=value hint that the signature does not cover, such as location=<url>, may be given more than once
+        #[structopt(long = "hint", number_of_values = 1, parse(try_from_str = "envelope::parse_pair"))]
+        hint: Vec<(String, String)>,
+
+        /// Embed the public key, its comment and fingerprint in the signed output
+        #[structopt(long = "embed-key")]
+        embed_key: bool,
+
+        /// Attach the OpenSSH certificate of the key, such as ~/.ssh/id_ed25519-cert.pub, for `verify --trusted-ca`
+        #[structopt(long = "certificate", parse(from_os_str), conflicts_with = "raw")]
+        certificate: Option<PathBuf>,
+
+        /// Attach a delegation from `ca issue` that allows the key to sign in the namespace
+        #[structopt(long = "delegation", parse(from_os_str), conflicts_with = "raw", requires = "namespace")]
+        delegation: Option<PathBuf>,
+
+        /// Render the compact JSON output as a QR code, written as a PNG image with -o
+        #[structopt(long = "qr", conflicts_with = "pretty")]
+        qr: bool,
+
+        /// Copy the output to the system clipboard instead of printing it
+        #[structopt(long = "copy", raw(conflicts_with_all = r#"&["output", "qr"]"#))]
+        copy: bool,
+
+        /// Also publish the signature, printing where it can be fetched from
+        #[structopt(
+            long = "publish",
+            raw(possible_values = r#"&["ipfs"]"#),
+            raw(conflicts_with_all = r#"&["copy", "qr", "raw", "postcard", "inline"]"#)
+        )]
+        publish: Option<String>,
+
+        /// Sign a document in place, writing it out with the signature inside
+        #[structopt(long = "inline", raw(conflicts_with_all = r#"&["qr", "hint"]"#), raw(possible_values = "inline::FORMATS"))]
+        inline: Option<String>,
+
+        /// Output only the 64-byte ed25519 signature over the input, as base64 or binary
+        #[structopt(
+            long = "raw",
+            raw(possible_values = r#"&["base64", "binary"]"#),
+            raw(conflicts_with_all = r#"&["github", "namespace", "nonce", "random_nonce", "timestamp", "identity", "meta", "hint", "embed_key", "qr", "inline"]"#)
+        )]
+        raw: Option<String>,
+
+        /// Output the raw signature in this multibase encoding instead, as Data Integrity proofs hold it
+        #[structopt(long = "key-encoding", requires = "raw", conflicts_with = "copy", raw(possible_values = "keys::MULTIBASES"))]
+        key_encoding: Option<String>,
+
+        /// Output the binary envelope that `signit-core` verifies on devices without an allocator
+        #[structopt(
+            long = "postcard",
+            raw(conflicts_with_all = r#"&["github", "nonce", "random_nonce", "timestamp", "identity", "meta", "hint", "embed_key", "qr", "inline", "raw", "pretty", "copy"]"#)
+        )]
+        postcard: bool,
+
+        /// Output a detached signature over the input's digest, streaming it rather than holding it in memory
+        #[structopt(
+            long = "detached",
+            raw(conflicts_with_all = r#"&["qr", "inline", "raw", "postcard", "skip_existing"]"#)
+        )]
+        detached: bool,
+
+        /// Hash the input in chunks of this size, such as 1MiB, and sign their Merkle root
+        #[structopt(long = "chunk-size", requires = "detached", parse(try_from_str = "merkle::parse_size"))]
+        chunk_size: Option<u64>,
+
+        /// Memory-map the -i file to hash it, instead of reading it in chunks
+        #[structopt(long = "mmap", requires = "detached", raw(requires = r#""input""#))]
+        mmap: bool,
+
+        /// Leave -o alone if it already holds a valid signature by this key over the same message
+        #[structopt(
+            long = "skip-existing",
+            requires = "output",
+            raw(conflicts_with_all = r#"&["random_nonce", "qr", "copy", "inline", "raw", "postcard"]"#)
+        )]
+        skip_existing: bool,
+
+        /// Refuse to sign if the key may not sign the namespace under this JSON policy
+        #[structopt(long = "usage-policy", parse(from_os_str))]
+        usage_policy: Option<PathBuf>,
+
+        /// Append an NDJSON record of this operation to the given file
+        #[structopt(long = "audit-log", parse(from_os_str))]
+        audit_log: Option<PathBuf>,
+
+        /// Also stream the records of this operation to stderr in this format
+        #[structopt(long = "log-format", raw(possible_values = r#"&["ndjson"]"#))]
+        log_format: Option<String>,
+
+        /// Also send the records of this operation to the system log
+        #[structopt(long = "log-to", raw(possible_values = "syslog::TARGETS"))]
+        log_to: Option<String>,
+    },
+
+    /// Verify a stream of envelopes, one per line on stdin, writing one JSON result per line
+    #[structopt(name = "verify-stream")]
+    VerifyStream {
+        /// Path to ed25519 public keys, one per line, parsed once for the whole stream, may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1, required = true)]
+        public_key: Vec<PathBuf>,
+
+        /// Require every signature to be made for this namespace
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Verify this many envelopes at a time across all cores, instead of answering each line as it arrives
+        #[structopt(long = "batch")]
+        batch: Option<usize>,
+
+        /// Fail lines longer than this, such as 1MiB, without reading the rest of them into memory
+        #[structopt(long = "max-envelope-size", raw(default_value = "limits::ENVELOPE_SIZE"), parse(try_from_str = "merkle::parse_size"))]
+        max_envelope_size: u64,
+
+        /// Fail lines whose JSON nests arrays and objects more than this deep
+        #[structopt(long = "max-depth", raw(default_value = "limits::DEPTH"))]
+        max_depth: usize,
+    },
+
+    /// Verify a message using an ed25519 public key
+    #[structopt(name = "verify")]
+    Verify {
+        /// File to sign, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Message to verify (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Read the message to verify from the system clipboard
+        #[structopt(long = "paste", raw(conflicts_with_all = r#"&["input", "message"]"#))]
+        paste: bool,
+
+        /// Detached signature to verify -i against, from `watch` or a raw 64-byte signature
+        #[structopt(
+            short = "s",
+            parse(from_os_str),
+            raw(conflicts_with_all = r#"&["message", "paste", "chain", "inner_key", "trust_embedded"]"#)
+        )]
+        signature: Option<PathBuf>,
+
+        /// Memory-map the -i file to check it against -s, instead of reading it into memory
+        #[structopt(long = "mmap", requires = "signature", raw(requires = r#""input""#))]
+        mmap: bool,
+
+        /// Hash -i as it streams past to check it against -s, with progress, instead of reading it into memory
+        #[structopt(
+            long = "stream",
+            requires = "signature",
+            raw(conflicts_with_all = r#"&["mmap", "range", "output", "extract", "exec"]"#)
+        )]
+        stream: bool,
+
+        /// Verify only this byte range of data signed with `sign --chunk-size`, such as 1GiB..2GiB, given as -i
+        #[structopt(long = "range", requires = "signature", parse(try_from_str = "merkle::Range::parse"))]
+        range: Option<merkle::Range>,
+
+        /// Verify a document signed in place with `sign --inline`
+        #[structopt(long = "inline", conflicts_with = "signature", raw(possible_values = "inline::FORMATS"))]
+        inline: Option<String>,
+
+        /// Write the signed message to this file once verified, or - for stdout
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Write the signed message to stdout once verified, same as -o -
+        #[structopt(long = "extract", conflicts_with = "output")]
+        extract: bool,
+
+        /// Run this shell command with the signed message on its stdin, only once verified
+        #[structopt(long = "exec", raw(conflicts_with_all = r#"&["output", "extract"]"#))]
+        exec: Option<String>,
+
+        /// Path to ed25519 public keys, one per line, defaults to "$HOME/.ssh/id_ed25519.pub", overrides -g, may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Public key given in full, like "ssh-ed25519 AAAA... comment", may be repeated
+        #[structopt(short = "K", number_of_values = 1)]
+        key: Vec<String>,
+
+        /// Accept any key in the *.pub files of this directory, as with -k
+        #[structopt(
+            long = "keydir",
+            parse(from_os_str),
+            raw(conflicts_with_all = r#"&["github_team", "launchpad", "keys_url", "authorized_keys", "known_hosts"]"#)
+        )]
+        keydir: Option<PathBuf>,
+
+        /// Also load *.pub files from subdirectories of --keydir
+        #[structopt(long = "recursive", raw(requires = r#""keydir""#))]
+        recursive: bool,
+
+        /// Pull public keys from github
+        #[structopt(short = "g")]
+        github: bool,
+
+        /// Pull public keys from this github user, and fail if the signature claims anyone else
+        #[structopt(
+            long = "github",
+            raw(conflicts_with_all = r#"&["public_key", "key", "github", "github_team", "launchpad", "keys_url", "trust_embedded"]"#)
+        )]
+        github_user: Option<String>,
+
+        /// Accept a signature by any member of this github team, as org/team-slug, using $GITHUB_TOKEN
+        #[structopt(long = "github-team", raw(conflicts_with_all = r#"&["public_key", "key", "github"]"#))]
+        github_team: Option<String>,
+
+        /// Pull public keys from this Launchpad user
+        #[structopt(long = "launchpad", raw(conflicts_with_all = r#"&["public_key", "key", "github", "github_team"]"#))]
+        launchpad: Option<String>,
+
+        /// Pull public keys from an authorized_keys file served at this https:// URL
+        #[structopt(long = "keys-url", raw(conflicts_with_all = r#"&["public_key", "key", "github", "github_team", "launchpad"]"#))]
+        keys_url: Option<String>,
+
+        /// Accept any ed25519 key in this authorized_keys file, such as ~/.ssh/authorized_keys, may be repeated
+        #[structopt(
+            long = "authorized-keys",
+            parse(from_os_str),
+            number_of_values = 1,
+            raw(conflicts_with_all = r#"&["public_key", "key", "github", "github_team", "launchpad", "keys_url"]"#)
+        )]
+        authorized_keys: Vec<PathBuf>,
+
+        /// Accept the key of any host in this known_hosts file, such as ~/.ssh/known_hosts, may be repeated
+        #[structopt(
+            long = "known-hosts",
+            parse(from_os_str),
+            number_of_values = 1,
+            raw(conflicts_with_all = r#"&["public_key", "key", "github", "github_team", "launchpad", "keys_url", "authorized_keys"]"#)
+        )]
+        known_hosts: Vec<PathBuf>,
+
+        /// Only accept the key of this host in the --known-hosts files, as name or [name]:port
+        #[structopt(long = "host", raw(requires = r#""known_hosts""#))]
+        host: Option<String>,
+
+        /// Accept keys certified by this SSH CA, whose certificate the signature carries, may be repeated
+        #[structopt(
+            long = "trusted-ca",
+            parse(from_os_str),
+            number_of_values = 1,
+            raw(conflicts_with_all = r#"&["public_key", "key", "keydir", "github", "github_user", "github_team", "launchpad", "keys_url", "authorized_keys", "known_hosts", "policy", "trust_embedded"]"#)
+        )]
+        trusted_ca: Vec<PathBuf>,
+
+        /// Require the certificate to be valid for this principal, with --trusted-ca
+        #[structopt(long = "principal", raw(requires = r#""trusted_ca""#))]
+        principal: Option<String>,
+
+        /// Require the signature to be made for this namespace
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Require the signature to include this nonce
+        #[structopt(long = "expect-nonce")]
+        expect_nonce: Option<String>,
+
+        /// Check the signatures against the signers and rules of this JSON policy instead of -k or -g
+        #[structopt(
+            long = "policy",
+            parse(from_os_str),
+            raw(conflicts_with_all = r#"&["public_key", "key", "keydir", "github", "github_team", "launchpad", "keys_url", "authorized_keys", "known_hosts", "github_user", "signature", "trust_embedded", "chain", "inner_key", "namespace", "expect_nonce", "usage_policy"]"#)
+        )]
+        policy: Option<PathBuf>,
+
+        /// Refuse signatures by keys revoked in this file or https:// URL, may be repeated
+        #[structopt(long = "revocations", number_of_values = 1)]
+        revocations: Vec<String>,
+
+        /// Verify with the public key embedded in the envelope, ignoring -k and -g
+        #[structopt(long = "trust-embedded")]
+        trust_embedded: bool,
+
+        /// Fail if the signing key may not sign the namespace under this JSON policy
+        #[structopt(long = "usage-policy", parse(from_os_str))]
+        usage_policy: Option<PathBuf>,
+
+        /// Also verify the envelope's signature chain, and list its signers in order
+        #[structopt(long = "chain")]
+        chain: bool,
+
+        /// Path to the ed25519 public key of countersigned envelopes, overrides -g for them
+        #[structopt(long = "inner-key", parse(from_os_str))]
+        inner_key: Option<PathBuf>,
+
+        /// Append an NDJSON record of this operation to the given file
+        #[structopt(long = "audit-log", parse(from_os_str))]
+        audit_log: Option<PathBuf>,
+
+        /// Also stream the records of this operation to stderr in this format
+        #[structopt(long = "log-format", raw(possible_values = r#"&["ndjson"]"#))]
+        log_format: Option<String>,
+
+        /// Also send the records of this operation to the system log
+        #[structopt(long = "log-to", raw(possible_values = "syslog::TARGETS"))]
+        log_to: Option<String>,
+
+        /// Print the fingerprint and OpenSSH randomart of each verified signer
+        #[structopt(long = "randomart")]
+        randomart: bool,
+
+        /// Require a signed --identity, and check that the key belongs to it, on github or in a -k file's comments
+        #[structopt(long = "require-claimed-identity", conflicts_with = "policy")]
+        require_claimed_identity: bool,
+
+        /// Warn about signatures older than this, like 90d, 12h or 30m, or a number of days, or without a timestamp
+        #[structopt(long = "warn-age", conflicts_with = "policy", parse(try_from_str = "envelope::parse_age"))]
+        warn_age: Option<time::Duration>,
+
+        /// How far the signer's clock and this one may disagree, for timestamps, expiry and certificates
+        #[structopt(long = "clock-skew", default_value = "5m", parse(try_from_str = "envelope::parse_duration"))]
+        clock_skew: time::Duration,
+
+        /// Refuse envelopes and signature files larger than this, such as 1MiB, without reading the rest of them
+        #[structopt(long = "max-envelope-size", raw(default_value = "limits::ENVELOPE_SIZE"), parse(try_from_str = "merkle::parse_size"))]
+        max_envelope_size: u64,
+
+        /// Refuse to read more than this of -i into memory to check a detached signature, unless streaming or mapping it
+        #[structopt(long = "max-input-size", parse(try_from_str = "merkle::parse_size"))]
+        max_input_size: Option<u64>,
+
+        /// Refuse envelopes and signature files whose JSON nests arrays and objects more than this deep
+        #[structopt(long = "max-depth", raw(default_value = "limits::DEPTH"))]
+        max_depth: usize,
+
+        /// Fail verification if there are any warnings
+        #[structopt(long = "deny-warnings")]
+        deny_warnings: bool,
+
+        /// Print the outcome, signers and warnings as JSON instead of status lines
+        #[structopt(long = "json", raw(conflicts_with_all = r#"&["randomart", "chain"]"#))]
+        json: bool,
+    },
+
+    /// Append a link to an envelope's signature chain
+    #[structopt(name = "chain")]
+    Chain {
+        /// Envelope to extend, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the extended envelope, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Envelope to extend (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Github username to couple with the new link
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Sign a statement revoking a key, for `verify --revocations`
+    #[structopt(name = "revoke")]
+    Revoke {
+        /// Path to the ed25519 private key to revoke, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Why the key is revoked, such as "superseded" or "compromised"
+        #[structopt(long = "reason")]
+        reason: Option<String>,
+
+        /// Refuse signatures claiming to be made from this UTC time on, instead of from now
+        ///
+        /// The time is only what the signer claims, so a stolen key can backdate signatures past it. Use
+        /// --compromised for a key that leaked.
+        #[structopt(long = "since", parse(try_from_str = "envelope::parse_timestamp"))]
+        since: Option<time::Tm>,
+
+        /// The key leaked, so refuse all of its signatures, whatever time they claim
+        #[structopt(long = "compromised", conflicts_with = "since")]
+        compromised: bool,
+
+        /// Output of the revocation, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Move signatures from an old key to a new one, and sign a statement linking the two
+    #[structopt(name = "rotate")]
+    Rotate {
+        /// Path to the ed25519 private key the files are signed with now
+        #[structopt(long = "old-key", parse(from_os_str))]
+        old_key: PathBuf,
+
+        /// Path to the ed25519 private key to sign them with from now on
+        #[structopt(long = "new-key", parse(from_os_str))]
+        new_key: PathBuf,
+
+        /// Add the new key's signature next to the old one instead of replacing it
+        #[structopt(long = "cross-sign")]
+        cross_sign: bool,
+
+        /// Output of the rotation statement, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+
+        /// Envelopes, bundles and detached signatures to re-sign in place
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Sign an existing envelope or bundle, attesting to it and its signatures
+    #[structopt(name = "countersign")]
+    Countersign {
+        /// Envelope to countersign, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of signature, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Envelope to countersign (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Github username to couple with json output
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Namespace the countersignature is valid for, covered by the signature
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Sign a message, then encrypt it to a recipient's ed25519 key
+    #[structopt(name = "seal")]
+    Seal {
+        /// Recipient's ed25519 public key file, or their github username
+        #[structopt(short = "r")]
+        recipient: String,
+
+        /// File to seal, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of sealed message, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Message to seal (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Github username to couple with the signed message
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Namespace the signature is valid for, covered by the signature
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Decrypt a sealed message, verify the sender's signature, and write out the message
+    #[structopt(name = "open")]
+    Open {
+        /// Sealed file to open, defaults to stdin if no file is specified or -m is not used
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the verified message, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Sealed message to open (overrides -i flag or stdin)
+        #[structopt(short = "m")]
+        message: Option<String>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Path to the sender's ed25519 public key, overrides -g
+        #[structopt(short = "s", parse(from_os_str))]
+        sender: Option<PathBuf>,
+
+        /// Pull the sender's public keys from github, using the github user claimed in the message
+        #[structopt(short = "g")]
+        github: bool,
+
+        /// Require the signature to be made for this namespace
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+    },
+
+    /// Sign new and changed files in a directory into detached signatures
+    #[structopt(name = "watch")]
+    Watch {
+        /// Directory to watch, including its subdirectories
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Directory to write the signatures to, mirroring the watched directory
+        #[structopt(long = "out", parse(from_os_str))]
+        out: PathBuf,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Github username to couple with json output
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Namespace the signatures are valid for, covered by the signature
+        #[structopt(short = "n")]
+        namespace: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+
+        /// Seconds between scans of the directory
+        #[structopt(long = "interval", default_value = "2")]
+        interval: u64,
+
+        /// Sign the files that need it once, then exit
+        #[structopt(long = "once")]
+        once: bool,
+
+        /// Don't sign paths matching this gitignore-style pattern, in addition to .signitignore
+        #[structopt(long = "exclude", number_of_values = 1)]
+        exclude: Vec<String>,
+    },
+
+    /// Describe an envelope, bundle or sealed message without verifying it
+    #[structopt(name = "show")]
+    Show {
+        /// File to describe, defaults to stdin if no file is specified
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+    },
+
+    /// List the principals of an allowed_signers file that a signature was made by, like ssh-keygen -Y find-principals
+    #[structopt(name = "who-signed")]
+    WhoSigned {
+        /// Envelope, bundle or detached signature
+        #[structopt(short = "s", parse(from_os_str))]
+        signature: PathBuf,
+
+        /// OpenSSH allowed_signers file, such as ~/.ssh/allowed_signers
+        #[structopt(short = "f", parse(from_os_str))]
+        allowed_signers: PathBuf,
+    },
+
+    /// Check the structure and encoding of an envelope, printing JSON diagnostics
+    #[structopt(name = "validate")]
+    Validate {
+        /// File to validate, defaults to stdin if no file is specified
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Report whether two envelopes or signatures cover the same message, and list their signers
+    #[structopt(name = "compare")]
+    Compare {
+        /// First envelope, bundle or detached signature
+        #[structopt(parse(from_os_str))]
+        first: PathBuf,
+
+        /// Second envelope, bundle or detached signature
+        #[structopt(parse(from_os_str))]
+        second: PathBuf,
+    },
+
+    /// Rewrite an envelope, bundle or detached signature in canonical form, so it is byte for byte stable
+    #[structopt(name = "canonicalize")]
+    Canonicalize {
+        /// Envelope, bundle or detached signature, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output file, which may be the input, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Only check that the input is in canonical form, failing if it is not
+        #[structopt(long = "check", conflicts_with = "output")]
+        check: bool,
+    },
+
+    /// List or change the hints of an envelope, bundle or detached signature, which no signature covers
+    #[structopt(name = "hints")]
+    Hints {
+        /// Envelope, bundle or detached signature, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Set a key=value hint, may be given more than once
+        #[structopt(long = "set", number_of_values = 1, parse(try_from_str = "envelope::parse_pair"))]
+        set: Vec<(String, String)>,
+
+        /// Remove the hint with this key, may be given more than once
+        #[structopt(long = "unset", number_of_values = 1)]
+        unset: Vec<String>,
+
+        /// Output file for the changed envelope, which may be the input, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Export the public key, signature and digest of an envelope as C or Rust constants
+    #[structopt(name = "export-c")]
+    ExportC {
+        /// Path to the signer's ed25519 public key
+        #[structopt(short = "k", long = "key", parse(from_os_str))]
+        key: PathBuf,
+
+        /// Envelope, bundle or detached signature to export
+        #[structopt(short = "s", long = "sig", parse(from_os_str))]
+        sig: PathBuf,
+
+        /// Language of the generated source
+        #[structopt(long = "lang", default_value = "c", raw(possible_values = "export::LANGUAGES"))]
+        lang: String,
+
+        /// Prefix of the generated constant names
+        #[structopt(long = "prefix", default_value = "signit")]
+        prefix: String,
+
+        /// Output of the generated source, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the fingerprint of an ed25519 public or private key
+    #[structopt(name = "fingerprint")]
+    Fingerprint {
+        /// Path to ed25519 public or private key, defaults to "$HOME/.ssh/id_ed25519.pub"
+        #[structopt(short = "k", parse(from_os_str))]
+        key: Option<PathBuf>,
+
+        /// Also draw the OpenSSH randomart of the fingerprint
+        #[structopt(long = "randomart")]
+        randomart: bool,
+    },
+
+    /// Print the public key of an ed25519 private key
+    #[structopt(name = "pubkey")]
+    Pubkey {
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output format
+        #[structopt(short = "f", default_value = "openssh", raw(possible_values = r#"&["openssh", "raw", "pem", "did-key"]"#))]
+        format: String,
+
+        /// Output the key as a Multikey in this multibase encoding instead
+        #[structopt(long = "key-encoding", conflicts_with = "format", raw(possible_values = "keys::MULTIBASES"))]
+        key_encoding: Option<String>,
+    },
+
+    /// Utilities for working with keys
+    #[structopt(name = "key")]
+    Key(KeyCommands),
+
+    /// Utilities for working with multi-signature bundles
+    #[structopt(name = "bundle")]
+    Bundle(BundleCommands),
+
+    /// Git hooks that sign commits and tags
+    #[structopt(name = "hooks")]
+    Hooks(HookCommands),
+
+    /// Sign firmware images with a fixed-layout header a bootloader can check
+    #[structopt(name = "firmware")]
+    Firmware(FirmwareCommands),
+
+    /// Inspect the keys held by ssh-agent
+    #[structopt(name = "agent")]
+    Agent(AgentCommands),
+
+    /// Try out verification policies
+    #[structopt(name = "policy")]
+    Policy(PolicyCommands),
+
+    /// Sign and verify HTTP requests with RFC 9421 message signatures
+    #[structopt(name = "http")]
+    Http(HttpCommands),
+
+    /// Issue and verify JSON Web Tokens signed with EdDSA
+    #[structopt(name = "jwt")]
+    Jwt(JwtCommands),
+
+    /// Issue and verify PASETO v4.public tokens
+    #[structopt(name = "paseto")]
+    Paseto(PasetoCommands),
+
+    /// Sign and verify CycloneDX SBOMs with their embedded JSF signature
+    #[structopt(name = "sbom")]
+    Sbom(SbomCommands),
+
+    /// Attach signatures to images in OCI registries, and verify them
+    #[structopt(name = "oci")]
+    Oci(OciCommands),
+
+    /// Sign and verify bare DSSE envelopes
+    #[structopt(name = "dsse")]
+    Dsse(DsseCommands),
+
+    /// Delegate signing within a namespace to another key, for a while
+    #[structopt(name = "ca")]
+    Ca(CaCommands),
+
+    /// Issue and check challenges that prove a user holds an SSH key, for logins
+    #[structopt(name = "challenge")]
+    Challenge(ChallengeCommands),
+
+    /// Prove that a key belongs to an account, to third parties
+    #[structopt(name = "prove")]
+    Prove(ProveCommands),
+
+    /// Answer a challenge from `signit challenge new`
+    #[structopt(name = "respond")]
+    Respond {
+        /// Challenge to answer, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the response, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Refuse challenges for any other context, such as one relayed from another site
+        #[structopt(long = "context")]
+        context: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum KeyCommands {
+    /// Convert an ed25519 public or private key to its X25519 counterpart
+    #[structopt(name = "x25519")]
+    X25519 {
+        /// Path to ed25519 public or private key, defaults to "$HOME/.ssh/id_ed25519.pub"
+        #[structopt(short = "k", parse(from_os_str))]
+        key: Option<PathBuf>,
+
+        /// Print the key as an age recipient or identity instead of base64
+        #[structopt(long = "age")]
+        age: bool,
+    },
+
+    /// Convert an ed25519 private key between OpenSSH, PKCS#8 and raw encodings
+    #[structopt(name = "convert")]
+    Convert {
+        /// Path to ed25519 private key in any supported encoding, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output of the converted key, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Output encoding, raw32 and raw64 are base64 of the seed or the seed and public key
+        #[structopt(short = "f", default_value = "openssh", raw(possible_values = r#"&["openssh", "pkcs8", "raw32", "raw64"]"#))]
+        format: String,
+
+        /// Prompt for a passphrase to encrypt the output with, pkcs8 only
+        #[structopt(long = "encrypt")]
+        encrypt: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum BundleCommands {
+    /// Combine envelopes and bundles over the same message into one bundle
+    #[structopt(name = "merge")]
+    Merge {
+        /// Envelopes or bundles to merge
+        #[structopt(parse(from_os_str), required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output of the bundle, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum FirmwareCommands {
+    /// Add a signed header holding the image's length, digest and key id
+    #[structopt(name = "sign")]
+    Sign {
+        /// Firmware image to sign
+        #[structopt(parse(from_os_str))]
+        image: PathBuf,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output of the signed image, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Place the header after the image instead of before it
+        #[structopt(long = "append")]
+        append: bool,
+    },
+
+    /// Check the header of a signed image, at either end of it
+    #[structopt(name = "verify")]
+    Verify {
+        /// Signed firmware image to verify
+        #[structopt(parse(from_os_str))]
+        image: PathBuf,
+
+        /// Path to ed25519 public key, defaults to "$HOME/.ssh/id_ed25519.pub"
+        #[structopt(short = "k", parse(from_os_str))]
+        public_key: Option<PathBuf>,
+
+        /// Output of the image without its header, once verified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum AgentCommands {
+    /// List the type, fingerprint and comment of every key loaded into ssh-agent
+    #[structopt(name = "list")]
+    List,
+}
+
+#[derive(StructOpt)]
+enum PolicyCommands {
+    /// Explain which rules of a verification policy an envelope or bundle passes and fails
+    #[structopt(name = "check")]
+    Check {
+        /// JSON verification policy to check against
+        #[structopt(long = "policy", parse(from_os_str))]
+        policy: PathBuf,
+
+        /// Envelope or bundle to check, defaults to stdin if no file is specified
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Files or https:// URLs of revocations to apply along with those of the policy, may be repeated
+        #[structopt(long = "revocations", number_of_values = 1)]
+        revocations: Vec<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum HttpCommands {
+    /// Add Signature-Input and Signature headers to an HTTP request
+    #[structopt(name = "sign")]
+    Sign {
+        /// HTTP/1.1 request to sign, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the signed request, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Components to cover, such as @method or content-type, defaults to @method @authority @path @query
+        #[structopt(short = "c", number_of_values = 1)]
+        components: Vec<String>,
+
+        /// Key id to send, defaults to the key's fingerprint
+        #[structopt(long = "keyid")]
+        keyid: Option<String>,
+
+        /// Make the signature expire this many seconds after it is created
+        #[structopt(long = "expires")]
+        expires: Option<i64>,
+
+        /// Nonce to include in the signature parameters
+        #[structopt(long = "nonce")]
+        nonce: Option<String>,
+
+        /// Label of the signature in the headers
+        #[structopt(long = "label", default_value = "sig1")]
+        label: String,
+    },
+
+    /// Verify an RFC 9421 signature of an HTTP request
+    #[structopt(name = "verify")]
+    Verify {
+        /// Signed HTTP/1.1 request, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Label of the signature to verify, defaults to the first one
+        #[structopt(long = "label")]
+        label: Option<String>,
+
+        /// Refuse signatures created longer ago than this, like 5m or 300 seconds, or without a created time
+        #[structopt(long = "max-age", parse(try_from_str = "envelope::parse_duration"))]
+        max_age: Option<time::Duration>,
+
+        /// Components the signature must cover, may be repeated
+        #[structopt(short = "c", number_of_values = 1)]
+        components: Vec<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum JwtCommands {
+    /// Issue a token holding the claims of a JSON object
+    #[structopt(name = "sign")]
+    Sign {
+        /// JSON object of claims, defaults to stdin if no file is specified
+        #[structopt(long = "claims", parse(from_os_str))]
+        claims: Option<PathBuf>,
+
+        /// Output of the token, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Set exp to this many seconds from now
+        #[structopt(long = "expires")]
+        expires: Option<i64>,
+
+        /// Key id for the token header, defaults to the key's fingerprint
+        #[structopt(long = "kid")]
+        kid: Option<String>,
+    },
+
+    /// Verify a token, printing its claims
+    #[structopt(name = "verify")]
+    Verify {
+        /// File holding the token, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Require the aud claim to include this audience
+        #[structopt(long = "audience")]
+        audience: Option<String>,
+
+        /// Require the iss claim to be this issuer
+        #[structopt(long = "issuer")]
+        issuer: Option<String>,
+
+        /// Seconds of clock skew to allow when checking exp and nbf
+        #[structopt(long = "leeway", default_value = "0")]
+        leeway: i64,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum ProveCommands {
+    /// Sign a statement that the key belongs to a github user
+    #[structopt(name = "github")]
+    Github {
+        /// The github user
+        user: String,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output of the proof, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Also publish the proof as a public gist of the user, using $GITHUB_TOKEN
+        #[structopt(long = "gist")]
+        gist: bool,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Sign a profile listing the accounts a key belongs to, each of which must vouch for it
+    #[structopt(name = "profile")]
+    Profile {
+        /// Account to claim: dns:<domain>, https://<instance>/@<user>, or https://github.com/<user>, gitlab.com or codeberg.org, may be repeated
+        #[structopt(long = "claim", number_of_values = 1, required = true)]
+        claims: Vec<String>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output of the profile, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Check every claim of a profile against the account it names
+    #[structopt(name = "check")]
+    Check {
+        /// The profile, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Require the profile to be for this key
+        #[structopt(short = "k", parse(from_os_str))]
+        public_key: Option<PathBuf>,
+    },
+
+    /// Check a proof against the keys the account publishes
+    #[structopt(name = "verify")]
+    Verify {
+        /// The proof, defaults to stdin if neither this nor --gist is given
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Fetch the proof from this gist, by id or URL, which must belong to the user
+        #[structopt(long = "gist", conflicts_with = "input")]
+        gist: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum CaCommands {
+    /// Sign a delegation allowing another key to sign within a namespace, for `sign --delegation`
+    #[structopt(name = "issue")]
+    Issue {
+        /// Path to the delegated ed25519 public key
+        #[structopt(long = "delegate", parse(from_os_str))]
+        delegate: PathBuf,
+
+        /// The namespace the delegated key may sign in
+        #[structopt(short = "n")]
+        namespace: String,
+
+        /// Seconds until the delegation expires
+        #[structopt(long = "expires", default_value = "86400")]
+        expires: u32,
+
+        /// Path to the delegating ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Output of the delegation, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum ChallengeCommands {
+    /// Issue a challenge, for the server to keep until the response arrives
+    #[structopt(name = "new")]
+    New {
+        /// What the challenge is for, such as login:example.com
+        #[structopt(long = "context")]
+        context: String,
+
+        /// Seconds until the challenge expires
+        #[structopt(long = "expires", default_value = "300")]
+        expires: u32,
+
+        /// Output of the challenge, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Check that a response answers a challenge, and was made by one of the user's keys
+    #[structopt(name = "verify")]
+    Verify {
+        /// The challenge that was issued
+        #[structopt(short = "c", parse(from_os_str))]
+        challenge: PathBuf,
+
+        /// The response, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Path to the user's ed25519 public keys, may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Github user whose keys to accept, instead of -k
+        #[structopt(short = "g", conflicts_with = "public_key")]
+        github: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum DsseCommands {
+    /// Wrap a payload in a DSSE envelope and sign it
+    #[structopt(name = "sign")]
+    Sign {
+        /// Payload to sign, or the envelope to add a signature to with --append, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the envelope, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Type of the payload, such as application/vnd.in-toto+json
+        #[structopt(long = "payload-type", required_unless = "append")]
+        payload_type: Option<String>,
+
+        /// Add a signature to the envelope given as input, instead of making a new one
+        #[structopt(long = "append", conflicts_with = "payload_type")]
+        append: bool,
+
+        /// Key id to record with the signature, defaults to the key fingerprint
+        #[structopt(long = "keyid")]
+        keyid: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Verify a DSSE envelope
+    #[structopt(name = "verify")]
+    Verify {
+        /// Envelope to verify, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Write the payload here once verified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Require the payload to be of this type
+        #[structopt(long = "payload-type")]
+        payload_type: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum OciCommands {
+    /// Sign the manifest an image reference resolves to, pushing the signature as a referrer
+    #[structopt(name = "sign")]
+    Sign {
+        /// Image reference, like ghcr.io/org/app:1.0 or ghcr.io/org/app@sha256:...
+        reference: String,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+    },
+
+    /// Verify the signatures attached to the manifest an image reference resolves to
+    #[structopt(name = "verify")]
+    Verify {
+        /// Image reference, like ghcr.io/org/app:1.0 or ghcr.io/org/app@sha256:...
+        reference: String,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum SbomCommands {
+    /// Embed a signature in a CycloneDX JSON document
+    #[structopt(name = "sign")]
+    Sign {
+        /// CycloneDX JSON document, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Output of the signed document, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+
+    /// Verify the signature embedded in a CycloneDX JSON document
+    #[structopt(name = "verify")]
+    Verify {
+        /// Signed CycloneDX JSON document, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum PasetoCommands {
+    /// Issue a token holding the claims of a JSON object
+    #[structopt(name = "sign")]
+    Sign {
+        /// JSON object of claims, defaults to stdin if no file is specified
+        #[structopt(long = "claims", parse(from_os_str))]
+        claims: Option<PathBuf>,
+
+        /// Output of the token, defaults to stdout if no file is specified
+        #[structopt(short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Set exp to this many seconds from now
+        #[structopt(long = "expires")]
+        expires: Option<i64>,
+
+        /// Footer to append to the token, covered by the signature but not encrypted or hidden
+        #[structopt(long = "footer")]
+        footer: Option<String>,
+
+        /// Implicit assertion covered by the signature, which the verifier must pass as well
+        #[structopt(long = "implicit")]
+        implicit: Option<String>,
+    },
+
+    /// Verify a token, printing its claims
+    #[structopt(name = "verify")]
+    Verify {
+        /// File holding the token, defaults to stdin if no file is specified
+        #[structopt(short = "i", parse(from_os_str))]
+        input: Option<PathBuf>,
+
+        /// Path to ed25519 public keys, defaults to "$HOME/.ssh/id_ed25519.pub", may be repeated
+        #[structopt(short = "k", parse(from_os_str), number_of_values = 1)]
+        public_key: Vec<PathBuf>,
+
+        /// Require the aud claim to be this audience
+        #[structopt(long = "audience")]
+        audience: Option<String>,
+
+        /// Require the iss claim to be this issuer
+        #[structopt(long = "issuer")]
+        issuer: Option<String>,
+
+        /// Require the token to carry exactly this footer
+        #[structopt(long = "footer")]
+        footer: Option<String>,
+
+        /// Implicit assertion the token was signed with
+        #[structopt(long = "implicit")]
+        implicit: Option<String>,
+
+        /// Pretty Print the JSON output
+        #[structopt(short = "p")]
+        pretty: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum HookCommands {
+    /// Install hooks signing new commits (post-commit) and pushed tags (pre-push) into git notes
+    #[structopt(name = "install")]
+    Install {
+        /// Hooks to install, defaults to all of them
+        #[structopt(raw(possible_values = "hooks::HOOKS"))]
+        hooks: Vec<String>,
+
+        /// Repository to install the hooks into, defaults to the current directory
+        #[structopt(short = "C", parse(from_os_str))]
+        repo: Option<PathBuf>,
+
+        /// Path to ed25519 private key the hooks sign with, defaults to "$HOME/.ssh/id_ed25519"
+        #[structopt(short = "k", parse(from_os_str))]
+        private_key: Option<PathBuf>,
+
+        /// Github username the hooks couple with their signatures
+        #[structopt(short = "g")]
+        github: Option<String>,
+
+        /// Replace existing hooks that were not installed by signit
+        #[structopt(long = "force")]
+        force: bool,
+    },
+}
+
+fn main() {
+    let opt = Commands::from_args();
+
+    match opt {
+        Commands::Sign { input, output, message, edit, mut private_key, key_fd, agent_key, host_key, github, pretty, namespace, nonce, random_nonce, timestamp, identity, meta, hint, embed_key, certificate, delegation, qr, copy, publish, inline, raw, key_encoding, detached, chunk_size, mmap, postcard, skip_existing, usage_policy, audit_log, log_format, log_to } => {
+            let audit_log = audit::Sink::new(audit_log, log_format, log_to);
+            private_key.extend(key_fd.map(|fd| PathBuf::from(format!("fd:{}", fd))));
+            if host_key {
+                match std::fs::File::open(knownhosts::HOST_KEY) {
+                    Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                        eject(&format!("Only root can read the host key {}, run signit with sudo", knownhosts::HOST_KEY))
+                    }
+                    Err(e) => eject(&format!("Failed to open the host key {}\nError: {:?}", knownhosts::HOST_KEY, e)),
+                    Ok(_) => private_key.push(PathBuf::from(knownhosts::HOST_KEY)),
+                }
+            }
+            let private_key = if private_key.is_empty() && agent_key.is_empty() {
+                vec![private_key_path(None)]
+            } else {
+                private_key
+            };
+            let mut signing: Vec<_> = private_key
+                .into_iter()
+                .map(|path| SigningKey::File(get_private_key(Some(path.clone())), path))
+                .collect();
+            for query in &agent_key {
+                signing.push(SigningKey::Agent(agent::find(query).unwrap_or_else(|e| eject(&e))));
+            }
+
+            // Several keys make a bundle, which only holds plain envelopes
+            let single_only = [
+                ("--raw", raw.is_some()),
+                ("--detached", detached),
+                ("--postcard", postcard),
+                ("--qr", qr),
+                ("--inline", inline.is_some()),
+                ("--skip-existing", skip_existing),
+                ("--certificate", certificate.is_some()),
+                ("--delegation", delegation.is_some()),
+            ];
+            if let (true, Some((flag, _))) = (signing.len() > 1, single_only.iter().find(|(_, set)| *set)) {
+                eject(&format!("Signing with several keys makes a bundle, which {} cannot output!", flag));
+            }
+
+            if let Some(path) = usage_policy {
+                let policy = UsagePolicy::load(&path);
+                for key in &signing {
+                    policy
+                        .check(&keys::fingerprint(&key.public()), namespace.as_deref())
+                        .unwrap_or_else(|e| eject(&e));
+                }
+            }
+
+            let log_signed = |key: &SigningKey, input_digest: String, github_user: Option<&str>| {
+                if audit_log.is_enabled() {
+                    let mut record = audit::Record::with_digest("sign", input_digest, "signed");
+                    record.key_fingerprint = Some(keys::fingerprint(&key.public()));
+                    record.github_user = github_user;
+                    audit_log.write(record);
+                }
+            };
+            let key = &signing[0];
+
+            let certificate = certificate.map(|path| {
+                let line = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| eject(&format!("Failed to read certificate {:?}\nError: {:?}", path, e)));
+                let certified = cert::Certificate::parse(&line).unwrap_or_else(|e| eject(&e)).key;
+                if certified != key.public() {
+                    eject(&format!("Certificate {:?} is for {}, not the signing key!", path, keys::fingerprint(&certified)));
+                }
+                line.trim().to_string()
+            });
+            let delegation = delegation.map(|path| {
+                let raw = get_message(None, &Some(path.clone()));
+                let delegation: SignIt = serde_json::from_str(&raw)
+                    .unwrap_or_else(|e| eject(&format!("Delegation {:?} is not an envelope\nError: {:?}", path, e)));
+                let allowed = delegate::Delegation::parse(&delegation).unwrap_or_else(|e| eject(&e));
+                if allowed.key != key.public() {
+                    eject(&format!("Delegation {:?} is for {}, not the signing key!", path, keys::fingerprint(&allowed.key)));
+                }
+                if Some(&allowed.namespace) != namespace.as_ref() {
+                    eject(&format!("Delegation {:?} only allows signing in namespace {:?}!", path, allowed.namespace));
+                }
+                Box::new(delegation)
+            });
+
+            // Raw signatures cover the input bytes themselves, which need not be text
+            if let Some(encoding) = raw {
+                let data = match (edit, message) {
+                    (true, _) => editor::compose().unwrap_or_else(|e| eject(&e)).into_bytes(),
+                    (false, Some(message)) => message.into_bytes(),
+                    (false, None) => get_input(&input),
+                };
+                // Otherwise the signature would pass for one over an envelope's payload
+                if data.starts_with(envelope::PAYLOAD_PREFIX) {
+                    eject("Refusing to make a raw signature over input that starts like a signit payload!");
+                }
+                let sig = key.sign(&data);
+                log_signed(key, envelope::digest(&data[..]).unwrap(), None);
+
+                match (encoding.as_str(), copy) {
+                    ("binary", _) => write_file(&output.unwrap_or_else(|| PathBuf::from("-")), &sig),
+                    _ if key_encoding.is_some() => write_or_print(output, keys::multibase(&sig, &key_encoding.unwrap())),
+                    (_, true) => {
+                        clipboard::copy(&encode(&sig[..])).unwrap_or_else(|e| eject(&e));
+                        eprintln!("Copied signature to the clipboard");
+                    }
+                    _ => write_or_print(output, encode(&sig[..])),
+                }
+                return;
+            }
+
+            let nonce = if random_nonce {
+                let mut raw = [0u8; 16];
+                openssl::rand::rand_bytes(&mut raw).unwrap();
+                Some(hex::encode(raw))
+            } else {
+                nonce
+            };
+            let timestamp = Some(envelope::timestamp()).filter(|_| timestamp);
+            if let Some(identity) = &identity {
+                match envelope::parse_identity(identity).unwrap_or_else(|e| eject(&e)) {
+                    ("github", user) if github.as_deref().map_or(false, |g| g != user) => {
+                        eject(&format!("--identity claims github user {}, but -g gives {}!", user, github.unwrap()))
+                    }
+                    _ => (),
+                }
+            }
+            let mut metadata = BTreeMap::new();
+            for (key, value) in meta {
+                if metadata.insert(key.clone(), value).is_some() {
+                    eject(&format!("--meta gives {} more than once!", key));
+                }
+            }
+            let mut hints = BTreeMap::new();
+            for (key, value) in hint {
+                if hints.insert(key.clone(), value).is_some() {
+                    eject(&format!("--hint gives {} more than once!", key));
+                }
+            }
+
+            if detached {
+                let mapped;
+                let reader: Box<dyn std::io::Read + '_> = match (edit, message) {
+                    (true, _) => Box::new(std::io::Cursor::new(editor::compose().unwrap_or_else(|e| eject(&e)))),
+                    (false, Some(message)) => Box::new(std::io::Cursor::new(message)),
+                    (false, None) if mmap => {
+                        mapped = get_mapped(&input);
+                        Box::new(&mapped[..])
+                    }
+                    (false, None) => open_input(&input),
+                };
+
+                let (digest, chunks) = match chunk_size {
+                    Some(0) => eject("Chunk size must be more than zero bytes!"),
+                    Some(size) => {
+                        let chunks = merkle::chunks(reader, size)
+                            .unwrap_or_else(|e| eject(&format!("Failed to read input\nError: {:?}", e)));
+                        (merkle::digest(&chunks).unwrap(), Some(chunks))
+                    }
+                    None => {
+                        let digest = envelope::digest(reader)
+                            .unwrap_or_else(|e| eject(&format!("Failed to read input\nError: {:?}", e)));
+                        (digest, None)
+                    }
+                };
+
+                let mut out = Detached {
+                    digest,
+                    chunks,
+                    signer: Signer { namespace, nonce, timestamp, identity, metadata, github_user: github, certificate, delegation, ..Default::default() },
+                    hints,
+                };
+                if embed_key {
+                    out.signer.key = Some(keys::embed(&key.public(), key.comment()));
+                }
+                out.signer.signature = encode(&key.sign(&out.signed_bytes())[..]);
+                log_signed(key, out.digest.clone(), out.signer.github_user.as_deref());
+
+                let outstr = (if pretty {
+                    serde_json::to_string_pretty
+                } else {
+                    envelope::canonical
+                })(&out).unwrap();
+                if copy {
+                    clipboard::copy(&outstr).unwrap_or_else(|e| eject(&e));
+                    eprintln!("Copied signature to the clipboard");
+                    return;
+                }
+                publish_signature(publish.as_deref(), &outstr);
+                write_or_print(output, outstr);
+                return;
+            }
+
+            let message = if edit {
+                editor::compose().unwrap_or_else(|e| eject(&e))
+            } else {
+                get_message(message, &input)
+            };
+            let document = message;
+            let message = match &inline {
+                Some(format) => inline::message(format, &document).unwrap_or_else(|e| eject(&e)),
+                None => document.clone(),
+            };
+
+            let mut out = SignIt {
+                message,
+                signer: Signer { namespace, nonce, timestamp, identity, metadata, github_user: github, certificate, delegation, ..Default::default() },
+                hints,
+                ..Default::default()
+            };
+
+            if signing.len() > 1 {
+                let mut bundle = Bundle { message: out.message.clone(), hints: out.hints.clone(), ..Default::default() };
+                for key in &signing {
+                    if embed_key {
+                        out.signer.key = Some(keys::embed(&key.public(), key.comment()));
+                    }
+                    out.signer.signature = encode(&key.sign(&out.signed_bytes())[..]);
+                    log_signed(key, envelope::digest(out.message.as_bytes()).unwrap(), out.signer.github_user.as_deref());
+                    bundle.signatures.push(out.signer.clone());
+                }
+
+                let outstr = (if pretty {
+                    serde_json::to_string_pretty
+                } else {
+                    envelope::canonical
+                })(&bundle).unwrap();
+                if copy {
+                    clipboard::copy(&outstr).unwrap_or_else(|e| eject(&e));
+                    eprintln!("Copied signatures to the clipboard");
+                    return;
+                }
+                publish_signature(publish.as_deref(), &outstr);
+                write_or_print(output, outstr);
+                return;
+            }
+
+            if embed_key {
+                out.signer.key = Some(keys::embed(&key.public(), key.comment()));
+            }
+
+            if skip_existing {
+                let path = output.as_ref().unwrap();
+                if is_current(path, &out, &key.public()) {
+                    eprintln!("{:?} is already signed, skipping", path);
+                    return;
+                }
+            }
+
+            out.signer.signature = encode(&key.sign(&out.signed_bytes())[..]);
+
+            log_signed(key, envelope::digest(out.message.as_bytes()).unwrap(), out.signer.github_user.as_deref());
+
+            if postcard {
+                write_file(&output.unwrap_or_else(|| PathBuf::from("-")), &to_postcard(&out, &key.public()));
+                return;
+            }
+
+            if qr {
+                let compact = envelope::canonical(&out).unwrap();
+                match output {
+                    Some(opath) => write_file(&opath, &qr::png(&compact).unwrap_or_else(|e| eject(&e))),
+                    None => println!("{}", qr::terminal(&compact).unwrap_or_else(|e| eject(&e))),
+                }
+                return;
+            }
+
+            let outstr = match &inline {
+                Some(format) => inline::embed(format, &document, &out, pretty).unwrap_or_else(|e| eject(&e)),
+                None => (if pretty {
+                    serde_json::to_string_pretty
+                } else {
+                    envelope::canonical
+                })(&out).unwrap(),
+            };
+
+            if copy {
+                clipboard::copy(&outstr).unwrap_or_else(|e| eject(&e));
+                eprintln!("Copied signature to the clipboard");
+                return;
+            }
+
+            publish_signature(publish.as_deref(), &outstr);
+            write_or_print(output, outstr);
+
+        },
+        Commands::Verify { input, message, paste, signature, mmap, stream, range, inline, output, extract, exec, public_key, key, keydir, recursive, github, github_user: expected_user, github_team, launchpad, keys_url, authorized_keys, known_hosts, host, trusted_ca, principal, namespace, expect_nonce, policy, revocations, trust_embedded, usage_policy, chain, inner_key, audit_log, log_format, log_to, randomart, require_claimed_identity, warn_age, clock_skew, max_envelope_size, max_input_size, max_depth, deny_warnings, json } => {
+            let audit_log = audit::Sink::new(audit_log, log_format, log_to);
+            let message = if paste {
+                Some(clipboard::paste().unwrap_or_else(|e| eject(&e)))
+            } else {
+                message
+            };
+            let usage_policy = usage_policy.map(|path| UsagePolicy::load(&path));
+            let policy = policy.map(|path| policy::Policy::load(&path));
+            let mut revocations = revocations;
+            if let Some(policy) = &policy {
+                revocations.extend(policy.revocations.iter().cloned());
+            }
+            let revocations = get_revocations(&revocations);
+            let not_revoked = |fingerprint: String, timestamp: Option<&str>| {
+                revoke::check_signature(&revocations, &fingerprint, timestamp)
+                    .map(|_| fingerprint)
+                    .map_err(|e| ("revoked", e))
+            };
+            let mut public_key = public_key;
+            if let Some(dir) = &keydir {
+                let files = keys::key_files(dir, recursive)
+                    .unwrap_or_else(|e| eject(&format!("Failed to read key directory {:?}\nError: {:?}", dir, e)));
+                if files.is_empty() {
+                    eject(&format!("No .pub files in {:?}", dir));
+                }
+                public_key.extend(files);
+            }
+            let no_source = policy.is_none() && !github && expected_user.is_none() && github_team.is_none() && launchpad.is_none() && keys_url.is_none() && authorized_keys.is_empty() && known_hosts.is_empty() && trusted_ca.is_empty();
+            let key = key.iter().map(|line| {
+                keys::parse_public_key(line.as_bytes())
+                    .unwrap_or_else(|| eject(&format!("-K {:?} is not an ed25519 public key", line)))
+            }).collect::<Vec<_>>();
+            let public_key = if public_key.is_empty() && key.is_empty() && no_source {
+                vec![default_key("id_ed25519.pub")]
+            } else {
+                public_key
+            };
+
+            // Keys given locally, rather than fetched when verifying
+            let pinned = trust_embedded || !public_key.is_empty() || !key.is_empty();
+
+            let trusted_cas: Vec<_> = trusted_ca.iter().flat_map(|path| get_public_keys(Some(path.clone()), &None)).collect();
+
+            // Where each -k or -K key came from, to say which one matched
+            let mut key_files = vec![];
+
+            // Keys that do not depend on the github user each signature claims
+            let fixed_keys = match (&expected_user, &github_team, &launchpad, &keys_url) {
+                _ if trust_embedded => None,
+                _ if !public_key.is_empty() || !key.is_empty() => {
+                    let mut keys = vec![];
+                    for path in &public_key {
+                        for key in get_public_keys(Some(path.clone()), &None) {
+                            key_files.push((keys::fingerprint(&key), path.display().to_string()));
+                            keys.push(key);
+                        }
+                    }
+                    for key in key {
+                        key_files.push((keys::fingerprint(&key), "-K".to_string()));
+                        keys.push(key);
+                    }
+                    Some(keys)
+                }
+                (Some(user), _, _, _) => Some(forge::github_keys(user).unwrap_or_else(|e| {
+                    eject(&format!("Failed to get github keys for {}!\nError: {}", user, e))
+                })),
+                (None, Some(team), _, _) => Some(forge::github_team_keys(team).unwrap_or_else(|e| {
+                    eject(&format!("Failed to get github team keys!\nError: {}", e))
+                })),
+                (None, None, Some(user), _) => Some(forge::launchpad_keys(user).unwrap_or_else(|e| {
+                    eject(&format!("Failed to get Launchpad keys!\nError: {}", e))
+                })),
+                (None, None, None, Some(url)) => Some(forge::url_keys(url).unwrap_or_else(|e| {
+                    eject(&format!("Failed to get keys from {}!\nError: {}", url, e))
+                })),
+                (None, None, None, None) if !authorized_keys.is_empty() => Some(get_authorized_keys(&authorized_keys)),
+                (None, None, None, None) if !known_hosts.is_empty() => Some(get_known_host_keys(&known_hosts, host.as_deref(), &mut key_files)),
+                (None, None, None, None) => None,
+            };
+            // Comments of local keys, which email identities are checked against
+            let key_comments: Vec<(String, String)> = public_key
+                .iter()
+                .chain(&authorized_keys)
+                .filter(|_| require_claimed_identity)
+                .filter_map(|path| std::fs::read_to_string(path).ok())
+                .flat_map(|text| keys::parse_commented_keys(&text))
+                .map(|(key, comment)| (keys::fingerprint(&key), comment))
+                .collect();
+            let identity_checked = |fingerprint: String, identity: Option<&str>, github_user: Option<&str>| {
+                if !require_claimed_identity {
+                    return Ok(fingerprint);
+                }
+                check_identity(identity, github_user, &fingerprint, &key_comments).map(|_| fingerprint)
+            };
+
+            let mut warnings = vec![];
+            match (&expected_user, &github_team, &launchpad, &keys_url) {
+                _ if pinned => (),
+                (Some(user), _, _, _) => warnings.push(warning::Warning::network_keys(&format!("https://github.com/{}.keys", user))),
+                (None, Some(team), _, _) => warnings.push(warning::Warning::network_keys(&format!("the github team {}", team))),
+                (None, None, Some(user), _) => {
+                    warnings.push(warning::Warning::network_keys(&format!("https://launchpad.net/~{}/+sshkeys", user)))
+                }
+                (None, None, None, Some(url)) => warnings.push(warning::Warning::network_keys(url)),
+                (None, None, None, None) => (),
+            }
+            if let Some(policy) = &policy {
+                for user in policy.signers.iter().filter_map(|signer| signer.github.as_ref()) {
+                    warnings.push(warning::Warning::network_keys(&format!("https://github.com/{}.keys", user)));
+                }
+            }
+
+            // The claimed github user only chooses the keys when nothing else does
+            let github_checked = github && fixed_keys.is_none() && !trust_embedded;
+            let mut chains = vec![];
+            let mut policy_report = None;
+
+            // Each check is the signed data, the claimed github user and timestamp, and the outcome
+            let data;
+            let mut streamed = None;
+            let mut hints = BTreeMap::new();
+            let envelopes;
+            let checks: Vec<Check> = if let Some(sigpath) = &signature {
+                let sig = get_detached_signature(sigpath, max_envelope_size, max_depth);
+                data = if mmap {
+                    Box::new(get_mapped(&input)) as Box<dyn std::ops::Deref<Target = [u8]>>
+                } else if stream {
+                    Box::<Vec<u8>>::default()
+                } else {
+                    Box::new(get_input_limited(&input, max_input_size))
+                };
+                if stream {
+                    streamed = Some(stream_digest(&sig, &input));
+                }
+                let (github_user, timestamp, identity, metadata) = match &sig {
+                    DetachedSignature::SignIt(detached) => (
+                        detached.signer.github_user.clone(),
+                        detached.signer.timestamp.clone(),
+                        detached.signer.identity.as_deref(),
+                        detached.signer.metadata.clone(),
+                    ),
+                    DetachedSignature::Raw(_) => (None, None, None, BTreeMap::new()),
+                };
+                let covered = match (&streamed, &range) {
+                    (Some(digest), _) => Covered::Digest(digest),
+                    (None, Some(range)) => Covered::Range(range, &data),
+                    (None, None) => Covered::All(&data),
+                };
+                let signer = match &sig {
+                    DetachedSignature::SignIt(detached) => {
+                        hints = detached.hints.clone();
+                        Some(&detached.signer)
+                    }
+                    DetachedSignature::Raw(_) => None,
+                };
+                let result = check_claimed_user(github_user.as_deref(), &expected_user)
+                    .and_then(|_| signing_keys(signer, &fixed_keys, &trusted_cas, principal.as_deref(), clock_skew))
+                    .and_then(|signing| {
+                        let keys = if signing.is_some() { &signing } else { &fixed_keys };
+                        check_detached(&sig, covered, keys, github, &namespace, &expect_nonce, usage_policy.as_ref())
+                    })
+                    .and_then(|fingerprint| not_revoked(fingerprint, timestamp.as_deref()))
+                    .and_then(|fingerprint| identity_checked(fingerprint, identity, github_user.as_deref()));
+                vec![(&data[..], github_user, timestamp, metadata, result)]
+            } else {
+                let raw = message.unwrap_or_else(|| {
+                    String::from_utf8(get_input_limited(&input, Some(max_envelope_size)))
+                        .unwrap_or_else(|_| eject("Input is not UTF-8 text, which is all an envelope can hold!"))
+                });
+                // Documents signed in place are only JSON with --inline json
+                if inline.as_deref().map_or(true, |format| format == "json") {
+                    limits::check_depth(raw.as_bytes(), max_depth).unwrap_or_else(|e| eject(&format!("Refusing to parse the envelope, {}", e)));
+                }
+                envelopes = match &inline {
+                    Some(format) => vec![inline::extract(format, &raw).unwrap_or_else(|e| eject(&e))],
+                    None => envelope::parse_envelopes(&raw)
+                        .unwrap_or_else(|e| eject(&format!("Failed to parse message: {:?}\nError: {:?}", raw, e))),
+                };
+                // Every envelope of a bundle has the bundle's hints
+                hints = envelopes.first().map(|env| env.hints.clone()).unwrap_or_default();
+                if let Some(policy) = &policy {
+                    // The policy judges all the signatures together, so there is one outcome for the message
+                    let report = policy.evaluate(&envelopes, &revocations);
+                    let result = if report.passed {
+                        policy_report = Some(report.to_string());
+                        Ok(report.fingerprints.join(", "))
+                    } else {
+                        Err(("policy-violation", report.to_string()))
+                    };
+                    vec![(envelopes.first().map_or(&b""[..], |env| env.message.as_bytes()), None, None, BTreeMap::new(), result)]
+                } else {
+                    envelopes.iter().map(|msg| {
+                        let result = check_nonce(msg, &expect_nonce)
+                            .and_then(|_| check_claimed_user(msg.signer.github_user.as_deref(), &expected_user))
+                            .and_then(|_| signing_keys(Some(&msg.signer), &fixed_keys, &trusted_cas, principal.as_deref(), clock_skew))
+                            .and_then(|signing| {
+                                let keys = if signing.is_some() { &signing } else { &fixed_keys };
+                                check_envelope(msg, keys, github, trust_embedded, &namespace, usage_policy.as_ref())
+                            })
+                            .and_then(|fingerprint| not_revoked(fingerprint, msg.signer.timestamp.as_deref()))
+                            .and_then(|fingerprint| {
+                                identity_checked(fingerprint, msg.signer.identity.as_deref(), msg.signer.github_user.as_deref())
+                            })
+                            .and_then(|fingerprint| {
+                                check_countersigned(msg, &inner_key, github, usage_policy.as_ref())
+                                    .map(|_| fingerprint)
+                            })
+                            .and_then(|fingerprint| {
+                                if chain {
+                                    let signers = check_chain(msg, &fingerprint, github)?;
+                                    chains.push(signers);
+                                }
+                                Ok(fingerprint)
+                            });
+                        (msg.message.as_bytes(), msg.signer.github_user.clone(), msg.signer.timestamp.clone(), msg.signer.metadata.clone(), result)
+                    }).collect()
+                }
+            };
+
+            let mut failures = vec![];
+            let mut signers = vec![];
+            let mut verified = None;
+            let mut metadata = BTreeMap::new();
+            for (data, github_user, timestamp, signed_metadata, result) in checks {
+                if audit_log.is_enabled() {
+                    let (result_tag, fingerprint) = match &result {
+                        Ok(fingerprint) => ("verified", Some(fingerprint.clone())),
+                        Err((tag, _)) => (*tag, None),
+                    };
+                    let mut record = match &streamed {
+                        Some(digest) => audit::Record::with_digest("verify", digest.clone(), result_tag),
+                        None => audit::Record::new("verify", data, result_tag),
+                    };
+                    record.key_fingerprint = fingerprint;
+                    record.github_user = github_user.as_deref().filter(|_| github || expected_user.is_some());
+                    audit_log.write(record);
+                }
+
+                match result {
+                    Ok(fingerprint) => {
+                        match &github_user {
+                            Some(user) if github_checked => {
+                                warnings.push(warning::Warning::network_keys(&format!("https://github.com/{}.keys", user)))
+                            }
+                            Some(_) if expected_user.is_some() => (),
+                            Some(user) => warnings.push(warning::Warning::unchecked_github_user(&fingerprint, user)),
+                            None => (),
+                        }
+                        warnings.extend(warn_age.and_then(|max| warning::Warning::age(&fingerprint, timestamp.as_deref(), max, clock_skew)));
+                        warnings.extend(warning::Warning::future(&fingerprint, timestamp.as_deref(), clock_skew));
+                        if !signed_metadata.is_empty() {
+                            metadata.insert(fingerprint.clone(), signed_metadata);
+                        }
+                        signers.push(fingerprint);
+                        verified.get_or_insert(data);
+                    }
+                    Err((_, reason)) => failures.push(reason),
+                }
+            }
+
+            // Status goes to stderr when stdout carries the extracted message
+            let output = output.or_else(|| Some(PathBuf::from("-")).filter(|_| extract));
+            let to_stderr = exec.is_some() || output.as_deref().map_or(false, is_stdio);
+            let status = |line: String| if to_stderr { eprintln!("{}", line) } else { println!("{}", line) };
+
+            let mut unique: Vec<warning::Warning> = vec![];
+            for warning in warnings {
+                if !unique.iter().any(|seen| seen.message == warning.message) {
+                    unique.push(warning);
+                }
+            }
+            let warnings = unique;
+            let denied = deny_warnings && !warnings.is_empty();
+
+            if json {
+                let verified = !signers.is_empty() && !denied;
+                let report = warning::Report { verified, signers: &signers, metadata: &metadata, hints: &hints, failures: &failures, warnings: &warnings };
+                status(serde_json::to_string(&report).unwrap());
+                if !verified {
+                    eject("Verification failed!");
+                }
+            } else {
+                match (signers.len() + failures.len(), failures.len()) {
+                    (1, 1) => eject(&failures[0]),
+                    (total, failed) if failed == total => {
+                        eject(&format!("Verification failed!\n{}", failures.join("\n")))
+                    }
+                    _ if denied => {
+                        let messages: Vec<_> = warnings.iter().map(|w| format!("Warning: {}", w.message)).collect();
+                        eject(&format!("Verification failed, --deny-warnings was given!\n{}", messages.join("\n")))
+                    }
+                    (1, _) => status("Verified!".to_string()),
+                    (total, failed) => status(format!("Verified! ({} of {} signatures)", total - failed, total)),
+                }
+                for warning in &warnings {
+                    status(format!("Warning: {}", warning.message));
+                }
+
+                if let Some(report) = policy_report {
+                    status(report);
+                }
+                for (fingerprint, metadata) in &metadata {
+                    status(format!("Metadata signed by {}:", fingerprint));
+                    for (key, value) in metadata {
+                        status(format!("  {} = {}", key.escape_debug(), value.escape_debug()));
+                    }
+                }
+                if !hints.is_empty() {
+                    status("Hints, not covered by any signature:".to_string());
+                    for (key, value) in &hints {
+                        status(format!("  {} = {}", key.escape_debug(), value.escape_debug()));
+                    }
+                }
+            }
+
+            if key_files.len() > 1 {
+                for fingerprint in &signers {
+                    if let Some((_, path)) = key_files.iter().find(|(known, _)| known == fingerprint) {
+                        status(format!("Matched {} from {}", fingerprint, path));
+                    }
+                }
+            }
+
+            if randomart {
+                for fingerprint in &signers {
+                    status(format!("Signed by {}\n{}", fingerprint, keys::randomart(fingerprint)));
+                }
+            }
+
+            for signers in chains {
+                status("Signers, in order:".to_string());
+                for (i, signer) in signers.iter().enumerate() {
+                    status(format!("  {}. {}", i + 1, signer));
+                }
+            }
+
+            // Only the message of an envelope that checked out is ever written
+            if let (Some(opath), Some(message)) = (output, verified) {
+                write_file(&opath, message);
+            }
+            if let (Some(command), Some(message)) = (exec, verified) {
+                let status = exec::run(&command, message)
+                    .unwrap_or_else(|e| eject(&e));
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        },
+        Commands::Chain { input, output, message, private_key, github, pretty } => {
+            let raw = get_message(message, &input);
+            let mut env: SignIt = serde_json::from_str(&raw)
+                .unwrap_or_else(|e| {
+                    eject(&format!("Failed to parse envelope: {:?}\nError: {:?}", raw, e))
+                });
+
+            let secret = get_private_key(private_key);
+            chain::append(&mut env, &secret, github)
+                .unwrap_or_else(|e| eject(&e));
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&env).unwrap();
+
+            write_or_print(output, outstr);
+        },
+        Commands::Revoke { private_key, reason, since, compromised, output, pretty } => {
+            let key_path = private_key_path(private_key);
+            let secret = get_private_key(Some(key_path.clone()));
+            let public = secret.clone_public_key();
+
+            let now = envelope::timestamp();
+            let revocation = revoke::Revocation {
+                fingerprint: keys::fingerprint(&public),
+                revoked_at: since.map_or_else(|| now.clone(), |since| since.rfc3339().to_string()),
+                reason,
+                compromised,
+            };
+            let mut out = SignIt {
+                message: serde_json::to_string(&revocation).unwrap(),
+                signer: Signer {
+                    namespace: Some(revoke::NAMESPACE.to_string()),
+                    timestamp: Some(now),
+                    key: Some(keys::embed(&public, keys::comment_for(&key_path))),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            sign_signit(&secret, &mut out);
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&out).unwrap();
+            write_or_print(output, outstr);
+        },
+        Commands::Rotate { old_key, new_key, cross_sign, output, pretty, paths } => {
+            let old = get_private_key(Some(old_key.clone()));
+            let new = get_private_key(Some(new_key.clone()));
+            let (old_public, new_public) = (old.clone_public_key(), new.clone_public_key());
+            if keys::fingerprint(&old_public) == keys::fingerprint(&new_public) {
+                eject("The old and new keys are the same key!");
+            }
+
+            // Both keys sign, so the statement proves the holder of the old key chose the new one
+            let date = time::now_utc().rfc3339().to_string();
+            let mut statement = SignIt {
+                message: rotate::statement(&old_public, &new_public, &date),
+                signer: Signer { namespace: Some(rotate::NAMESPACE.to_string()), ..Default::default() },
+                ..Default::default()
+            };
+            let mut bundle = Bundle { message: statement.message.clone(), ..Default::default() };
+            for (secret, path) in &[(&old, &old_key), (&new, &new_key)] {
+                statement.signer.key = Some(keys::embed(&secret.clone_public_key(), keys::comment_for(path)));
+                sign_signit(secret, &mut statement);
+                bundle.signatures.push(statement.signer.clone());
+            }
+
+            let mut failures = vec![];
+            for path in &paths {
+                let rotated = rotate::Signed::parse(&get_message(None, &Some(path.clone())))
+                    .and_then(|signed| rotate::rotate(signed, &old_public, &new, keys::comment_for(&new_key), cross_sign));
+                match rotated {
+                    Ok(signed) => {
+                        write_file(path, signed.to_string(pretty).as_bytes());
+                        eprintln!("Re-signed {}", path.display());
+                    }
+                    Err(e) => failures.push(format!("{}: {}", path.display(), e)),
+                }
+            }
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&bundle).unwrap();
+            write_or_print(output, outstr);
+
+            if !failures.is_empty() {
+                eject(&format!("Failed to rotate {} of {} files!\n{}", failures.len(), paths.len(), failures.join("\n")));
+            }
+        },
+        Commands::Countersign { input, output, message, private_key, github, namespace, pretty } => {
+            let inner = get_message(message, &input);
+            envelope::parse_envelopes(&inner)
+                .unwrap_or_else(|e| {
+                    eject(&format!("Failed to parse envelope to countersign: {:?}\nError: {:?}", inner, e))
+                });
+
+            let secret = get_private_key(private_key);
+            let mut out = SignIt {
+                message: inner.trim_end().to_string(),
+                signer: Signer {
+                    namespace,
+                    countersign: true,
+                    github_user: github,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            sign_signit(&secret, &mut out);
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&out).unwrap();
+
+            write_or_print(output, outstr);
+        },
+        Commands::Seal { recipient, input, output, message, private_key, github, namespace, pretty } => {
+            let recipients = if recipient == "-" || Path::new(&recipient).exists() {
+                get_public_keys(Some(PathBuf::from(recipient)), &None)
+            } else {
+                get_public_keys(None, &Some(recipient))
+            };
+
+            let secret = get_private_key(private_key);
+            let mut signed = SignIt {
+                message: get_message(message, &input),
+                signer: Signer { namespace, github_user: github, ..Default::default() },
+                ..Default::default()
+            };
+            signed.signer.metadata.insert(seal::RECIPIENTS.to_string(), seal::recipients(&recipients));
+            sign_signit(&secret, &mut signed);
+
+            let plaintext = envelope::canonical(&signed).unwrap().into_bytes();
+            let sealed = seal::seal(&plaintext, &recipients)
+                .unwrap_or_else(|e| eject(&e));
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&sealed).unwrap();
+
+            write_or_print(output, outstr);
+        },
+        Commands::Watch { dir, out, private_key, github, namespace, pretty, interval, once, exclude } => {
+            let secret = get_private_key(private_key);
+            std::fs::create_dir_all(&out)
+                .unwrap_or_else(|e| eject(&format!("Failed to create {:?}\nError: {:?}", out, e)));
+            let canonical = |path: &Path| {
+                path.canonicalize()
+                    .unwrap_or_else(|e| eject(&format!("Failed to open {:?}\nError: {:?}", path, e)))
+            };
+            let (dir, out) = (canonical(&dir), canonical(&out));
+
+            loop {
+                let pending = watch::pending(&dir, &out, &exclude)
+                    .unwrap_or_else(|e| eject(&format!("Failed to scan {:?}\nError: {:?}", dir, e)));
+
+                for file in pending {
+                    // Files can vanish between the scan and now, which is not worth stopping for
+                    let digest = match std::fs::File::open(&file.file).and_then(envelope::digest) {
+                        Ok(digest) => digest,
+                        Err(e) => {
+                            eprintln!("Skipping {:?}: {}", file.name, e);
+                            continue;
+                        }
+                    };
+
+                    let mut detached = Detached {
+                        digest,
+                        chunks: None,
+                        signer: Signer {
+                            namespace: namespace.clone(),
+                            github_user: github.clone(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    detached.signer.signature = signature_over(&secret, &detached.signed_bytes());
+
+                    let outstr = if pretty {
+                        serde_json::to_string_pretty
+                    } else {
+                        envelope::canonical
+                    }(&detached).unwrap();
+
+                    if let Some(parent) = file.signature.parent() {
+                        std::fs::create_dir_all(parent)
+                            .unwrap_or_else(|e| eject(&format!("Failed to create {:?}\nError: {:?}", parent, e)));
+                    }
+                    write_file(&file.signature, outstr.as_bytes());
+                    eprintln!("Signed {}", file.name.display());
+                }
+
+                if once {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_secs(interval));
+            }
+        },
+        Commands::VerifyStream { public_key, namespace, batch, max_envelope_size, max_depth } => {
+            use std::io::Write;
+
+            let store = KeyStore::new(public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect());
+            let mut lines = limits::Lines::new(std::io::BufReader::new(take_stdin()), max_envelope_size).enumerate().peekable();
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            let failed_write = |e: std::io::Error| eject(&format!("Failed to write results\nError: {:?}", e));
+
+            while lines.peek().is_some() {
+                // Each line is an envelope or a bundle, so it holds one or more envelopes
+                let mut counts = vec![];
+                let mut envelopes = vec![];
+                for (number, line) in lines.by_ref().take(batch.unwrap_or(1).max(1)) {
+                    let line = match line {
+                        Ok(line) => line,
+                        // Too long or not UTF-8, which fails this line and not the stream
+                        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                            counts.push((number + 1, Err(format!("Line is invalid, {}", e))));
+                            continue;
+                        }
+                        Err(e) => eject(&format!("Failed to read stdin\nError: {:?}", e)),
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = limits::check_depth(line.as_bytes(), max_depth) {
+                        counts.push((number + 1, Err(e)));
+                        continue;
+                    }
+                    match envelope::parse_envelopes(&line) {
+                        Ok(mut parsed) => {
+                            counts.push((number + 1, Ok(parsed.len())));
+                            envelopes.append(&mut parsed);
+                        }
+                        Err(e) => counts.push((number + 1, Err(format!("{:?}", e)))),
+                    }
+                }
+
+                let verified = store.verify_batch(&envelopes);
+                let mut results = verified.into_iter().zip(&envelopes);
+                for (number, count) in counts {
+                    let result = match count {
+                        Ok(count) => {
+                            let signers: Vec<String> = results
+                                .by_ref()
+                                .take(count)
+                                .filter(|(_, env)| namespace.is_none() || env.signer.namespace == namespace)
+                                .filter_map(|(key, _)| key.map(keys::fingerprint))
+                                .collect();
+                            serde_json::json!({ "line": number, "verified": !signers.is_empty(), "signers": signers })
+                        }
+                        Err(e) => serde_json::json!({ "line": number, "verified": false, "error": e }),
+                    };
+                    writeln!(out, "{}", result).unwrap_or_else(failed_write);
+                }
+                out.flush().unwrap_or_else(failed_write);
+            }
+        },
+        Commands::Show { input } => {
+            let raw = get_message(None, &input);
+            let description = show::describe(&raw)
+                .unwrap_or_else(|e| eject(&e));
+            println!("{}", description);
+        },
+        Commands::WhoSigned { signature, allowed_signers } => {
+            let entries = allowed::parse(&get_message(None, &Some(allowed_signers.clone())))
+                .unwrap_or_else(|e| eject(&format!("{:?}: {}", allowed_signers, e)));
+
+            // Detached signatures cover their digest, so the data itself is not needed
+            let raw = get_message(None, &Some(signature.clone()));
+            let signed: Vec<(Vec<u8>, Signer)> = match serde_json::from_str::<Detached>(&raw) {
+                Ok(detached) => vec![(detached.signed_bytes(), detached.signer)],
+                Err(_) => envelope::parse_envelopes(&raw)
+                    .unwrap_or_else(|e| eject(&format!("{:?} is not a signature\nError: {:?}", signature, e)))
+                    .into_iter()
+                    .map(|env| (env.signed_bytes(), env.signer))
+                    .collect(),
+            };
+
+            let mut principals: Vec<String> = vec![];
+            for (bytes, signer) in &signed {
+                let sig = decode(&signer.signature).unwrap_or_else(|_| eject("Signature not proper base64!"));
+                for principal in entries.iter().flat_map(|entry| entry.principals_for(bytes, &sig, signer)) {
+                    if !principals.contains(&principal) {
+                        principals.push(principal);
+                    }
+                }
+            }
+            if principals.is_empty() {
+                eject(&format!("No principal in {:?} made the signature", allowed_signers));
+            }
+            for principal in principals {
+                println!("{}", principal);
+            }
+        },
+        Commands::Validate { input, pretty } => {
+            let report = validate::validate(&get_message(None, &input));
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&report).unwrap();
+
+            println!("{}", outstr);
+            if !report.valid {
+                eject("Envelope is not valid!");
+            }
+        },
+        Commands::Compare { first, second } => {
+            let load = |path: &PathBuf| {
+                compare::load(&get_message(None, &Some(path.clone())))
+                    .unwrap_or_else(|e| eject(&format!("{:?}: {}", path, e)))
+            };
+            let (a, b) = (load(&first), load(&second));
+            let names = [first.to_string_lossy(), second.to_string_lossy()];
+
+            println!("{}", compare::report([&names[0], &names[1]], [&a, &b]));
+            if a.digest != b.digest {
+                eject("Different messages!");
+            }
+            println!("Same message");
+        },
+        Commands::Canonicalize { input, output, check } => {
+            let raw = get_message(None, &input);
+            let canonical = envelope::canonicalize(&raw)
+                .unwrap_or_else(|e| eject(&format!("Failed to parse envelope\nError: {:?}", e)));
+            if check {
+                // Writing to stdout adds a newline, so a file saved from it is canonical too
+                if raw.strip_suffix('\n').unwrap_or(&raw) != canonical {
+                    eject("Not in canonical form, rewrite it with signit canonicalize!");
+                }
+                println!("Canonical");
+            } else {
+                write_or_print(output, canonical);
+            }
+        },
+        Commands::Hints { input, set, unset, output } => {
+            let raw = get_message(None, &input);
+            if set.is_empty() && unset.is_empty() {
+                if output.is_some() {
+                    eject("Give --set or --unset to change the hints");
+                }
+                let hints = envelope::hints(&raw)
+                    .unwrap_or_else(|e| eject(&format!("Failed to parse envelope\nError: {:?}", e)));
+                for (key, value) in hints {
+                    println!("{} = {}", key.escape_debug(), value.escape_debug());
+                }
+            } else {
+                let changed = envelope::rewrite(&raw, |hints| {
+                    for key in &unset {
+                        hints.remove(key);
+                    }
+                    hints.extend(set);
+                })
+                .unwrap_or_else(|e| eject(&format!("Failed to parse envelope\nError: {:?}", e)));
+                write_or_print(output, changed);
+            }
+        },
+        Commands::ExportC { key, sig, lang, prefix, output } => {
+            let public = get_public_keys(Some(key), &None);
+            let raw = get_message(None, &Some(sig));
+
+            // Only a signature that checks out is worth compiling into a device
+            let signed = match serde_json::from_str::<Detached>(&raw) {
+                Ok(detached) => vec![(detached.signed_bytes(), detached.signer.signature)],
+                Err(_) => envelope::parse_envelopes(&raw)
+                    .unwrap_or_else(|e| eject(&format!("Failed to parse signature\nError: {:?}", e)))
+                    .into_iter()
+                    .map(|env| (env.signed_bytes(), env.signer.signature))
+                    .collect(),
+            };
+            let (signed, signature, signer) = signed.into_iter()
+                .filter_map(|(bytes, signature)| Some((bytes, decode(&signature).ok()?)))
+                .find_map(|(bytes, signature)| {
+                    let signer = keys::find_key(&bytes, &signature, &public)?;
+                    Some((bytes, signature, signer))
+                })
+                .unwrap_or_else(|| eject("Verification failed!"));
+
+            let public_key = keys::raw_ed25519(signer)
+                .unwrap_or_else(|| eject("Specified key was not an Ed25519 key!"));
+            let vectors = export::Vectors {
+                fingerprint: &keys::fingerprint(signer),
+                public_key: &public_key,
+                signature: &signature,
+                digest: &openssl::sha::sha256(&signed),
+                signed_len: signed.len(),
+            };
+            write_or_print(output, export::source(&lang, &prefix, &vectors));
+        },
+        Commands::Fingerprint { key, randomart } => {
+            let path = key.unwrap_or_else(|| default_key("id_ed25519.pub"));
+
+            let public = match get_any_key(path) {
+                AnyKey::Public(public) => public,
+                AnyKey::Private(secret) => secret.clone_public_key(),
+            };
+            let fingerprint = keys::fingerprint(&public);
+
+            println!("{}", fingerprint);
+            if randomart {
+                println!("{}", keys::randomart(&fingerprint));
+            }
+        },
+        Commands::Pubkey { private_key, format, key_encoding } => {
+            let key_path = private_key_path(private_key);
+            let public = get_private_key(Some(key_path.clone())).clone_public_key();
+            let raw = keys::raw_ed25519(&public)
+                .unwrap_or_else(|| eject("Specified or detected key was not an Ed25519 key!"));
+
+            if let Some(encoding) = key_encoding {
+                println!("{}", keys::multikey(&raw, &encoding));
+                return;
+            }
+            match format.as_str() {
+                "raw" => println!("{}", encode(&raw)),
+                "pem" => println!("{}", keys::pem(&raw)),
+                "did-key" => println!("{}", keys::did_key(&raw)),
+                _ => match keys::comment_for(&key_path) {
+                    Some(comment) => println!("{} {}", keys::openssh_line(&public), comment),
+                    None => println!("{}", keys::openssh_line(&public)),
+                },
+            }
+        },
+        Commands::Key(KeyCommands::X25519 { key, age }) => {
+            let path = key.unwrap_or_else(|| default_key("id_ed25519.pub"));
+
+            let secret = match get_any_key(path) {
+                AnyKey::Private(secret) => secret,
+                AnyKey::Public(public) => {
+                    let converted = match public {
+                        PublicKey::Ed25519(ed) => x25519::public_key(&ed.key),
+                        _ => eject("Specified or detected key was not an Ed25519 key!"),
+                    }
+                    .unwrap_or_else(|| eject("Public key has no X25519 counterpart!"));
+
+                    if age {
+                        println!("{}", x25519::age_recipient(&converted));
+                    } else {
+                        println!("{}", encode(&converted));
+                    }
+                    return;
+                }
+            };
+
+            let converted = match &*secret {
+                KeyPair::Ed25519(sk) => Zeroizing::new(x25519::secret_key(&sk.key[..32])),
+                _ => eject("Specified or detected key was not an Ed25519 key!"),
+            };
+
+            if age {
+                println!("{}", Zeroizing::new(x25519::age_identity(&converted)).as_str());
+            } else {
+                println!("{}", Zeroizing::new(encode(&converted[..])).as_str());
+            }
+        },
+        Commands::Key(KeyCommands::Convert { private_key, output, format, encrypt }) => {
+            if encrypt && format != "pkcs8" {
+                eject("Only pkcs8 output can be encrypted, use `ssh-keygen -p` to encrypt OpenSSH keys!");
+            }
+
+            let key_path = private_key_path(private_key);
+            let secret = get_private_key(Some(key_path.clone()));
+
+            let passphrase = if encrypt {
+                let first = secret::prompt_passphrase("New passphrase: ")
+                    .unwrap_or_else(|e| eject(&format!("Failed to read passphrase\nError: {:?}", e)));
+                let second = secret::prompt_passphrase("Repeat passphrase: ")
+                    .unwrap_or_else(|e| eject(&format!("Failed to read passphrase\nError: {:?}", e)));
+                if first != second {
+                    eject("Passphrases do not match!");
+                }
+                Some(first)
+            } else {
+                None
+            };
+
+            let encoded = match format.as_str() {
+                "pkcs8" => keyfile::encode_pkcs8(&secret, passphrase.as_ref().map(|p| p.as_bytes())),
+                "raw32" => keyfile::encode_raw(&secret, 32),
+                "raw64" => keyfile::encode_raw(&secret, 64),
+                _ => keyfile::encode_openssh(&secret, &keys::comment_for(&key_path).unwrap_or_default()),
+            }
+            .unwrap_or_else(|e| eject(&e));
+
+            write_private(output, &encoded);
+        },
+        Commands::Hooks(HookCommands::Install { hooks, repo, private_key, github, force }) => {
+            let hooks = if hooks.is_empty() {
+                hooks::HOOKS.iter().map(|h| h.to_string()).collect()
+            } else {
+                hooks
+            };
+            let repo = repo.unwrap_or_else(|| PathBuf::from("."));
+
+            // The hooks run from the repository, so relative key paths would break
+            let private_key = private_key.map(|key| {
+                canonical_path(&key)
+                    .unwrap_or_else(|e| eject(&format!("Failed to find key {:?}\nError: {:?}", key, e)))
+            });
+
+            let options = hooks::Options { private_key, github };
+            for path in hooks::install(&repo, &hooks, &options, force).unwrap_or_else(|e| eject(&e)) {
+                println!("Installed {}", path.display());
+            }
+        },
+        Commands::Firmware(FirmwareCommands::Sign { image, private_key, output, append }) => {
+            let secret = get_private_key(private_key);
+            let public = keys::raw_ed25519(&secret.clone_public_key())
+                .unwrap_or_else(|| eject("Specified or detected key was not an Ed25519 key!"));
+            let image = get_input(&Some(image));
+
+            let mut header = firmware::Header::new(&image, &public).unwrap_or_else(|e| eject(&e));
+            header.signature = signature_bytes(&secret, &header.signed_bytes());
+
+            let header = header.to_bytes();
+            let signed = if append {
+                [&image[..], &header[..]].concat()
+            } else {
+                [&header[..], &image[..]].concat()
+            };
+            write_file(&output.unwrap_or_else(|| PathBuf::from("-")), &signed);
+        },
+        Commands::Firmware(FirmwareCommands::Verify { image, public_key, output }) => {
+            let public = get_public_keys(Some(public_key.unwrap_or_else(|| default_key("id_ed25519.pub"))), &None);
+            let signed = get_input(&Some(image));
+
+            let (header, image) = firmware::split(&signed).unwrap_or_else(|e| eject(&e));
+            let signer = keys::find_key(&header.signed_bytes(), &header.signature, &public)
+                .unwrap_or_else(|| eject("Verification failed!"));
+
+            // Bootloaders pick the key by id, so it has to name the key that signed
+            let raw = keys::raw_ed25519(signer).unwrap_or_else(|| eject("Specified key was not an Ed25519 key!"));
+            if header.key_id != firmware::key_id(&raw) {
+                eject("Header key id does not match the signing key!");
+            }
+
+            // The image goes to stdout only when asked for, so status stays out of it
+            if let Some(output) = output {
+                eprintln!("Verified! Signed by {}", keys::fingerprint(signer));
+                write_file(&output, image);
+            } else {
+                println!("Verified! Signed by {}", keys::fingerprint(signer));
+            }
+        },
+        Commands::Agent(AgentCommands::List) => {
+            let identities = agent::identities().unwrap_or_else(|e| eject(&e));
+            if identities.is_empty() {
+                eject("ssh-agent has no keys loaded, add one with ssh-add");
+            }
+            for identity in identities {
+                println!("{} {} {}", identity.key_type(), identity.fingerprint(), identity.comment);
+            }
+        },
+        Commands::Policy(PolicyCommands::Check { policy, input, mut revocations }) => {
+            let policy = policy::Policy::load(&policy);
+            revocations.extend(policy.revocations.iter().cloned());
+            let revocations = get_revocations(&revocations);
+
+            let report = policy.explain(&get_envelopes(None, &input), &revocations);
+            if !report.passed {
+                eject(&report.to_string());
+            }
+            println!("{}", report);
+        },
+        Commands::Http(HttpCommands::Sign { input, output, private_key, components, keyid, expires, nonce, label }) => {
+            let secret = get_private_key(private_key);
+            let mut request = httpsig::Request::parse(&get_input(&input)).unwrap_or_else(|e| eject(&e));
+
+            let mut components = if components.is_empty() {
+                httpsig::DEFAULT_COMPONENTS.iter().map(|c| c.to_string()).collect()
+            } else {
+                components
+            };
+            if request.has_body() && !components.iter().any(|c| c == "content-digest") {
+                components.push("content-digest".to_string());
+            }
+            if components.iter().any(|c| c == "content-digest") && request.header("content-digest").is_none() {
+                let digest = request.content_digest();
+                request.add_header("Content-Digest", digest);
+            }
+
+            let created = time::now_utc().to_timespec().sec;
+            let params = httpsig::Params {
+                components,
+                created: Some(created),
+                expires: expires.map(|seconds| created + seconds),
+                keyid: Some(keyid.unwrap_or_else(|| keys::fingerprint(&secret.clone_public_key()))),
+                alg: Some(httpsig::ALG.to_string()),
+                nonce,
+            };
+            httpsig::sign(&mut request, &label, &params, |base| signature_bytes(&secret, base))
+                .unwrap_or_else(|e| eject(&e));
+
+            write_file(&output.unwrap_or_else(|| PathBuf::from("-")), &request.to_bytes());
+        },
+        Commands::Http(HttpCommands::Verify { input, public_key, label, max_age, components }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+            let request = httpsig::Request::parse(&get_input(&input)).unwrap_or_else(|e| eject(&e));
+
+            let sig = httpsig::signature(&request, label.as_deref()).unwrap_or_else(|e| eject(&e));
+            let signer = keys::find_key(&sig.base, &sig.signature, &keys)
+                .unwrap_or_else(|| eject("Verification failed!"));
+
+            let now = time::now_utc().to_timespec().sec;
+            if sig.params.expires.map_or(false, |expires| now >= expires) {
+                eject(&format!("Signature {} has expired!", sig.label));
+            }
+            if let Some(max_age) = max_age {
+                match sig.params.created {
+                    Some(created) if now.saturating_sub(created) <= max_age.num_seconds() => (),
+                    Some(_) => eject(&format!("Signature {} was created more than {} seconds ago!", sig.label, max_age.num_seconds())),
+                    None => eject(&format!("Signature {} has no created time!", sig.label)),
+                }
+            }
+            if let Some(missing) = components.iter().find(|c| !sig.params.components.contains(c)) {
+                eject(&format!("Signature {} does not cover {}!", sig.label, missing));
+            }
+
+            println!("Verified! Signed by {}", keys::fingerprint(signer));
+        },
+        Commands::Jwt(JwtCommands::Sign { claims, output, private_key, expires, kid }) => {
+            let secret = get_private_key(private_key);
+            let raw = get_input(&claims);
+            let mut claims: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&raw)
+                .unwrap_or_else(|e| eject(&format!("Claims are not a JSON object\nError: {:?}", e)));
+
+            let now = time::now_utc().to_timespec().sec;
+            claims.entry("iat").or_insert_with(|| now.into());
+            if let Some(seconds) = expires {
+                claims.insert("exp".to_string(), (now + seconds).into());
+            }
+
+            let kid = kid.unwrap_or_else(|| keys::fingerprint(&secret.clone_public_key()));
+            let signing_input = jwt::signing_input(&kid, &claims);
+            let token = jwt::finish(&signing_input, &signature_bytes(&secret, signing_input.as_bytes()));
+            write_or_print(output, token);
+        },
+        Commands::Jwt(JwtCommands::Verify { input, public_key, audience, issuer, leeway, pretty }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+
+            let token = jwt::Token::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e));
+            let signer = keys::find_key(token.signed.as_bytes(), &token.signature, &keys)
+                .unwrap_or_else(|| eject("Verification failed!"));
+
+            token.check_time(time::now_utc().to_timespec().sec, leeway).unwrap_or_else(|e| eject(&e));
+            if let Some(audience) = &audience {
+                token.check_audience(audience).unwrap_or_else(|e| eject(&e));
+            }
+            if let Some(issuer) = &issuer {
+                token.check_issuer(issuer).unwrap_or_else(|e| eject(&e));
+            }
+
+            // The claims go to stdout for scripts, so status stays out of them
+            eprintln!("Verified! Signed by {}", keys::fingerprint(signer));
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&token.claims).unwrap();
+            println!("{}", outstr);
+        },
+        Commands::Paseto(PasetoCommands::Sign { claims, output, private_key, expires, footer, implicit }) => {
+            let secret = get_private_key(private_key);
+            let raw = get_input(&claims);
+            let mut claims: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&raw)
+                .unwrap_or_else(|e| eject(&format!("Claims are not a JSON object\nError: {:?}", e)));
+
+            let now = time::now_utc();
+            claims.entry("iat").or_insert_with(|| paseto::format_time(&now).into());
+            if let Some(seconds) = expires {
+                claims.insert("exp".to_string(), paseto::format_time(&(now + time::Duration::seconds(seconds))).into());
+            }
+
+            let message = serde_json::to_vec(&claims).unwrap();
+            let footer = footer.unwrap_or_default();
+            let signed = paseto::signed_bytes(&message, footer.as_bytes(), implicit.unwrap_or_default().as_bytes());
+            write_or_print(output, paseto::finish(&message, &signature_bytes(&secret, &signed), footer.as_bytes()));
+        },
+        Commands::Paseto(PasetoCommands::Verify { input, public_key, audience, issuer, footer, implicit, pretty }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+
+            let implicit = implicit.unwrap_or_default();
+            let token = paseto::Token::parse(&get_message(None, &input), implicit.as_bytes()).unwrap_or_else(|e| eject(&e));
+            let signer = keys::find_key(&token.signed, &token.signature, &keys)
+                .unwrap_or_else(|| eject("Verification failed!"));
+
+            token.check_time(&time::now_utc()).unwrap_or_else(|e| eject(&e));
+            if let Some(footer) = &footer {
+                if token.footer != footer.as_bytes() {
+                    eject("Token footer is not the expected one!");
+                }
+            }
+            for (claim, expected) in [("aud", &audience), ("iss", &issuer)] {
+                if let Some(expected) = expected {
+                    token.check_claim(claim, expected).unwrap_or_else(|e| eject(&e));
+                }
+            }
+
+            // The claims go to stdout for scripts, so status stays out of them
+            eprintln!("Verified! Signed by {}", keys::fingerprint(signer));
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&token.claims).unwrap();
+            println!("{}", outstr);
+        },
+        Commands::Sbom(SbomCommands::Sign { input, output, private_key, pretty }) => {
+            let secret = get_private_key(private_key);
+            let mut bom = sbom::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e));
+            let signed = sbom::prepare(&mut bom, &secret.clone_public_key()).unwrap_or_else(|e| eject(&e));
+            sbom::finish(&mut bom, &signature_bytes(&secret, &signed));
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&bom).unwrap();
+            write_or_print(output, outstr);
+        },
+        Commands::Sbom(SbomCommands::Verify { input, public_key }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+
+            let bom = sbom::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e));
+            let (signed, signature) = sbom::signature(&bom).unwrap_or_else(|e| eject(&e));
+            let signer = keys::find_key(&signed, &signature, &keys).unwrap_or_else(|| eject("Verification failed!"));
+            println!("Verified! Signed by {}", keys::fingerprint(signer));
+        },
+        Commands::Prove(ProveCommands::Github { user, private_key, output, gist, pretty }) => {
+            let key_path = private_key_path(private_key);
+            let secret = get_private_key(Some(key_path.clone()));
+            let mut out = proof::new(&format!("github:{}", user));
+            out.signer.key = Some(keys::embed(&secret.clone_public_key(), keys::comment_for(&key_path)));
+            sign_signit(&secret, &mut out);
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&out).unwrap();
+            if gist {
+                let url = forge::create_gist(&out.message, proof::GIST_FILE, &outstr).unwrap_or_else(|e| eject(&e));
+                eprintln!("Published to {}", url);
+            }
+            write_or_print(output, outstr);
+        },
+        Commands::Prove(ProveCommands::Profile { claims, private_key, output, pretty }) => {
+            let key_path = private_key_path(private_key);
+            let secret = get_private_key(Some(key_path.clone()));
+            let public = secret.clone_public_key();
+            let claims: Vec<_> = claims
+                .iter()
+                .map(|claim| proof::Claim::parse(claim).unwrap_or_else(|e| eject(&e)))
+                .collect();
+
+            let mut out = proof::profile(&public, &claims);
+            out.signer.key = Some(keys::embed(&public, keys::comment_for(&key_path)));
+            sign_signit(&secret, &mut out);
+
+            eprintln!("Each account must now vouch for the key:");
+            for claim in &claims {
+                match claim {
+                    proof::Claim::Forge { .. } => eprintln!("  {}: add the key to the account's SSH keys", claim),
+                    _ => eprintln!("  {}: publish {}", claim, proof::backlink(&keys::fingerprint(&public))),
+                }
+            }
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&out).unwrap();
+            write_or_print(output, outstr);
+        },
+        Commands::Prove(ProveCommands::Check { input, public_key }) => {
+            let profile: SignIt = serde_json::from_str(&get_message(None, &input))
+                .unwrap_or_else(|e| eject(&format!("Profile is not an envelope\nError: {:?}", e)));
+            let (fingerprint, claims) = proof::claims(&profile).unwrap_or_else(|e| eject(&e));
+
+            // The profile speaks for its own key, which the claimed accounts then vouch for
+            let keys: Vec<_> = match public_key {
+                Some(path) => get_public_keys(Some(path), &None),
+                None => profile.signer.key.iter().filter_map(|key| keys::parse_openssh_line(&key.public_key)).collect(),
+            };
+            let signer = find_signer(&profile, &keys)
+                .filter(|signer| keys::fingerprint(signer) == fingerprint)
+                .unwrap_or_else(|| eject("Verification failed! The profile was not signed by the key it names"));
+
+            let mut failed = 0;
+            for claim in &claims {
+                match claim.check(signer) {
+                    Ok(()) => println!("Verified {}", claim),
+                    Err(e) => {
+                        println!("Failed {}: {}", claim, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                eject(&format!("{} of {} claims failed for {}!", failed, claims.len(), fingerprint));
+            }
+            println!("All {} claims hold for {}", claims.len(), fingerprint);
+        },
+        Commands::Prove(ProveCommands::Verify { input, gist }) => {
+            let (proof, owner) = match &gist {
+                Some(gist) => {
+                    let (owner, files) = forge::gist(gist).unwrap_or_else(|e| eject(&e));
+                    let content = files
+                        .into_iter()
+                        .find(|(name, _)| name == proof::GIST_FILE)
+                        .map(|(_, content)| content)
+                        .unwrap_or_else(|| eject(&format!("Gist has no {} file!", proof::GIST_FILE)));
+                    (content, Some(owner))
+                }
+                None => (get_message(None, &input), None),
+            };
+            let proof: SignIt = serde_json::from_str(&proof)
+                .unwrap_or_else(|e| eject(&format!("Proof is not an envelope\nError: {:?}", e)));
+
+            let identity = proof::identity(&proof).unwrap_or_else(|e| eject(&e));
+            let user = identity
+                .strip_prefix("github:")
+                .unwrap_or_else(|| eject(&format!("Proof is for {}, not a github user!", identity)));
+            if let Some(owner) = &owner {
+                if !owner.eq_ignore_ascii_case(user) {
+                    eject(&format!("Proof is for github:{}, but the gist belongs to {}!", user, owner));
+                }
+            }
+            let keys = get_public_keys(None, &Some(user.to_string()));
+            let signer = find_signer(&proof, &keys)
+                .unwrap_or_else(|| eject(&format!("Verification failed! The proof was not signed by a key of github:{}", user)));
+
+            println!(
+                "Verified! {} holds {}, as of {}{}",
+                identity,
+                keys::fingerprint(signer),
+                proof.signer.timestamp.as_deref().unwrap(),
+                if owner.is_some() { ", as published in their gist" } else { "" },
+            );
+        },
+        Commands::Ca(CaCommands::Issue { delegate, namespace, expires, private_key, output, pretty }) => {
+            let secret = get_private_key(private_key);
+            let delegated = get_public_keys(Some(delegate.clone()), &None);
+            if delegated.len() != 1 {
+                eject(&format!("{:?} must hold exactly one key to delegate to", delegate));
+            }
+
+            let mut out = delegate::new(&delegated[0], &namespace, expires.into());
+            sign_signit(&secret, &mut out);
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&out).unwrap();
+            write_or_print(output, outstr);
+        },
+        Commands::Challenge(ChallengeCommands::New { context, expires, output }) => {
+            let challenge = challenge::Challenge::new(&context, expires.into());
+            write_or_print(output, serde_json::to_string(&challenge).unwrap());
+        },
+        Commands::Challenge(ChallengeCommands::Verify { challenge, input, public_key, github }) => {
+            let keys: Vec<_> = if public_key.is_empty() {
+                get_public_keys(None, &Some(github.unwrap_or_else(|| eject("Give the user's keys with -k or -g!"))))
+            } else {
+                public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect()
+            };
+            let challenge = challenge::Challenge::parse(&get_message(None, &Some(challenge))).unwrap_or_else(|e| eject(&e));
+            let response: SignIt = serde_json::from_str(&get_message(None, &input))
+                .unwrap_or_else(|e| eject(&format!("Response is not an envelope\nError: {:?}", e)));
+
+            challenge.check(&response).unwrap_or_else(|e| eject(&e));
+            let signer = find_signer(&response, &keys).unwrap_or_else(|| eject("Verification failed!"));
+            println!("Verified! {} proven by {}", challenge.context, keys::fingerprint(signer));
+        },
+        Commands::Respond { input, output, private_key, context } => {
+            let secret = get_private_key(private_key);
+            let challenge = challenge::Challenge::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e));
+            if let Some(expected) = &context {
+                if challenge.context != *expected {
+                    eject(&format!("Challenge is for {:?}, not {:?}!", challenge.context, expected));
+                }
+            }
+            challenge.check_expiry().unwrap_or_else(|e| eject(&e));
+
+            eprintln!("Responding to a challenge for {}", challenge.context);
+            let mut response = challenge.response();
+            sign_signit(&secret, &mut response);
+            write_or_print(output, envelope::canonical(&response).unwrap());
+        },
+        Commands::Dsse(DsseCommands::Sign { input, output, private_key, payload_type, append, keyid, pretty }) => {
+            let secret = get_private_key(private_key);
+            let mut env = if append {
+                dsse::Envelope::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e))
+            } else {
+                dsse::Envelope::new(&payload_type.unwrap(), &get_input(&input))
+            };
+
+            let signed = env.signed_bytes().unwrap_or_else(|e| eject(&e));
+            env.signatures.push(dsse::Signature {
+                keyid: keyid.unwrap_or_else(|| keys::fingerprint(&secret.clone_public_key())),
+                sig: signature_over(&secret, &signed),
+            });
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                serde_json::to_string
+            }(&env).unwrap();
+            write_or_print(output, outstr);
+        },
+        Commands::Dsse(DsseCommands::Verify { input, output, public_key, payload_type }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+
+            let env = dsse::Envelope::parse(&get_message(None, &input)).unwrap_or_else(|e| eject(&e));
+            if let Some(expected) = &payload_type {
+                if env.payload_type != *expected {
+                    eject(&format!("Payload is of type {}, not {}!", env.payload_type, expected));
+                }
+            }
+            let signed = env.signed_bytes().unwrap_or_else(|e| eject(&e));
+
+            // Signatures by other keys are expected in multi-party envelopes, so they are skipped
+            let signers: Vec<String> = env
+                .signatures
+                .iter()
+                .filter_map(|sig| decode(&sig.sig).ok())
+                .filter_map(|sig| keys::find_key(&signed, &sig, &keys).map(keys::fingerprint))
+                .collect();
+            if signers.is_empty() {
+                eject("Verification failed!");
+            }
+            for signer in signers {
+                println!("Verified! Signed by {}", signer);
+            }
+            if let Some(output) = output {
+                write_file(&output, &env.payload().unwrap());
+            }
+        },
+        Commands::Oci(OciCommands::Sign { reference, private_key }) => {
+            let secret = get_private_key(private_key);
+            let reference = oci::Reference::parse(&reference).unwrap_or_else(|e| eject(&e));
+            let mut registry = oci::Registry::new(&reference);
+            let subject = registry.resolve(&reference).unwrap_or_else(|e| eject(&e));
+
+            let mut out = SignIt {
+                message: subject.digest.clone(),
+                signer: Signer {
+                    namespace: Some(oci::NAMESPACE.to_string()),
+                    timestamp: Some(envelope::timestamp()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            sign_signit(&secret, &mut out);
+            let envelope = envelope::canonical(&out).unwrap().into_bytes();
+            let attached = registry.attach(&subject, &envelope).unwrap_or_else(|e| eject(&e));
+            println!("Signed {}/{}@{}, signature {}", reference.registry, reference.repository, subject.digest, attached);
+        },
+        Commands::Oci(OciCommands::Verify { reference, public_key }) => {
+            let public_key = if public_key.is_empty() { vec![default_key("id_ed25519.pub")] } else { public_key };
+            let keys: Vec<_> = public_key.into_iter().flat_map(|path| get_public_keys(Some(path), &None)).collect();
+
+            let reference = oci::Reference::parse(&reference).unwrap_or_else(|e| eject(&e));
+            let mut registry = oci::Registry::new(&reference);
+            let subject = registry.resolve(&reference).unwrap_or_else(|e| eject(&e));
+            let signatures = registry.signatures(&subject).unwrap_or_else(|e| eject(&e));
+
+            // Envelopes that do not parse or sign something else are skipped, any one good signature will do
+            let signers: Vec<String> = signatures
+                .iter()
+                .filter_map(|raw| serde_json::from_slice::<SignIt>(raw).ok())
+                .filter(|env| env.message == subject.digest && env.signer.namespace.as_deref() == Some(oci::NAMESPACE))
+                .filter_map(|env| find_signer(&env, &keys).map(keys::fingerprint))
+                .collect();
+            if signers.is_empty() {
+                eject(&format!("Verification failed! None of the {} signatures of {} were made by a given key", signatures.len(), subject.digest));
+            }
+            for signer in signers {
+                println!("Verified! {}/{}@{} signed by {}", reference.registry, reference.repository, subject.digest, signer);
+            }
+        },
+        Commands::Bundle(BundleCommands::Merge { inputs, output, pretty }) => {
+            let mut bundle: Option<Bundle> = None;
+
+            for path in &inputs {
+                for env in get_envelopes(None, &Some(path.clone())) {
+                    let bundle = bundle.get_or_insert_with(|| Bundle {
+                        message: env.message.clone(),
+                        ..Default::default()
+                    });
+
+                    if !env.chain.is_empty() {
+                        eject(&format!("{:?} has a signature chain, which bundles cannot hold!", path));
+                    }
+                    if bundle.message != env.message {
+                        eject(&format!("{:?} signs a different message than the other inputs!", path));
+                    }
+                    if !bundle.signatures.contains(&env.signer) {
+                        bundle.signatures.push(env.signer);
+                    }
+                    // Hints are unsigned, so the first input to give one wins
+                    for (key, value) in env.hints {
+                        bundle.hints.entry(key).or_insert(value);
+                    }
+                }
+            }
+
+            let outstr = if pretty {
+                serde_json::to_string_pretty
+            } else {
+                envelope::canonical
+            }(&bundle.unwrap()).unwrap();
+
+            write_or_print(output, outstr);
+        },
+        Commands::Open { input, output, message, private_key, sender, github, namespace } => {
+            if sender.is_none() && !github {
+                eject("Specify the sender's public key with -s, or use -g to pull it from github!");
+            }
+
+            let raw = get_message(message, &input);
+            let sealed: seal::Sealed = serde_json::from_str(&raw)
+                .unwrap_or_else(|e| {
+                    eject(&format!("Failed to parse sealed message: {:?}\nError: {:?}", raw, e))
+                });
+
+            let secret = get_private_key(private_key);
+            let plaintext = seal::open(&sealed, &secret)
+                .unwrap_or_else(|e| eject(&e));
+            let msg: SignIt = serde_json::from_slice(&plaintext)
+                .unwrap_or_else(|e| {
+                    eject(&format!("Failed to parse decrypted message\nError: {:?}", e))
+                });
+
+            if let Some(expected) = &namespace {
+                if msg.signer.namespace.as_ref() != Some(expected) {
+                    eject(&format!("Signature was not made for namespace {:?}!", expected));
+                }
+            }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SignIt {
-    message: String,
-    signature: String,
+            let guser = match (sender.is_some(), github, &msg.signer.github_user) {
+                (false, true, Some(_)) => &msg.signer.github_user,
+                (false, true, None) => eject("No github user in message!"),
+                _ => &None,
+            };
+            let keys = get_public_keys(sender, guser);
+            if find_signer(&msg, &keys).is_none() {
+                eject("Verification failed!");
+            }
+            seal::check_recipient(&msg, &secret).unwrap_or_else(|e| eject(&e));
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    github_user: Option<String>,
+            // Nothing is written until the signature has checked out
+            write_or_print(output, msg.message);
+        }
+    }
 }
 
-#[derive(StructOpt)]
-enum Commands {
-    /// Sign a message using an ed25519 private key
-    #[structopt(name = "sign")]
-    Sign {
-        /// File to sign, defaults to stdin if no file is specified or -m is not used
-        #[structopt(short = "i", parse(from_os_str))]
-        input: Option<PathBuf>,
+fn sign_signit(secret: &SecretKey, out: &mut SignIt) {
+    out.signer.signature = signature_over(secret, &out.signed_bytes());
+}
 
-        /// Output of signature, defaults to stdout if no file is specified
-        #[structopt(short = "o", parse(from_os_str))]
-        output: Option<PathBuf>,
+/// Publish a signature for `sign --publish`, before it is written out
+fn publish_signature(target: Option<&str>, outstr: &str) {
+    if target.is_some() {
+        let cid = objstore::publish(outstr.as_bytes()).unwrap_or_else(|e| eject(&e));
+        eprintln!("Published to ipfs://{}", cid);
+    }
+}
 
-        /// Message to sign (overrides -i flag or stdin)
-        #[structopt(short = "m")]
-        message: Option<String>,
+fn signature_over(secret: &SecretKey, bytes: &[u8]) -> String {
+    encode(&signature_bytes(secret, bytes)[..])
+}
 
-        /// Path to ed25519 private key, defaults to "$HOME/.ssh/id_ed25519"
-        #[structopt(short = "k", parse(from_os_str))]
-        private_key: Option<PathBuf>,
+/// Re-encode a signed envelope for `signit-core`, checking that it still verifies there
+fn to_postcard(env: &SignIt, public: &PublicKey) -> Vec<u8> {
+    let mut signature = [0u8; 64];
+    signature.copy_from_slice(&decode(&env.signer.signature).unwrap());
+    let binary = signit_core::Envelope {
+        message: env.message.as_bytes(),
+        namespace: env.signer.namespace.as_deref(),
+        signature,
+    };
 
-        /// Github username to couple with json output
-        #[structopt(short = "g")]
-        github: Option<String>,
+    let public = keys::raw_ed25519(public)
+        .unwrap_or_else(|| eject("Specified or detected key was not an Ed25519 key!"));
+    binary.verify(&public)
+        .unwrap_or_else(|e| eject(&format!("Binary envelope does not verify\nError: {:?}", e)));
 
-        /// Pretty Print the JSON output
-        #[structopt(short = "p")]
-        pretty: bool,
-    },
+    let mut buf = vec![0u8; binary.encoded_len()];
+    binary.encode(&mut buf).unwrap();
+    buf
+}
 
-    /// Verify a message using an ed25519 public key
-    #[structopt(name = "verify")]
-    Verify {
-        /// File to sign, defaults to stdin if no file is specified or -m is not used
-        #[structopt(short = "i", parse(from_os_str))]
-        input: Option<PathBuf>,
+fn signature_bytes(secret: &SecretKey, bytes: &[u8]) -> [u8; 64] {
+    let sig = secret.sign_detached(bytes).unwrap();
+    match sig {
+        Signature::Ed25519(sig) => sig.0,
+        _ => eject("Specified or detected key was not an Ed25519 key!"),
+    }
+}
 
-        /// Message to verify (overrides -i flag or stdin)
-        #[structopt(short = "m")]
-        message: Option<String>,
+/// Whether `path` holds an envelope signing the same thing as `expected`, made by `public`
+fn is_current(path: &Path, expected: &SignIt, public: &PublicKey) -> bool {
+    let raw = match std::fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(_) => return false,
+    };
+    let envelopes = envelope::parse_envelopes(&raw).unwrap_or_default();
 
-        /// Path to ed25519 public key, defaults to "$HOME/.ssh/id_ed25519.pub", overrides -g
-        #[structopt(short = "k", parse(from_os_str))]
-        public_key: Option<PathBuf>,
+    envelopes.iter().any(|env| {
+        env.chain.is_empty()
+            && env.signs_same_as(expected)
+            && env.signer.github_user == expected.signer.github_user
+            && env.signer.certificate == expected.signer.certificate
+            && env.signer.delegation == expected.signer.delegation
+            && env.hints == expected.hints
+            && decode(&env.signer.signature).map_or(false, |sig| public.verify_detached(&env.signed_bytes(), &sig))
+    })
+}
 
-        /// Pull public keys from github
-        #[structopt(short = "g")]
-        github: bool,
+fn find_signer<'a>(msg: &SignIt, keys: &'a [PublicKey]) -> Option<&'a PublicKey> {
+    let sig = decode(&msg.signer.signature)
+        .unwrap_or_else(|_e| eject("Signature not proper base64!") );
+
+    keys::find_key(&msg.signed_bytes(), &sig, keys)
+}
+
+/// The data a signature covers, its github user, timestamp and metadata, and whether it checked out
+type Check<'a> = (&'a [u8], Option<String>, Option<String>, BTreeMap<String, String>, Result<String, (&'static str, String)>);
+
+enum DetachedSignature {
+    /// A signature file written by `signit watch`
+    SignIt(Box<Detached>),
+
+    /// A bare ed25519 signature over the data itself, as binary or base64
+    Raw(Vec<u8>),
+}
+
+fn get_detached_signature(path: &Path, max_size: u64, max_depth: usize) -> DetachedSignature {
+    let too_large = |e: std::io::Error| eject(&format!("Failed to read signature {:?}\nError: {}", path, e));
+    let contents = if objstore::is_object(path) {
+        Zeroizing::new(limits::read(objstore::open(path).unwrap_or_else(|e| eject(&e)), max_size).unwrap_or_else(too_large))
+    } else if is_stdio(path) {
+        Zeroizing::new(limits::read(take_stdin(), max_size).unwrap_or_else(too_large))
+    } else {
+        // Files are checked before reading them, and other key sources after
+        if std::fs::metadata(path).map_or(false, |meta| meta.len() > max_size) {
+            too_large(limits::too_large(max_size));
+        }
+        let contents = read_key_file(path)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to read signature {:?}\nError: {:?}", path, e));
+            });
+        if contents.len() as u64 > max_size {
+            too_large(limits::too_large(max_size));
+        }
+        contents
+    };
+    if matches!(contents.iter().find(|b| !b.is_ascii_whitespace()), Some(b'{') | Some(b'[')) {
+        limits::check_depth(&contents, max_depth)
+            .unwrap_or_else(|e| eject(&format!("Refusing to parse signature {:?}, {}", path, e)));
+    }
+
+    if let Ok(detached) = serde_json::from_slice(&contents) {
+        return DetachedSignature::SignIt(Box::new(detached));
+    }
+    if contents.len() == 64 {
+        return DetachedSignature::Raw(contents.to_vec());
+    }
+    // Base64 of 64 bytes always ends in padding, which no multibase string has
+    let text = std::str::from_utf8(&contents).unwrap_or("").trim();
+    match decode(text).ok().or_else(|| keys::multibase_decode(text)) {
+        Some(sig) if sig.len() == 64 => DetachedSignature::Raw(sig),
+        _ => eject(&format!("{:?} is neither a signit signature nor a raw 64-byte ed25519 signature!", path)),
     }
 }
 
-fn main() {
-    let opt = Commands::from_args();
+/// Why a signature without chunks cannot verify `verify --range`
+const UNCHUNKED_RANGE: &str = "Only signatures made with `sign --chunk-size` can verify a range!";
 
-    match opt {
-        Commands::Sign { input, output, message, private_key, github, pretty } => {
+/// The data a detached signature is checked against
+enum Covered<'a> {
+    /// All of the signed data
+    All(&'a [u8]),
 
-            let secret = get_private_key(private_key);
-            let message = get_message(message, &input);
+    /// Part of data signed with `sign --chunk-size`, for `verify --range`
+    Range(&'a merkle::Range, &'a [u8]),
 
-            let sig = secret.sign_detached(message.as_bytes()).unwrap();
-            let sig = match sig {
-                Signature::Ed25519(sig) => sig,
-                _ => eject("Specified or detected key was not an Ed25519 key!"),
-            };
+    /// The digest of all of the signed data, hashed as it streamed past for `verify --stream`
+    Digest(&'a str),
+}
 
+/// The digest of `-i` in the form `sig` signed it, hashed as it streams past
+fn stream_digest(sig: &DetachedSignature, input: &Option<PathBuf>) -> String {
+    let chunks = match sig {
+        DetachedSignature::SignIt(detached) => detached.chunks.as_ref(),
+        DetachedSignature::Raw(_) => eject("--stream needs a signature from sign --detached, raw signatures cover the data itself!"),
+    };
+    let local = input.as_ref().filter(|path| !is_stdio(path) && !objstore::is_object(path));
+    let total = local.and_then(|path| std::fs::metadata(path).ok()).map(|metadata| metadata.len());
+    let reader = progress::Progress::new(open_input(input), "Verifying", total);
 
-            let out = SignIt {
-                message,
-                signature: encode(&sig.0[..]),
-                github_user: github,
+    let digest = match chunks {
+        Some(chunks) => merkle::chunks(reader, chunks.size).map(|chunks| merkle::digest(&chunks)),
+        None => envelope::digest(reader).map(Ok),
+    };
+    digest
+        .unwrap_or_else(|e| eject(&format!("Failed to read input\nError: {:?}", e)))
+        .unwrap_or_else(|e| eject(&e))
+}
+
+/// Check a detached signature over `data`, like `check_envelope` does for envelopes
+fn check_detached(
+    sig: &DetachedSignature,
+    data: Covered,
+    fixed_keys: &Option<Vec<PublicKey>>,
+    github: bool,
+    namespace: &Option<String>,
+    expect_nonce: &Option<String>,
+    usage_policy: Option<&UsagePolicy>,
+) -> Result<String, (&'static str, String)> {
+    let fetched;
+    let (signed, sig_bytes, keys, signed_namespace): (_, _, &[PublicKey], _) = match sig {
+        DetachedSignature::SignIt(detached) => {
+            if let Some(expected) = namespace {
+                if detached.signer.namespace.as_ref() != Some(expected) {
+                    return Err(("failed", format!("Signature was not made for namespace {:?}!", expected)));
+                }
+            }
+            if let Some(expected) = expect_nonce {
+                if detached.signer.nonce.as_ref() != Some(expected) {
+                    return Err(("failed", format!("Signature does not include the expected nonce {:?}!", expected)));
+                }
+            }
+            match (&detached.chunks, data) {
+                (Some(chunks), Covered::All(data)) => merkle::check(chunks, &detached.digest)
+                    .and_then(|_| merkle::check_all(chunks, data))
+                    .map_err(|e| ("failed", e))?,
+                (Some(chunks), Covered::Range(range, data)) => merkle::check(chunks, &detached.digest)
+                    .and_then(|_| merkle::check_range(chunks, range, data))
+                    .map_err(|e| ("failed", e))?,
+                (_, Covered::Digest(digest)) if digest != detached.digest => {
+                    return Err(("failed", "Signature was made for different data!".into()));
+                }
+                (_, Covered::Digest(_)) => (),
+                (None, Covered::Range(..)) => return Err(("failed", UNCHUNKED_RANGE.into())),
+                (None, Covered::All(data)) if envelope::digest(data).unwrap() != detached.digest => {
+                    return Err(("failed", "Signature was made for different data!".into()));
+                }
+                (None, Covered::All(_)) => (),
+            }
+
+            let guser = match (github, &detached.signer.github_user) {
+                (true, Some(_)) => &detached.signer.github_user,
+                (true, None) => return Err(("failed", "No github user in signature!".into())),
+                (false, _) => &None,
+            };
+            let sig_bytes = decode(&detached.signer.signature)
+                .map_err(|_| ("failed", "Signature not proper base64!".to_string()))?;
+            let keys = match fixed_keys {
+                Some(keys) => keys,
+                None => {
+                    fetched = get_public_keys(None, guser);
+                    &fetched
+                }
+            };
+            (detached.signed_bytes(), sig_bytes, keys, detached.signer.namespace.as_deref())
+        }
+        DetachedSignature::Raw(sig_bytes) => {
+            // There is no signer information to check, nor a github user to look up
+            let keys = match fixed_keys {
+                Some(keys) => keys,
+                None => return Err(("failed", "Raw signatures need the signer's public key given with -k!".into())),
             };
+            if namespace.is_some() || expect_nonce.is_some() {
+                return Err(("failed", "Raw signatures carry no namespace or nonce to check!".into()));
+            }
+            let data = match data {
+                Covered::All(data) => data,
+                Covered::Range(..) => return Err(("failed", UNCHUNKED_RANGE.into())),
+                Covered::Digest(_) => return Err(("failed", "Raw signatures cannot be checked against a digest!".into())),
+            };
+            if data.starts_with(envelope::PAYLOAD_PREFIX) {
+                return Err(("failed", "Raw signatures cannot cover data that starts like a signit payload!".into()));
+            }
+            (data.to_vec(), sig_bytes.clone(), keys, None)
+        }
+    };
 
-            let outstr = if pretty {
-                serde_json::to_string_pretty
-            } else {
-                serde_json::to_string
-            }(&out).unwrap();
+    let good = keys::find_key(&signed, &sig_bytes, keys)
+        .ok_or(("failed", "Verification failed!".to_string()))?;
+    let fingerprint = keys::fingerprint(good);
 
-            write_or_print(output, outstr);
+    if let Some(policy) = usage_policy {
+        policy
+            .check(&fingerprint, signed_namespace)
+            .map_err(|e| ("policy-violation", format!("Usage policy violation!\n{}", e)))?;
+    }
 
-        },
-        Commands::Verify { input, message, public_key, github } => {
-            let msg = get_sig_message(message, &input);
-            let guser = match (github, &msg.github_user) {
-                (true, Some(_)) => &msg.github_user,
-                (true, None) => eject("No github user in message!"),
+    Ok(fingerprint)
+}
+
+/// Check one envelope against the key sources given to `verify`
+///
+/// Returns the fingerprint of the signing key, or the audit log result and a
+/// human readable reason on failure.
+fn check_envelope(
+    msg: &SignIt,
+    fixed_keys: &Option<Vec<PublicKey>>,
+    github: bool,
+    trust_embedded: bool,
+    namespace: &Option<String>,
+    usage_policy: Option<&UsagePolicy>,
+) -> Result<String, (&'static str, String)> {
+    if let Some(expected) = namespace {
+        if msg.signer.namespace.as_ref() != Some(expected) {
+            return Err(("failed", format!("Signature was not made for namespace {:?}!", expected)));
+        }
+    }
+
+    let embedded = match &msg.signer.key {
+        Some(embedded) => {
+            let key = keys::parse_openssh_line(&embedded.public_key)
+                .ok_or(("failed", "Embedded public key is invalid!".to_string()))?;
+            if keys::fingerprint(&key) != embedded.fingerprint {
+                return Err(("failed", "Embedded fingerprint does not match the embedded key!".into()));
+            }
+            Some(key)
+        }
+        None if trust_embedded => return Err(("failed", "No public key embedded in message!".into())),
+        None => None,
+    };
+
+    let owned;
+    let keys: &[PublicKey] = match embedded {
+        Some(key) if trust_embedded => {
+            owned = vec![key];
+            &owned
+        }
+        _ => {
+            let guser = match (github, &msg.signer.github_user) {
+                (true, Some(_)) => &msg.signer.github_user,
+                (true, None) => return Err(("failed", "No github user in message!".into())),
                 (false, _) => &None,
             };
-            let keys = get_public_keys(public_key, guser);
+            match fixed_keys {
+                Some(keys) => keys,
+                None => {
+                    owned = get_public_keys(None, guser);
+                    &owned
+                }
+            }
+        }
+    };
+
+    let good = find_signer(msg, keys)
+        .ok_or(("failed", "Verification failed!".to_string()))?;
+    let fingerprint = keys::fingerprint(good);
+
+    // Strict verifiers still cross-check the embedded key against their own
+    if let Some(embedded) = &msg.signer.key {
+        if embedded.fingerprint != fingerprint {
+            return Err(("failed", format!(
+                "Embedded key {} is not the key that verified the signature ({})!",
+                embedded.fingerprint, fingerprint,
+            )));
+        }
+    }
+
+    if let Some(policy) = usage_policy {
+        policy
+            .check(&fingerprint, msg.signer.namespace.as_deref())
+            .map_err(|e| ("policy-violation", format!("Usage policy violation!\n{}", e)))?;
+    }
+
+    Ok(fingerprint)
+}
+
+/// The keys a signature must be made with, when not `fixed_keys`
+///
+/// A certified key replaces the fixed keys with `--trusted-ca`, and a
+/// delegated key replaces the keys that delegated to it.
+fn signing_keys(
+    signer: Option<&Signer>,
+    fixed_keys: &Option<Vec<PublicKey>>,
+    cas: &[PublicKey],
+    principal: Option<&str>,
+    skew: time::Duration,
+) -> Result<Option<Vec<PublicKey>>, (&'static str, String)> {
+    let certified = certified_key(signer, cas, principal, skew)?;
+    let delegation = match signer.and_then(|signer| signer.delegation.as_deref()) {
+        Some(delegation) => delegation,
+        None => return Ok(certified),
+    };
+    let trusted = certified
+        .as_ref()
+        .or(fixed_keys.as_ref())
+        .ok_or(("failed", "Signature is delegated, give the delegating key with -k!".to_string()))?;
+    let allowed = delegate::Delegation::verify(delegation, trusted, skew).map_err(|e| ("failed", e))?;
+    if signer.and_then(|signer| signer.namespace.as_ref()) != Some(&allowed.namespace) {
+        return Err(("failed", format!("Signature is delegated only for namespace {:?}!", allowed.namespace)));
+    }
+    Ok(Some(vec![allowed.key]))
+}
+
+/// The key of a signature's certificate, once it checks out against `verify --trusted-ca`
+fn certified_key(
+    signer: Option<&Signer>,
+    cas: &[PublicKey],
+    principal: Option<&str>,
+    skew: time::Duration,
+) -> Result<Option<Vec<PublicKey>>, (&'static str, String)> {
+    if cas.is_empty() {
+        return Ok(None);
+    }
+    let line = signer
+        .and_then(|signer| signer.certificate.as_deref())
+        .ok_or(("failed", "Signature carries no certificate, it must be made with sign --certificate!".to_string()))?;
+    let certificate = cert::Certificate::parse(line).map_err(|e| ("failed", e))?;
+    certificate
+        .check(cas, cert::USER, principal, cert::now(), skew.num_seconds().max(0) as u64)
+        .map_err(|e| ("failed", e))?;
+    Ok(Some(vec![certificate.key]))
+}
 
-            let sig = decode(&msg.signature)
-                .unwrap_or_else(|_e| eject("Signature not proper base64!") );
+fn check_nonce(msg: &SignIt, expected: &Option<String>) -> Result<(), (&'static str, String)> {
+    match expected {
+        Some(expected) if msg.signer.nonce.as_ref() != Some(expected) => {
+            Err(("failed", format!("Signature does not include the expected nonce {:?}!", expected)))
+        }
+        _ => Ok(()),
+    }
+}
 
-            let good = keys
+/// Check the identity a signature claims against the source it names
+///
+/// Github identities must be made with one of the user's github keys, and
+/// email identities with a key whose comment in a local key file is the
+/// address, so a signature cannot simply claim to be anyone.
+fn check_identity(
+    identity: Option<&str>,
+    github_user: Option<&str>,
+    fingerprint: &str,
+    key_comments: &[(String, String)],
+) -> Result<(), (&'static str, String)> {
+    let claimed = identity.ok_or(("failed", "Signature claims no identity, sign with --identity!".to_string()))?;
+    match envelope::parse_identity(claimed).map_err(|e| ("failed", e))? {
+        ("github", user) => {
+            if let Some(other) = github_user.filter(|other| *other != user) {
+                return Err(("failed", format!("Signature claims identity {}, but github user {}!", claimed, other)));
+            }
+            let keys = forge::github_keys(user)
+                .map_err(|e| ("failed", format!("Failed to get github keys for {}!\nError: {}", user, e)))?;
+            if !keys.iter().any(|key| keys::fingerprint(key) == fingerprint) {
+                return Err(("failed", format!("Key {} is not one of the github keys of {}!", fingerprint, user)));
+            }
+        }
+        (_, address) => {
+            let listed = key_comments
                 .iter()
-                .any(|k| {
-                    k.verify_detached(msg.message.as_bytes(), &sig)
-                });
+                .any(|(known, comment)| known == fingerprint && comment.split_whitespace().any(|word| word == address));
+            if !listed {
+                return Err(("failed", format!("Key {} is not listed for {} in the -k or --authorized-keys files!", fingerprint, address)));
+            }
+        }
+    }
+    Ok(())
+}
 
-            if !good {
-                eject("Verification failed!")
-            } else {
-                println!("Verified!");
+/// Fail if the signature claims a github user other than the one given with `--github`
+fn check_claimed_user(claimed: Option<&str>, expected: &Option<String>) -> Result<(), (&'static str, String)> {
+    match (claimed, expected) {
+        (Some(claimed), Some(expected)) if claimed != expected => {
+            Err(("failed", format!("Signature claims github user {}, not {}!", claimed, expected)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verify an envelope's signature chain, describing each signer in order
+///
+/// With `-g`, the key of every link that claims a github user must be one of
+/// that user's keys.
+fn check_chain(msg: &SignIt, fingerprint: &str, github: bool) -> Result<Vec<String>, (&'static str, String)> {
+    let describe = |fingerprint: &str, user: &Option<String>| match user {
+        Some(user) => format!("{} (github: {})", fingerprint, user),
+        None => fingerprint.to_string(),
+    };
+
+    let notaries = chain::verify(msg).map_err(|e| ("failed", e))?;
+
+    let mut signers = vec![describe(fingerprint, &msg.signer.github_user)];
+    for notary in notaries {
+        if let (true, Some(_)) = (github, &notary.github_user) {
+            let owned = get_public_keys(None, &notary.github_user)
+                .iter()
+                .any(|k| keys::fingerprint(k) == notary.fingerprint);
+            if !owned {
+                return Err((
+                    "failed",
+                    format!("Chain key {} does not belong to the claimed github user!", notary.fingerprint),
+                ));
             }
         }
+        signers.push(describe(&notary.fingerprint, &notary.github_user));
+    }
+
+    Ok(signers)
+}
+
+/// Verify the envelopes nested inside a countersignature, all the way down
+fn check_countersigned(
+    msg: &SignIt,
+    inner_key: &Option<PathBuf>,
+    github: bool,
+    usage_policy: Option<&UsagePolicy>,
+) -> Result<(), (&'static str, String)> {
+    if !msg.signer.countersign {
+        return Ok(());
+    }
+    if inner_key.is_none() && !github {
+        return Err((
+            "failed",
+            "Envelope is a countersignature, use --inner-key or -g to verify what it countersigns!".into(),
+        ));
+    }
+
+    let inner = envelope::parse_envelopes(&msg.message)
+        .map_err(|e| ("failed", format!("Failed to parse countersigned envelope\nError: {:?}", e)))?;
+
+    let inner_keys = inner_key.as_ref().map(|path| get_public_keys(Some(path.clone()), &None));
+    let mut failures = vec![];
+    for env in &inner {
+        let result = check_envelope(env, &inner_keys, github && inner_key.is_none(), false, &None, usage_policy)
+            .and_then(|_| check_countersigned(env, inner_key, github, usage_policy));
+        if let Err((_, reason)) = result {
+            failures.push(reason);
+        }
+    }
+
+    if failures.len() == inner.len() {
+        Err(("failed", format!("Countersigned envelope failed verification!\n{}", failures.join("\n"))))
+    } else {
+        Ok(())
     }
 }
 
+/// Whether a path option is `-`, standing for stdin or stdout
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
 fn write_or_print(output: Option<PathBuf>, outstr: String) {
+    match output {
+        Some(opath) if !is_stdio(&opath) => write_file(&opath, outstr.as_bytes()),
+        _ => println!("{}", outstr),
+    }
+}
+
+fn write_file(opath: &Path, contents: &[u8]) {
     use std::io::Write;
-    if let Some(opath) = output {
-        let mut file = std::fs::File::create(&opath)
-            .unwrap_or_else(|e| {
-                eject(&format!("Failed to open file: {:?}\nError: {:?}", opath, e));
-            });
-        file.write_all(outstr.as_bytes())
+    if is_stdio(opath) {
+        return std::io::stdout().write_all(contents)
             .unwrap_or_else(|e| {
-                eject(&format!("Failed to write to file: {:?}\nError: {:?}", opath, e));
+                eject(&format!("Failed to write to stdout\nError: {:?}", e));
             });
-    } else {
-        println!("{}", outstr);
     }
+    if objstore::is_object(opath) {
+        return objstore::put(opath, contents).unwrap_or_else(|e| eject(&e));
+    }
+
+    let mut file = std::fs::File::create(opath)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to open file: {:?}\nError: {:?}", opath, e));
+        });
+    file.write_all(contents)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to write to file: {:?}\nError: {:?}", opath, e));
+        });
+}
+
+/// Like `write_or_print`, but a created file is only readable by its owner
+fn write_private(output: Option<PathBuf>, outstr: &str) {
+    use std::io::Write;
+    let opath = match output {
+        Some(opath) if !is_stdio(&opath) => opath,
+        _ => return println!("{}", outstr),
+    };
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&opath)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to create file: {:?}\nError: {:?}", opath, e));
+        });
+    writeln!(file, "{}", outstr)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to write to file: {:?}\nError: {:?}", opath, e));
+        });
 }
 
-fn get_sig_message(message: Option<String>, input: &Option<PathBuf>) -> SignIt {
+fn get_envelopes(message: Option<String>, input: &Option<PathBuf>) -> Vec<SignIt> {
     let raw = get_message(message, input);
-    serde_json::from_str(&raw)
+    envelope::parse_envelopes(&raw)
         .unwrap_or_else(|e| {
             eject(&format!("Failed to parse message: {:?}\nError: {:?}", raw, e))
         })
@@ -161,75 +3761,285 @@ fn get_message(message: Option<String>, input: &Option<PathBuf>) -> String {
         return msg;
     }
 
-    if let Some(fpath) = input {
-        return read_to_string(&fpath)
+    String::from_utf8(get_input(input))
+        .unwrap_or_else(|_| eject("Input is not UTF-8 text, which is all an envelope can hold!"))
+}
+
+/// Read the -i file as bytes, or stdin if it is not given or is `-`
+fn get_input(input: &Option<PathBuf>) -> Vec<u8> {
+    match input {
+        Some(fpath) if objstore::is_object(fpath) => objstore::get(fpath).unwrap_or_else(|e| eject(&e)),
+        Some(fpath) if !is_stdio(fpath) => read(fpath)
             .unwrap_or_else(|e| {
                 eject(&format!("Failed to read file {:?}\nError: {:?}", fpath, e));
-            });
+            }),
+        _ => read_stdin(),
+    }
+}
+
+/// Read the -i file like `get_input`, failing rather than reading more than `limit` bytes
+fn get_input_limited(input: &Option<PathBuf>, limit: Option<u64>) -> Vec<u8> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return get_input(input),
+    };
+    let name = input.as_ref().filter(|fpath| !is_stdio(fpath)).map_or("stdin".to_string(), |fpath| format!("{:?}", fpath));
+    limits::read(open_input(input), limit)
+        .unwrap_or_else(|e| eject(&format!("Failed to read {}\nError: {}", name, e)))
+}
+
+/// Map the -i file into memory, which stdin cannot be
+fn get_mapped(input: &Option<PathBuf>) -> mmap::Mapped {
+    match input {
+        Some(fpath) if objstore::is_object(fpath) => eject("--mmap needs a local file, not an object URL!"),
+        Some(fpath) if !is_stdio(fpath) => mmap::map(fpath)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to map file {:?}\nError: {:?}", fpath, e));
+            }),
+        _ => eject("--mmap needs a file given with -i, not stdin!"),
+    }
+}
+
+/// Open the -i file, or stdin if it is not given or is `-`, to be read as a stream
+fn open_input(input: &Option<PathBuf>) -> Box<dyn std::io::Read> {
+    match input {
+        Some(fpath) if objstore::is_object(fpath) => objstore::open(fpath).unwrap_or_else(|e| eject(&e)),
+        Some(fpath) if !is_stdio(fpath) => Box::new(std::fs::File::open(fpath)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to read file {:?}\nError: {:?}", fpath, e));
+            })),
+        _ => Box::new(take_stdin()),
     }
+}
 
+/// Read all of stdin, which only one of the inputs can do
+fn read_stdin() -> Vec<u8> {
     use std::io::Read;
-    let mut buffer = String::new();
-    std::io::stdin().read_to_string(&mut buffer)
+
+    let mut buffer = vec![];
+    take_stdin().read_to_end(&mut buffer)
         .unwrap_or_else(|e| {
             eject(&format!("Failed to read stdin\nError: {:?}", e))
         });
     buffer
 }
 
-fn get_private_key(path: Option<PathBuf>) -> KeyPair {
-    let path = path
-        .unwrap_or_else(|| {
-            let mut private_key_file = home_dir()
-                .unwrap_or_else(|| {
-                    eject("No home directory detected, please specify private key using -k!");
-                });
-            private_key_file.push(".ssh");
-            private_key_file.push("id_ed25519");
+/// Stdin, for the one input that may read it
+fn take_stdin() -> std::io::Stdin {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    static READ: AtomicBool = AtomicBool::new(false);
+
+    if READ.swap(true, Ordering::SeqCst) {
+        eject("Only one input can be read from stdin!");
+    }
+    std::io::stdin()
+}
+
+/// Read a key file, or stdin for `-`
+fn read_key_file(path: &Path) -> std::io::Result<Zeroizing<Vec<u8>>> {
+    if is_stdio(path) {
+        Ok(Zeroizing::new(read_stdin()))
+    } else {
+        keysource::read(path)
+    }
+}
+
+fn private_key_path(path: Option<PathBuf>) -> PathBuf {
+    path.unwrap_or_else(|| default_key("id_ed25519"))
+}
+
+/// Longest path Windows accepts without the `\\?\` prefix, counting the terminating NUL
+const MAX_PATH: usize = 260;
+
+/// `canonicalize`, without the `\\?\` prefix it adds on Windows, which shells cannot use
+///
+/// The prefix is kept on paths too long to open without it.
+fn canonical_path(path: &Path) -> std::io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let verbatim = path.to_str().and_then(|p| p.strip_prefix(r"\\?\"));
+    match verbatim {
+        Some(rest) if cfg!(windows) && rest.as_bytes().get(1) == Some(&b':') && rest.len() < MAX_PATH => {
+            Ok(PathBuf::from(rest))
+        }
+        _ => Ok(path),
+    }
+}
 
-            private_key_file
+/// Path of a key file in the user's `.ssh` directory
+///
+/// On Windows this is `%USERPROFILE%\.ssh`, where the OpenSSH that ships with
+/// Windows keeps its keys, even when a shell such as Git Bash sets `HOME`.
+fn default_key(file: &str) -> PathBuf {
+    let profile = std::env::var_os("USERPROFILE")
+        .filter(|_| cfg!(windows))
+        .map(PathBuf::from);
+    let mut path = profile.or_else(home_dir)
+        .unwrap_or_else(|| {
+            eject("No home directory detected, please specify key using -k!");
         });
+    path.push(".ssh");
+    path.push(file);
+    path
+}
 
-    load_secret_key(&path, None)
+fn get_private_key(path: Option<PathBuf>) -> SecretKey {
+    let path = private_key_path(path);
+    let contents = read_key_file(&path)
         .unwrap_or_else(|e| {
             eject(&format!("Unable to detect private key, please specify using -k!\nError: {:?}", e));
-        })
+        });
+    decode_private_key(&path, &contents)
+}
+
+fn decode_private_key(path: &Path, contents: &[u8]) -> SecretKey {
+    let decrypted = atrest::decrypt(contents).unwrap_or_else(|e| eject(&e));
+    let contents = decrypted.as_ref().map_or(contents, |decrypted| &decrypted[..]);
+
+    match SecretKey::decode(contents, None) {
+        Err(thrussh_keys::Error::KeyIsEncrypted) => {
+            let passphrase = secret::prompt_passphrase(&format!("Enter passphrase for {}: ", path.display()))
+                .unwrap_or_else(|e| eject(&format!("Failed to read passphrase\nError: {:?}", e)));
+            SecretKey::decode(contents, Some(passphrase.as_bytes()))
+                .unwrap_or_else(|e| {
+                    eject(&format!("Unable to decrypt private key, wrong passphrase?\nError: {:?}", e));
+                })
+        }
+        loaded => loaded
+            .unwrap_or_else(|e| {
+                eject(&format!("Unable to detect private key, please specify using -k!\nError: {:?}", e));
+            }),
+    }
+}
+
+/// A key `sign` can sign with
+enum SigningKey {
+    /// A private key file, and the path it was loaded from
+    File(SecretKey, PathBuf),
+
+    /// A key held by ssh-agent, which signs on our behalf
+    Agent(agent::Identity),
+}
+
+impl SigningKey {
+    fn public(&self) -> PublicKey {
+        match self {
+            SigningKey::File(secret, _) => secret.clone_public_key(),
+            SigningKey::Agent(identity) => keys::parse_openssh_line(&encode(&identity.blob))
+                .unwrap_or_else(|| eject(&format!("Agent key {} is not an ed25519 key!", identity.fingerprint()))),
+        }
+    }
+
+    /// The comment to embed with `--embed-key`
+    fn comment(&self) -> Option<String> {
+        match self {
+            SigningKey::File(_, path) => keys::comment_for(path),
+            SigningKey::Agent(identity) => Some(identity.comment.clone()).filter(|c| !c.is_empty()),
+        }
+    }
+
+    fn sign(&self, bytes: &[u8]) -> [u8; 64] {
+        match self {
+            SigningKey::File(secret, _) => signature_bytes(secret, bytes),
+            SigningKey::Agent(identity) => agent::sign(identity, bytes).unwrap_or_else(|e| eject(&e)),
+        }
+    }
+}
+
+enum AnyKey {
+    Public(PublicKey),
+    Private(SecretKey),
+}
+
+/// Load a key file that may hold either a public or a private key
+fn get_any_key(path: PathBuf) -> AnyKey {
+    let contents = read_key_file(&path)
+        .unwrap_or_else(|e| {
+            eject(&format!("Failed to load key at {:?}\nError: {:?}", path, e));
+        });
+
+    match std::str::from_utf8(&contents).ok().and_then(keys::parse_openssh_line) {
+        Some(public) => AnyKey::Public(public),
+        None => AnyKey::Private(decode_private_key(&path, &contents)),
+    }
 }
 
 fn get_public_keys(path: Option<PathBuf>, guser: &Option<String>) -> Vec<PublicKey> {
     let mut ed_keys = vec![];
 
     if let Some(pkpath) = path {
-        let key = load_public_key(&pkpath)
+        let contents = read_key_file(&pkpath)
             .unwrap_or_else(|e| {
                 eject(&format!("Failed to load key at {:?}\nError: {:?}", pkpath, e));
             });
-        ed_keys.push(key);
+        let keys = keys::parse_public_keys(&contents);
+        if keys.is_empty() {
+            eject(&format!("Failed to load key at {:?}\nNot an ed25519 public key", pkpath));
+        }
+        ed_keys.extend(keys);
     } else if let Some(user) = guser {
-        let url = format!("https://github.com/{}.keys", user);
-        let body = reqwest::get(&url)
+        let keys = forge::github_keys(user)
             .unwrap_or_else(|e| {
-                eject(&format!("Failed to get github keys!\nError: {:?}", e))
-            })
-            .text()
-            .unwrap_or_else(|e| {
-                eject(&format!("Failed to get github keys!\nError: {:?}", e))
+                eject(&format!("Failed to get github keys!\nError: {}", e))
             });
+        ed_keys.extend(keys);
+    }
+
+    ed_keys
+}
 
-        body.lines()
-            .filter(|l| {
-                l.starts_with("ssh-ed25519")
-            })
-            .filter_map(|l| l.split_whitespace().skip(1).next())
-            .filter_map(|l| {
-                parse_public_key_base64(l).ok()
-            })
-            .for_each(|pk| ed_keys.push(pk));
+/// Every revocation in the files or https:// URLs given to `verify --revocations`
+fn get_revocations(sources: &[String]) -> Vec<revoke::Revocation> {
+    let mut revocations = vec![];
+    for source in sources {
+        let raw = if source.starts_with("https://") {
+            keycache::fetch(source)
+                .unwrap_or_else(|e| eject(&format!("Failed to get revocations from {}!\nError: {}", source, e)))
+        } else {
+            get_message(None, &Some(PathBuf::from(source)))
+        };
+        revocations.extend(revoke::load(&raw).unwrap_or_else(|e| {
+            eject(&format!("Failed to load revocations from {}\nError: {}", source, e))
+        }));
     }
+    revocations
+}
 
+/// Every ed25519 key across `authorized_keys` files
+fn get_authorized_keys(paths: &[PathBuf]) -> Vec<PublicKey> {
+    let mut ed_keys = vec![];
+    for path in paths {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| eject(&format!("Failed to load authorized keys at {:?}\nError: {:?}", path, e)));
+        ed_keys.extend(keys::parse_authorized_keys(&contents));
+    }
+    if ed_keys.is_empty() {
+        eject(&format!("No ed25519 keys found in {:?}", paths));
+    }
     ed_keys
 }
 
+/// Every host key across `known_hosts` files, or those of `host`, noting the hosts of each in `key_files`
+fn get_known_host_keys(paths: &[PathBuf], host: Option<&str>, key_files: &mut Vec<(String, String)>) -> Vec<PublicKey> {
+    let mut ed_keys = vec![];
+    for path in paths {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| eject(&format!("Failed to load known hosts at {:?}\nError: {:?}", path, e)));
+        for entry in knownhosts::host_keys(knownhosts::parse(&contents)) {
+            if host.map_or(false, |host| !entry.matches(host)) {
+                continue;
+            }
+            let hosts = host.unwrap_or_else(|| entry.describe());
+            key_files.push((keys::fingerprint(&entry.key), format!("{} in {}", hosts, path.display())));
+            ed_keys.push(entry.key);
+        }
+    }
+    match host {
+        _ if !ed_keys.is_empty() => ed_keys,
+        Some(host) => eject(&format!("No ed25519 host key for {} found in {:?}", host, paths)),
+        None => eject(&format!("No ed25519 host keys found in {:?}", paths)),
+    }
+}
+
 pub fn eject(reason: &str) -> ! {
     eprintln!("{}", reason);
     std::process::exit(-1);
@@ -0,0 +1,320 @@
+//! Chunked digests of large files, for `sign --detached --chunk-size`
+//!
+//! The data is split into fixed-size chunks and each chunk is hashed. The
+//! signature's digest is then the Merkle root over those hashes, computed as
+//! in RFC 6962 with distinct prefixes for leaves and interior nodes. The chunk
+//! hashes are stored next to the signature, so any byte range can later be
+//! checked by hashing just the chunks it overlaps and checking the stored
+//! hashes against the signed root.
+
+use openssl::sha::{sha256, Sha256};
+use std::io::{self, Read};
+
+use crate::envelope::Chunks;
+
+/// Prefix of a detached signature's digest when it is a Merkle root
+pub const PREFIX: &str = "merkle-sha256:";
+
+/// Number of bytes in a size such as `4096`, `64KiB` or `1GiB`
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+
+    let shift = match unit.trim() {
+        "" | "B" => 0,
+        "KiB" => 10,
+        "MiB" => 20,
+        "GiB" => 30,
+        "TiB" => 40,
+        _ => return Err(format!("Unknown size {:?}, expected bytes or a KiB, MiB, GiB or TiB suffix", text)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or(format!("Invalid size {:?}", text))
+}
+
+/// A byte range of the signed data, where a missing end is the end of the data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl Range {
+    /// Parse `start..end`, where either size may be left out, such as `1GiB..2GiB` or `4MiB..`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.splitn(2, "..");
+        let (start, end) = match (parts.next(), parts.next()) {
+            (Some(start), Some(end)) => (start.trim(), end.trim()),
+            _ => return Err(format!("Invalid range {:?}, expected start..end", text)),
+        };
+
+        let start = if start.is_empty() { 0 } else { parse_size(start)? };
+        let end = if end.is_empty() { None } else { Some(parse_size(end)?) };
+        if end.map_or(false, |end| end < start) {
+            return Err(format!("Range {:?} ends before it starts", text));
+        }
+        Ok(Range { start, end })
+    }
+}
+
+/// Hash everything `reader` produces in chunks of `size` bytes
+pub fn chunks(mut reader: impl Read, size: u64) -> io::Result<Chunks> {
+    let mut chunks = Chunks { size, length: 0, hashes: vec![] };
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        // Each chunk is hashed as it streams past, so only `buf` is held in memory
+        let mut hasher = leaf_hasher();
+        let mut filled = 0;
+        while filled < size {
+            let want = buf.len().min((size - filled) as usize);
+            match reader.read(&mut buf[..want]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    hasher.update(&buf[..n]);
+                    filled += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        chunks.length += filled;
+        chunks.hashes.push(hex::encode(hasher.finish()));
+        if filled < size {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+/// The `merkle-sha256:` digest of the chunk hashes, which is what gets signed
+pub fn digest(chunks: &Chunks) -> Result<String, String> {
+    Ok(format!("{}{}", PREFIX, hex::encode(root(&leaves(chunks)?))))
+}
+
+/// Check that the chunk hashes add up to the signed digest, and to the stated length
+pub fn check(chunks: &Chunks, signed_digest: &str) -> Result<(), String> {
+    if chunks.size == 0 {
+        return Err("Chunk size is zero!".to_string());
+    }
+    // Rounded up without adding to the length, since both come from the signature file
+    let expected = chunks.length / chunks.size + (chunks.length % chunks.size != 0) as u64;
+    if chunks.hashes.len() as u64 != expected {
+        return Err(format!(
+            "{} chunk hashes do not fit {} bytes in chunks of {}",
+            chunks.hashes.len(),
+            chunks.length,
+            chunks.size
+        ));
+    }
+    if digest(chunks)? != signed_digest {
+        return Err("Chunk hashes do not match the signed digest!".to_string());
+    }
+    Ok(())
+}
+
+/// Check `data` against chunk hashes that have already passed `check`
+///
+/// `data` starts at chunk `first`, and runs to the end of a chunk or of the file.
+pub fn check_data(chunks: &Chunks, first: u64, data: &[u8]) -> Result<(), String> {
+    let leaves = leaves(chunks)?;
+    let mut index = first;
+    for chunk in data.chunks(chunks.size as usize) {
+        let expected = leaves.get(index as usize).ok_or("Data runs past the end of the signed file!")?;
+        if leaf(chunk) != *expected {
+            return Err(format!("Chunk {} was changed since it was signed!", index));
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+/// Check the whole of `data` against chunk hashes that have already passed `check`
+pub fn check_all(chunks: &Chunks, data: &[u8]) -> Result<(), String> {
+    if data.len() as u64 != chunks.length {
+        return Err("Signature was made for different data!".to_string());
+    }
+    check_data(chunks, 0, data)
+}
+
+/// Check `data`, which holds just the bytes of `range`, against chunk hashes that have already passed `check`
+///
+/// Chunks are verified whole, so the range must start and end on a chunk
+/// boundary, or end at the end of the data.
+pub fn check_range(chunks: &Chunks, range: &Range, data: &[u8]) -> Result<(), String> {
+    let end = range.end.unwrap_or(chunks.length);
+    if end > chunks.length {
+        return Err(format!("Range ends past the end of the {} signed bytes", chunks.length));
+    }
+    if range.start % chunks.size != 0 || (end % chunks.size != 0 && end != chunks.length) {
+        return Err(format!("Range must start and end on a multiple of the chunk size, {} bytes", chunks.size));
+    }
+    if data.len() as u64 != end - range.start {
+        return Err(format!("Input is {} bytes, but the range is {} bytes", data.len(), end - range.start));
+    }
+    check_data(chunks, range.start / chunks.size, data)
+}
+
+fn leaves(chunks: &Chunks) -> Result<Vec<[u8; 32]>, String> {
+    chunks
+        .hashes
+        .iter()
+        .map(|hash| {
+            let mut leaf = [0u8; 32];
+            match hex::decode(hash) {
+                Ok(ref bytes) if bytes.len() == 32 => leaf.copy_from_slice(bytes),
+                _ => return Err(format!("Chunk hash {:?} is not a hex SHA-256 hash", hash)),
+            }
+            Ok(leaf)
+        })
+        .collect()
+}
+
+fn leaf_hasher() -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update(&[0]);
+    hasher
+}
+
+fn leaf(chunk: &[u8]) -> [u8; 32] {
+    let mut hasher = leaf_hasher();
+    hasher.update(chunk);
+    hasher.finish()
+}
+
+/// The RFC 6962 Merkle tree hash, splitting at the largest power of two below the length
+fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => sha256(&[]),
+        1 => leaves[0],
+        n => {
+            let split = n.next_power_of_two() / 2;
+            let mut hasher = Sha256::new();
+            hasher.update(&[1]);
+            hasher.update(&root(&leaves[..split]));
+            hasher.update(&root(&leaves[split..]));
+            hasher.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The leaves of the RFC 6962 test tree, from the certificate-transparency test suite
+    const INPUTS: [&str; 8] = ["", "00", "10", "2021", "3031", "40414243", "5051525354555657", "606162636465666768696a6b6c6d6e6f"];
+
+    /// Its root over the first 1 to 8 leaves
+    const ROOTS: [&str; 8] = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+
+    fn signed(data: &[u8], size: u64) -> (Chunks, String) {
+        let chunks = super::chunks(data, size).unwrap();
+        let digest = digest(&chunks).unwrap();
+        check(&chunks, &digest).unwrap();
+        (chunks, digest)
+    }
+
+    #[test]
+    fn root_matches_rfc6962() {
+        let leaves: Vec<_> = INPUTS.iter().map(|input| leaf(&hex::decode(input).unwrap())).collect();
+        for (n, expected) in ROOTS.iter().enumerate() {
+            assert_eq!(hex::encode(root(&leaves[..n + 1])), *expected);
+        }
+        assert_eq!(hex::encode(root(&[])), hex::encode(sha256(&[])));
+    }
+
+    #[test]
+    fn chunks_split_at_size() {
+        assert_eq!(chunks(&b""[..], 4).unwrap().hashes.len(), 0);
+        for (len, count) in [(1, 1), (3, 1), (4, 1), (5, 2), (8, 2), (9, 3)] {
+            let chunks = chunks(&vec![7u8; len][..], 4).unwrap();
+            assert_eq!((chunks.length, chunks.hashes.len()), (len as u64, count));
+        }
+    }
+
+    #[test]
+    fn check_rejects_wrong_chunk_counts() {
+        let (chunks, digest) = signed(b"012345678", 4);
+
+        let mut extra = chunks.clone();
+        extra.hashes.push(extra.hashes[0].clone());
+        assert!(check(&extra, &digest).is_err());
+
+        let longer = Chunks { length: 13, ..chunks.clone() };
+        assert!(check(&longer, &digest).is_err());
+        let shorter = Chunks { length: 8, ..chunks.clone() };
+        assert!(check(&shorter, &digest).is_err());
+
+        assert!(check(&Chunks { size: 0, ..chunks.clone() }, &digest).is_err());
+
+        let mut changed = chunks;
+        changed.hashes[1] = changed.hashes[0].clone();
+        assert!(check(&changed, &digest).is_err());
+    }
+
+    #[test]
+    fn check_survives_huge_sizes() {
+        let (chunks, digest) = signed(&[1u8; 9], 4);
+        for size in [u64::MAX, u64::MAX - 1, 1 << 63] {
+            assert!(check(&Chunks { size, ..chunks.clone() }, &digest).is_err());
+        }
+
+        let whole = Chunks { size: u64::MAX, length: u64::MAX, hashes: vec![chunks.hashes[0].clone()] };
+        assert!(check(&whole, &digest).is_err());
+    }
+
+    #[test]
+    fn check_all_finds_changed_chunks() {
+        let data = b"0123456789";
+        let (chunks, _) = signed(data, 4);
+        assert_eq!(check_all(&chunks, data), Ok(()));
+        assert!(check_all(&chunks, b"0123456788").is_err());
+        assert!(check_all(&chunks, b"012345678").is_err());
+    }
+
+    #[test]
+    fn check_range_needs_chunk_boundaries() {
+        let data = b"0123456789";
+        let (chunks, _) = signed(data, 4);
+        let range = |start, end| Range { start, end };
+
+        assert_eq!(check_range(&chunks, &range(0, Some(4)), b"0123"), Ok(()));
+        assert_eq!(check_range(&chunks, &range(4, Some(8)), b"4567"), Ok(()));
+        assert_eq!(check_range(&chunks, &range(8, None), b"89"), Ok(()));
+        assert_eq!(check_range(&chunks, &range(4, Some(10)), b"456789"), Ok(()));
+        assert_eq!(check_range(&chunks, &range(0, None), data), Ok(()));
+
+        assert!(check_range(&chunks, &range(1, Some(4)), b"123").is_err());
+        assert!(check_range(&chunks, &range(0, Some(5)), b"01234").is_err());
+        assert!(check_range(&chunks, &range(8, Some(12)), b"89").is_err());
+        assert!(check_range(&chunks, &range(4, Some(8)), b"456").is_err());
+        assert!(check_range(&chunks, &range(4, Some(8)), b"4568").is_err());
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(Range::parse("1KiB..2KiB"), Ok(Range { start: 1024, end: Some(2048) }));
+        assert_eq!(Range::parse("..4"), Ok(Range { start: 0, end: Some(4) }));
+        assert_eq!(Range::parse("4MiB.."), Ok(Range { start: 4 << 20, end: None }));
+        assert!(Range::parse("2..1").is_err());
+        assert!(Range::parse("4").is_err());
+        assert!(parse_size("16777216TiB").is_err());
+    }
+}
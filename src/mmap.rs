@@ -0,0 +1,80 @@
+//! Memory-mapped input files, for `--mmap`
+//!
+//! Mapping a file lets the digest modes hash it, or check it against a
+//! detached signature, without copying it onto the heap first. The file must
+//! not be truncated while it is mapped, or reading it crashes the process.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// A read-only mapping of a whole file
+#[cfg(unix)]
+pub struct Mapped {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+/// Where mapping is not supported, the file is read into memory instead
+#[cfg(not(unix))]
+pub struct Mapped(Vec<u8>);
+
+#[cfg(unix)]
+pub fn map(path: &Path) -> io::Result<Mapped> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+
+    // Empty files cannot be mapped, and have nothing to map anyway
+    if len == 0 {
+        return Ok(Mapped { ptr: std::ptr::null_mut(), len });
+    }
+
+    let ptr = unsafe {
+        libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Only a hint for the kernel to read ahead, so failure does not matter
+    unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+    Ok(Mapped { ptr, len })
+}
+
+#[cfg(not(unix))]
+pub fn map(path: &Path) -> io::Result<Mapped> {
+    use std::io::Read;
+
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+    Ok(Mapped(contents))
+}
+
+impl Deref for Mapped {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
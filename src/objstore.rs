@@ -0,0 +1,112 @@
+//! Objects in S3, Google Cloud Storage and IPFS, for `-i`, `-o` and `-s`
+//!
+//! `s3://bucket/key` and `gs://bucket/key` are streamed through the `aws` and
+//! `gcloud` commands, so they use whatever credentials those find in the
+//! environment: profiles, SSO sessions, instance and workload identities.
+//!
+//! `ipfs://<cid>` is read through the local IPFS node with `ipfs cat`, which
+//! checks the content against its CID. It cannot be written to, as the CID
+//! is only known once the content is added, which `publish` does.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// Whether `path` names an object rather than a local file
+pub fn is_object(path: &Path) -> bool {
+    path.to_str().map_or(false, |url| ["s3://", "gs://", "ipfs://"].iter().any(|scheme| url.starts_with(scheme)))
+}
+
+/// Add `contents` to the local IPFS node, returning its CID
+pub fn publish(contents: &[u8]) -> Result<String, String> {
+    let mut child = Command::new("ipfs")
+        .args(["add", "--quiet", "--cid-version", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ipfs to publish\nError: {:?}", e))?;
+    let written = child.stdin.take().unwrap().write_all(contents);
+    let output = child.wait_with_output().map_err(|e| format!("ipfs failed\nError: {:?}", e))?;
+    match (written, output.status.success()) {
+        (Ok(()), true) => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        _ => Err("Failed to publish, ipfs could not add it, is the IPFS daemon running?".to_string()),
+    }
+}
+
+/// Stream the object at `path`
+pub fn open(path: &Path) -> Result<Box<dyn Read>, String> {
+    let url = path.to_str().unwrap();
+    let mut child = command(url, false)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {} to read {}\nError: {:?}", program(url), url, e))?;
+    let stdout = child.stdout.take().unwrap();
+    Ok(Box::new(Download { child, stdout, url: url.to_string() }))
+}
+
+pub fn get(path: &Path) -> Result<Vec<u8>, String> {
+    let mut contents = vec![];
+    open(path)?
+        .read_to_end(&mut contents)
+        .map_err(|e| format!("Failed to read {}\nError: {}", path.display(), e))?;
+    Ok(contents)
+}
+
+pub fn put(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let url = path.to_str().unwrap();
+    if url.starts_with("ipfs://") {
+        return Err(format!("Cannot write to {}, publish to IPFS with sign --publish ipfs instead", url));
+    }
+    let mut child = command(url, true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {} to write {}\nError: {:?}", program(url), url, e))?;
+    let written = child.stdin.take().unwrap().write_all(contents);
+    let status = child.wait().map_err(|e| format!("{} failed\nError: {:?}", program(url), e))?;
+    match (written, status.success()) {
+        (Ok(()), true) => Ok(()),
+        _ => Err(format!("Failed to write {}, {} did not upload it", url, program(url))),
+    }
+}
+
+/// The copy command between `url` and stdin or stdout, which both tools name `-`
+fn command(url: &str, upload: bool) -> Command {
+    let mut command = Command::new(program(url));
+    if let Some(cid) = url.strip_prefix("ipfs://") {
+        command.args(["cat", &format!("/ipfs/{}", cid)]);
+        return command;
+    }
+    command.args([if url.starts_with("s3://") { "s3" } else { "storage" }, "cp", "--quiet"]);
+    if upload {
+        command.args(["-", url]);
+    } else {
+        command.args([url, "-"]);
+    }
+    command
+}
+
+fn program(url: &str) -> &'static str {
+    match &url[..url.find(':').unwrap()] {
+        "s3" => "aws",
+        "gs" => "gcloud",
+        _ => "ipfs",
+    }
+}
+
+/// An object being downloaded, which fails at its end if the download did
+struct Download {
+    child: Child,
+    stdout: ChildStdout,
+    url: String,
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() && !self.child.wait()?.success() {
+            return Err(io::Error::new(io::ErrorKind::Other, format!("{} could not download {}", program(&self.url), self.url)));
+        }
+        Ok(read)
+    }
+}
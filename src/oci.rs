@@ -0,0 +1,375 @@
+//! Signatures attached to images in OCI registries, for `signit oci`
+//!
+//! A signature is an envelope over the manifest digest, in namespace
+//! `oci-manifest`, pushed as the single layer of an artifact manifest whose
+//! `subject` is the signed manifest. Registries that implement the referrers
+//! API index it themselves; for those that do not, the artifact is added to
+//! the `sha256-<hex>` tagged index of the referrers tag schema, which is where
+//! OCI 1.1 clients look in that case.
+//!
+//! Registries are reached over HTTPS, except `localhost` which is plain HTTP.
+//! Credentials are read from the Docker config, as written by `docker login`,
+//! and exchanged for a bearer token when the registry asks for one.
+
+use std::fs;
+use std::path::PathBuf;
+
+use base64::decode;
+use openssl::sha::sha256;
+use reqwest::header::{HeaderMap, ACCEPT, CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Namespace of envelopes over a manifest digest
+pub const NAMESPACE: &str = "oci-manifest";
+
+/// `artifactType` of signature manifests
+pub const ARTIFACT_TYPE: &str = "application/vnd.signit.signature.v1+json";
+
+/// Media type of the layer holding the envelope
+const ENVELOPE_TYPE: &str = "application/vnd.signit.envelope.v1+json";
+
+const EMPTY_TYPE: &str = "application/vnd.oci.empty.v1+json";
+const MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const INDEX_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// Manifest types a reference may resolve to
+const MANIFEST_TYPES: &[&str] = &[
+    MANIFEST_TYPE,
+    INDEX_TYPE,
+    "application/vnd.docker.distribution.manifest.v2+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+/// An image reference, like `ghcr.io/org/app:1.0` or `app@sha256:...`
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Descriptor {
+    #[serde(rename = "mediaType")]
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+
+    #[serde(rename = "artifactType", skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+}
+
+pub struct Registry {
+    client: Client,
+    base: String,
+    repository: String,
+
+    /// `user:password` for the registry, from the Docker config
+    credentials: Option<(String, String)>,
+    token: Option<String>,
+}
+
+impl Reference {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (name, digest) = match text.find('@') {
+            Some(at) => (&text[..at], Some(text[at + 1..].to_string())),
+            None => (text, None),
+        };
+        let (name, tag) = match name.rfind(':').filter(|at| !name[*at..].contains('/')) {
+            Some(at) => (&name[..at], Some(name[at + 1..].to_string())),
+            None => (name, None),
+        };
+
+        let (registry, repository) = match name.find('/') {
+            Some(at) if name[..at].contains('.') || name[..at].contains(':') || &name[..at] == "localhost" => {
+                (name[..at].to_string(), name[at + 1..].to_string())
+            }
+            Some(_) => ("docker.io".to_string(), name.to_string()),
+            None => ("docker.io".to_string(), format!("library/{}", name)),
+        };
+        if repository.is_empty() || repository.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(format!("{:?} is not an image reference like registry/repository:tag", text));
+        }
+        if let Some(digest) = &digest {
+            check_digest(digest)?;
+        }
+        let tag = tag.or_else(|| Some("latest".to_string()).filter(|_| digest.is_none()));
+        Ok(Reference { registry, repository, tag, digest })
+    }
+}
+
+impl Descriptor {
+    fn of(media_type: &str, body: &[u8]) -> Self {
+        Descriptor {
+            media_type: media_type.to_string(),
+            digest: digest(body),
+            size: body.len() as u64,
+            artifact_type: None,
+        }
+    }
+}
+
+impl Registry {
+    pub fn new(reference: &Reference) -> Self {
+        let host = match reference.registry.as_str() {
+            "docker.io" => "registry-1.docker.io",
+            host => host,
+        };
+        let local = host == "localhost" || host.starts_with("localhost:") || host.starts_with("127.0.0.1");
+        Registry {
+            client: Client::new(),
+            base: format!("{}://{}/v2/{}", if local { "http" } else { "https" }, host, reference.repository),
+            repository: reference.repository.clone(),
+            credentials: docker_credentials(&reference.registry),
+            token: None,
+        }
+    }
+
+    /// The manifest `reference` names, checked against its digest if it has one
+    pub fn resolve(&mut self, reference: &Reference) -> Result<Descriptor, String> {
+        let name = reference.digest.as_ref().or(reference.tag.as_ref()).unwrap();
+        let (media_type, body) = self
+            .manifest(name)?
+            .ok_or_else(|| format!("{}/{} has no manifest {}", reference.registry, self.repository, name))?;
+        let descriptor = Descriptor::of(&media_type, &body);
+        if reference.digest.as_ref().map_or(false, |digest| *digest != descriptor.digest) {
+            return Err(format!("Registry sent a manifest that does not match {}", name));
+        }
+        Ok(descriptor)
+    }
+
+    /// Push `envelope` as a signature of `subject`, returning the digest of the signature manifest
+    pub fn attach(&mut self, subject: &Descriptor, envelope: &[u8]) -> Result<String, String> {
+        let config = Descriptor::of(EMPTY_TYPE, b"{}");
+        self.push_blob(&config, b"{}")?;
+        let layer = Descriptor::of(ENVELOPE_TYPE, envelope);
+        self.push_blob(&layer, envelope)?;
+
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": MANIFEST_TYPE,
+            "artifactType": ARTIFACT_TYPE,
+            "config": config,
+            "layers": [layer],
+            "subject": subject,
+        });
+        let manifest = serde_json::to_vec(&manifest).unwrap();
+        let mut descriptor = Descriptor::of(MANIFEST_TYPE, &manifest);
+        descriptor.artifact_type = Some(ARTIFACT_TYPE.to_string());
+
+        let response = self.push_manifest(&descriptor.digest, MANIFEST_TYPE, manifest)?;
+        if !response.headers().contains_key("oci-subject") {
+            self.add_to_fallback(subject, &descriptor)?;
+        }
+        Ok(descriptor.digest)
+    }
+
+    /// The envelopes of every signature attached to `subject`
+    pub fn signatures(&mut self, subject: &Descriptor) -> Result<Vec<Vec<u8>>, String> {
+        let url = format!("{}/referrers/{}?artifactType={}", self.base, subject.digest, ARTIFACT_TYPE.replace('/', "%2F").replace('+', "%2B"));
+        let mut response = self.send(|client| client.get(&url).header(ACCEPT, INDEX_TYPE))?;
+        let index: Value = if response.status().is_success() {
+            response.json().map_err(|e| format!("Registry sent an invalid referrers index\nError: {:?}", e))?
+        } else if response.status() == StatusCode::NOT_FOUND {
+            match self.manifest(&fallback_tag(&subject.digest))? {
+                Some((_, body)) => serde_json::from_slice(&body).map_err(|e| format!("Referrers tag is not an index\nError: {:?}", e))?,
+                None => return Ok(vec![]),
+            }
+        } else {
+            return Err(format!("Registry answered {} when listing referrers", response.status()));
+        };
+
+        let referrers = index.get("manifests").cloned().map(serde_json::from_value::<Vec<Descriptor>>);
+        let referrers = referrers.unwrap_or_else(|| Ok(vec![])).map_err(|e| format!("Registry sent an invalid referrers index\nError: {:?}", e))?;
+
+        let mut envelopes = vec![];
+        for referrer in referrers.iter().filter(|r| r.artifact_type.as_deref() == Some(ARTIFACT_TYPE)) {
+            let body = match self.manifest(&referrer.digest)? {
+                Some((_, body)) if digest(&body) == referrer.digest => body,
+                _ => continue,
+            };
+            let layers = serde_json::from_slice::<Value>(&body)
+                .ok()
+                .and_then(|manifest| manifest.get("layers").cloned())
+                .and_then(|layers| serde_json::from_value::<Vec<Descriptor>>(layers).ok())
+                .unwrap_or_default();
+            for layer in layers.iter().filter(|layer| layer.media_type == ENVELOPE_TYPE) {
+                envelopes.push(self.blob(&layer.digest)?);
+            }
+        }
+        Ok(envelopes)
+    }
+
+    fn manifest(&mut self, name: &str) -> Result<Option<(String, Vec<u8>)>, String> {
+        let url = format!("{}/manifests/{}", self.base, name);
+        let mut response = self.send(|client| client.get(&url).header(ACCEPT, MANIFEST_TYPES.join(", ")))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = body(&mut response, "fetching manifest")?;
+        let media_type = header(response.headers(), CONTENT_TYPE)
+            .or_else(|| serde_json::from_slice::<Value>(&body).ok()?.get("mediaType")?.as_str().map(String::from))
+            .ok_or("Registry sent a manifest without a media type")?;
+        Ok(Some((media_type, body)))
+    }
+
+    fn blob(&mut self, digest_: &str) -> Result<Vec<u8>, String> {
+        let url = format!("{}/blobs/{}", self.base, digest_);
+        let mut response = self.send(|client| client.get(&url))?;
+        let body = body(&mut response, "fetching a signature")?;
+        if digest(&body) != digest_ {
+            return Err(format!("Registry sent a blob that does not match {}", digest_));
+        }
+        Ok(body)
+    }
+
+    fn push_blob(&mut self, descriptor: &Descriptor, contents: &[u8]) -> Result<(), String> {
+        let url = format!("{}/blobs/{}", self.base, descriptor.digest);
+        if self.send(|client| client.head(&url))?.status().is_success() {
+            return Ok(());
+        }
+
+        let url = format!("{}/blobs/uploads/", self.base);
+        let response = self.send(|client| client.post(&url))?;
+        if response.status() != StatusCode::ACCEPTED {
+            return Err(format!("Registry answered {} when starting an upload", response.status()));
+        }
+        let location = header(response.headers(), LOCATION).ok_or("Registry did not say where to upload")?;
+        let location = if location.starts_with('/') {
+            format!("{}{}", &self.base[..self.base.find("/v2/").unwrap()], location)
+        } else {
+            location
+        };
+        let separator = if location.contains('?') { '&' } else { '?' };
+        let url = format!("{}{}digest={}", location, separator, descriptor.digest.replace(':', "%3A"));
+
+        let response = self.send(|client| {
+            client.put(&url).header(CONTENT_TYPE, "application/octet-stream").body(contents.to_vec())
+        })?;
+        if !response.status().is_success() {
+            return Err(format!("Registry answered {} when uploading {}", response.status(), descriptor.digest));
+        }
+        Ok(())
+    }
+
+    fn push_manifest(&mut self, name: &str, media_type: &str, manifest: Vec<u8>) -> Result<Response, String> {
+        let url = format!("{}/manifests/{}", self.base, name);
+        let response = self.send(|client| client.put(&url).header(CONTENT_TYPE, media_type).body(manifest.clone()))?;
+        if !response.status().is_success() {
+            return Err(format!("Registry answered {} when pushing manifest {}", response.status(), name));
+        }
+        Ok(response)
+    }
+
+    /// Add `referrer` to the referrers tag schema index of `subject`
+    fn add_to_fallback(&mut self, subject: &Descriptor, referrer: &Descriptor) -> Result<(), String> {
+        let tag = fallback_tag(&subject.digest);
+        let mut index = match self.manifest(&tag)? {
+            Some((_, body)) => serde_json::from_slice(&body).map_err(|e| format!("Referrers tag is not an index\nError: {:?}", e))?,
+            None => json!({ "schemaVersion": 2, "mediaType": INDEX_TYPE, "manifests": [] }),
+        };
+        match index.get_mut("manifests").and_then(Value::as_array_mut) {
+            Some(manifests) => manifests.push(serde_json::to_value(referrer).unwrap()),
+            None => return Err(format!("Referrers tag {} has no manifests", tag)),
+        }
+        self.push_manifest(&tag, INDEX_TYPE, serde_json::to_vec(&index).unwrap()).map(|_| ())
+    }
+
+    /// Send a request, authenticating and trying again if the registry asks
+    fn send(&mut self, request: impl Fn(&Client) -> RequestBuilder) -> Result<Response, String> {
+        let response = self.authorized(request(&self.client)).send().map_err(|e| format!("{:?}", e))?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let challenge = header(response.headers(), WWW_AUTHENTICATE).unwrap_or_default();
+        self.authenticate(&challenge)?;
+        self.authorized(request(&self.client)).send().map_err(|e| format!("{:?}", e))
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match (&self.token, &self.credentials) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some((user, password))) => request.basic_auth(user, Some(password)),
+            (None, None) => request,
+        }
+    }
+
+    /// Get a bearer token as the `WWW-Authenticate` challenge asks, or fall back to basic credentials
+    fn authenticate(&mut self, challenge: &str) -> Result<(), String> {
+        let params = match challenge.strip_prefix("Bearer ") {
+            Some(params) => params,
+            None if self.credentials.is_some() && self.token.is_none() => return Ok(()),
+            None => return Err(format!("Registry refused access to {}, log in with docker login", self.repository)),
+        };
+        let param = |name: &str| {
+            params.split(',').find_map(|param| {
+                let value = param.trim().strip_prefix(name)?.strip_prefix('=')?;
+                Some(value.trim_matches('"').to_string())
+            })
+        };
+        let realm = param("realm").ok_or("Registry sent a bearer challenge without a realm")?;
+        let mut query = vec![];
+        for name in &["service", "scope"] {
+            if let Some(value) = param(name) {
+                query.push((name.to_string(), value));
+            }
+        }
+
+        let mut request = self.client.get(&realm).query(&query);
+        if let Some((user, password)) = &self.credentials {
+            request = request.basic_auth(user, Some(password));
+        }
+        let mut response = request.send().map_err(|e| format!("{:?}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Registry refused a token for {}: {}, log in with docker login", self.repository, response.status()));
+        }
+        let body: Value = response.json().map_err(|e| format!("Registry sent an invalid token\nError: {:?}", e))?;
+        let token = body.get("token").or_else(|| body.get("access_token")).and_then(Value::as_str);
+        self.token = Some(token.ok_or("Registry sent no token")?.to_string());
+        Ok(())
+    }
+}
+
+/// The `user:password` saved by `docker login` for `registry`
+fn docker_credentials(registry: &str) -> Option<(String, String)> {
+    let dir = std::env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".docker")))?;
+    let config: Value = serde_json::from_slice(&fs::read(dir.join("config.json")).ok()?).ok()?;
+    let key = if registry == "docker.io" { "https://index.docker.io/v1/" } else { registry };
+    let auth = decode(config.get("auths")?.get(key)?.get("auth")?.as_str()?).ok()?;
+    let auth = String::from_utf8(auth).ok()?;
+    let at = auth.find(':')?;
+    Some((auth[..at].to_string(), auth[at + 1..].to_string()))
+}
+
+/// The tag the referrers tag schema keeps the referrers of `digest` under
+fn fallback_tag(digest: &str) -> String {
+    digest.replace(':', "-")
+}
+
+fn check_digest(digest: &str) -> Result<(), String> {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+        _ => Err(format!("{:?} is not a sha256: digest", digest)),
+    }
+}
+
+fn digest(contents: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(sha256(contents)))
+}
+
+fn body(response: &mut Response, doing: &str) -> Result<Vec<u8>, String> {
+    if !response.status().is_success() {
+        return Err(format!("Registry answered {} when {}", response.status(), doing));
+    }
+    let mut body = vec![];
+    response.copy_to(&mut body).map_err(|e| format!("{:?}", e))?;
+    Ok(body)
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(String::from)
+}
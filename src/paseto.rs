@@ -0,0 +1,137 @@
+//! PASETO v4.public tokens, for `signit paseto`
+//!
+//! A token is `v4.public.` and the unpadded base64url of the claims followed
+//! by their ed25519 signature, then optionally `.` and a base64url footer. The
+//! signature covers the header, claims, footer and an implicit assertion,
+//! joined by pre-authentication encoding, so none of them can be swapped.
+//! Unlike JWT there is no algorithm to choose: the version fixes it.
+//!
+//! Registered claims `exp`, `nbf` and `iat` are RFC 3339 times.
+
+use base64::{decode_config, encode_config, URL_SAFE_NO_PAD};
+use serde_json::{Map, Value};
+
+const HEADER: &str = "v4.public.";
+
+pub struct Token {
+    pub claims: Map<String, Value>,
+    pub footer: Vec<u8>,
+
+    /// The bytes the signature covers
+    pub signed: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// What the signature covers, given the claims as serialized into the token
+pub fn signed_bytes(message: &[u8], footer: &[u8], implicit: &[u8]) -> Vec<u8> {
+    pae(&[HEADER.as_bytes(), message, footer, implicit])
+}
+
+/// The token for `message`, once `signature` has been made over `signed_bytes`
+pub fn finish(message: &[u8], signature: &[u8; 64], footer: &[u8]) -> String {
+    let mut token = format!("{}{}", HEADER, encode_config(&[message, &signature[..]].concat(), URL_SAFE_NO_PAD));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&encode_config(footer, URL_SAFE_NO_PAD));
+    }
+    token
+}
+
+/// An RFC 3339 time in UTC, as registered claims hold them
+pub fn format_time(tm: &time::Tm) -> String {
+    tm.rfc3339().to_string()
+}
+
+impl Token {
+    pub fn parse(token: &str, implicit: &[u8]) -> Result<Self, String> {
+        let body = token.trim().strip_prefix(HEADER).ok_or("Not a v4.public PASETO token")?;
+        let (payload, footer) = match body.find('.') {
+            Some(at) => (&body[..at], &body[at + 1..]),
+            None => (body, ""),
+        };
+        let payload = decode_config(payload, URL_SAFE_NO_PAD).map_err(|_| "Token payload is not base64url")?;
+        let footer = decode_config(footer, URL_SAFE_NO_PAD).map_err(|_| "Token footer is not base64url")?;
+        if payload.len() < 64 {
+            return Err("Token payload is too short to hold a signature".to_string());
+        }
+        let (message, signature) = payload.split_at(payload.len() - 64);
+
+        let claims = match serde_json::from_slice(message) {
+            Ok(Value::Object(claims)) => claims,
+            _ => return Err("Token claims are not a JSON object".to_string()),
+        };
+        Ok(Token {
+            claims,
+            signed: signed_bytes(message, &footer, implicit),
+            footer,
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Check `exp` and `nbf` against `now`
+    pub fn check_time(&self, now: &time::Tm) -> Result<(), String> {
+        if let Some(exp) = self.time_claim("exp")? {
+            if *now >= exp {
+                return Err(format!("Token expired at {}", self.claims["exp"]));
+            }
+        }
+        if let Some(nbf) = self.time_claim("nbf")? {
+            if *now < nbf {
+                return Err(format!("Token is not valid before {}", self.claims["nbf"]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a string claim, such as `aud` or `iss`, is `expected`
+    pub fn check_claim(&self, claim: &str, expected: &str) -> Result<(), String> {
+        match self.claims.get(claim).and_then(Value::as_str) {
+            Some(value) if value == expected => Ok(()),
+            _ => Err(format!("Token claim {} is not {:?}", claim, expected)),
+        }
+    }
+
+    fn time_claim(&self, claim: &str) -> Result<Option<time::Tm>, String> {
+        match self.claims.get(claim) {
+            None => Ok(None),
+            Some(Value::String(text)) => parse_time(text).map(Some),
+            Some(_) => Err(format!("Token claim {} is not an RFC 3339 time", claim)),
+        }
+    }
+}
+
+/// Parse an RFC 3339 time with `Z` or a numeric offset, as UTC
+fn parse_time(text: &str) -> Result<time::Tm, String> {
+    let invalid = || format!("{:?} is not an RFC 3339 time", text);
+    let (local, offset) = if let Some(local) = text.strip_suffix('Z') {
+        (local, 0)
+    } else {
+        let at = text.len().checked_sub(6).filter(|at| text.is_char_boundary(*at)).ok_or_else(invalid)?;
+        let (local, offset) = text.split_at(at);
+        if !offset.is_ascii() || offset.as_bytes()[3] != b':' {
+            return Err(invalid());
+        }
+        let sign = match offset.as_bytes()[0] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return Err(invalid()),
+        };
+        let hours: i64 = offset[1..3].parse().map_err(|_| invalid())?;
+        let minutes: i64 = offset[4..].parse().map_err(|_| invalid())?;
+        (local, sign * (hours * 3600 + minutes * 60))
+    };
+    // Fractional seconds do not change which second a token expires in
+    let local = local.split('.').next().unwrap();
+    let tm = time::strptime(local, "%Y-%m-%dT%H:%M:%S").map_err(|_| invalid())?;
+    Ok(time::at_utc(time::Timespec::new(tm.to_timespec().sec - offset, 0)))
+}
+
+/// Pre-authentication encoding: the count of pieces, then each piece after its length
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut out = (pieces.len() as u64).to_le_bytes().to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+        out.extend_from_slice(piece);
+    }
+    out
+}
@@ -0,0 +1,341 @@
+//! Verification policies, for `verify --policy`
+//!
+//! A policy says who has to sign and what their signatures have to look like,
+//! so a release check is one file rather than a long line of flags:
+//!
+//! ```json
+//! {
+//!   "signers": [
+//!     { "name": "alice", "key": "ssh-ed25519 AAAA..." },
+//!     { "name": "bob", "github": "bob" },
+//!     { "name": "release-bot", "fingerprint": "SHA256:bg+AEuD0..." }
+//!   ],
+//!   "threshold": 2,
+//!   "namespaces": ["release", "release/*"],
+//!   "max_age_days": 90,
+//!   "metadata": { "builder": ["github-actions"], "commit": ["*"] },
+//!   "revocations": ["revocations.json"]
+//! }
+//! ```
+//!
+//! A signer given only by fingerprint must embed their key in the signature.
+//! Each `metadata` key must be in the signature's `sign --meta` pairs, with a
+//! value matching one of its patterns.
+//! `threshold` defaults to every signer. A signer counts once one of their
+//! signatures passes every rule. A key only counts for one signer, so two
+//! signers sharing a key cannot meet a threshold of two with one signature.
+
+use base64::decode;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+use thrussh_keys::key::PublicKey;
+
+use crate::envelope::{parse_timestamp, SignIt};
+use crate::revoke::{self, Revocation};
+use crate::usage::glob_match;
+use crate::{eject, forge, keys};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub signers: Vec<RequiredSigner>,
+
+    /// How many of `signers` must sign, defaults to all of them
+    pub threshold: Option<usize>,
+
+    /// Namespace patterns signatures may be made for, as in usage policies
+    pub namespaces: Option<Vec<String>>,
+
+    /// Refuse signatures older than this, and those without a timestamp
+    pub max_age_days: Option<i64>,
+
+    /// Patterns the value of each of these signed metadata keys must match one of
+    #[serde(default)]
+    pub metadata: BTreeMap<String, Vec<String>>,
+
+    /// Files or https:// URLs of revocations, as for `verify --revocations`
+    #[serde(default)]
+    pub revocations: Vec<String>,
+}
+
+/// One signer a policy asks for, by exactly one of `key`, `fingerprint` or `github`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequiredSigner {
+    /// How the report refers to the signer
+    pub name: Option<String>,
+    pub key: Option<String>,
+    pub fingerprint: Option<String>,
+    pub github: Option<String>,
+}
+
+/// The outcome of each rule, in the order they were checked
+pub struct Report {
+    pub passed: bool,
+    pub clauses: Vec<Clause>,
+
+    /// Fingerprints of the keys that satisfied a signer
+    pub fingerprints: Vec<String>,
+}
+
+pub struct Clause {
+    pub passed: bool,
+    pub text: String,
+
+    /// The outcomes this one follows from, filled in by `explain`
+    pub details: Vec<Clause>,
+}
+
+/// A required signer, with the keys it may sign with
+struct Candidate {
+    label: String,
+    keys: Vec<PublicKey>,
+
+    /// Set for signers given by fingerprint, who are matched against embedded keys
+    fingerprint: Option<String>,
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Self {
+        let raw = read_to_string(path)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to read policy {:?}\nError: {:?}", path, e));
+            });
+        let policy: Policy = serde_json::from_str(&raw)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to parse policy {:?}\nError: {:?}", path, e));
+            });
+
+        if policy.signers.is_empty() {
+            eject(&format!("Policy {:?} names no signers", path));
+        }
+        if policy.threshold.map_or(false, |t| t == 0 || t > policy.signers.len()) {
+            eject(&format!("Policy {:?} has a threshold outside 1 to {}", path, policy.signers.len()));
+        }
+        for signer in &policy.signers {
+            let given = [&signer.key, &signer.fingerprint, &signer.github].iter().filter(|i| i.is_some()).count();
+            if given != 1 {
+                eject(&format!("Each signer in policy {:?} needs exactly one of key, fingerprint or github", path));
+            }
+        }
+        policy
+    }
+
+    /// Check `envelopes`, the signatures of a single envelope or bundle
+    pub fn evaluate(&self, envelopes: &[SignIt], revocations: &[Revocation]) -> Report {
+        let now = time::now_utc();
+        let mut clauses = vec![];
+        let mut fingerprints = vec![];
+
+        for candidate in self.signers.iter().map(resolve) {
+            let mut problems = vec![];
+            let mut satisfied = None;
+
+            for (i, env) in envelopes.iter().enumerate() {
+                let fingerprint = match signed_by(env, &candidate) {
+                    Some(fingerprint) => fingerprint,
+                    None => continue,
+                };
+                if fingerprints.contains(&fingerprint) {
+                    problems.push(format!("signature {} by {} already counted for another signer", i + 1, fingerprint));
+                    continue;
+                }
+                match self.check(env, &fingerprint, revocations, &now) {
+                    Ok(()) => {
+                        satisfied = Some(format!("signature {} by {}", i + 1, fingerprint));
+                        fingerprints.push(fingerprint);
+                        break;
+                    }
+                    Err(e) => problems.push(format!("signature {} {}", i + 1, e)),
+                }
+            }
+
+            clauses.push(match (satisfied, problems.is_empty()) {
+                (Some(how), _) => Clause::new(true, format!("{}: {}", candidate.label, how)),
+                (None, false) => Clause::new(false, format!("{}: {}", candidate.label, problems.join("; "))),
+                (None, true) if candidate.fingerprint.is_some() => {
+                    Clause::new(false, format!("{}: no signature with this key embedded", candidate.label))
+                }
+                (None, true) => Clause::new(false, format!("{}: no signature", candidate.label)),
+            });
+        }
+
+        self.tally(clauses, fingerprints)
+    }
+
+    /// Check `envelopes` like `evaluate`, listing every rule for every signature of each signer
+    ///
+    /// A signer's clause passes as in `evaluate`, so the verdict is the same,
+    /// only its reasons are spelled out.
+    pub fn explain(&self, envelopes: &[SignIt], revocations: &[Revocation]) -> Report {
+        let now = time::now_utc();
+        let mut clauses = vec![];
+        let mut fingerprints = vec![];
+
+        for candidate in self.signers.iter().map(resolve) {
+            let mut details = vec![];
+            let mut satisfied = None;
+
+            for (i, env) in envelopes.iter().enumerate() {
+                let fingerprint = match signed_by(env, &candidate) {
+                    Some(fingerprint) => fingerprint,
+                    None => continue,
+                };
+                details.push(Clause::new(true, format!("signature {} was made by {}", i + 1, fingerprint)));
+                if fingerprints.contains(&fingerprint) {
+                    details.push(Clause::new(false, format!("signature {} is by a key already counted for another signer", i + 1)));
+                    continue;
+                }
+
+                let rules = self.rules(env, &fingerprint, revocations, &now);
+                let passed = rules.iter().all(|rule| rule.passed);
+                details.extend(rules.into_iter().map(|rule| Clause { text: format!("signature {} {}", i + 1, rule.text), ..rule }));
+                if passed && satisfied.is_none() {
+                    satisfied = Some(fingerprint);
+                }
+            }
+
+            if details.is_empty() {
+                let keys = if candidate.fingerprint.is_some() { "its key embedded" } else { "one of its keys" };
+                details.push(Clause::new(false, format!("no signature was made with {}", keys)));
+            }
+            let text = match &satisfied {
+                Some(fingerprint) => format!("{}: signed by {}", candidate.label, fingerprint),
+                None => format!("{}: missing, no signature passes every rule", candidate.label),
+            };
+            clauses.push(Clause { passed: satisfied.is_some(), text, details });
+            fingerprints.extend(satisfied);
+        }
+
+        self.tally(clauses, fingerprints)
+    }
+
+    /// Add the threshold to the signer clauses, giving the verdict
+    fn tally(&self, mut clauses: Vec<Clause>, fingerprints: Vec<String>) -> Report {
+        let threshold = self.threshold.unwrap_or(self.signers.len());
+        let signed = fingerprints.iter().collect::<BTreeSet<_>>().len();
+        let passed = signed >= threshold;
+        clauses.push(Clause::new(
+            passed,
+            format!("{} of {} signers signed, {} required", signed, self.signers.len(), threshold),
+        ));
+
+        Report { passed, clauses, fingerprints }
+    }
+
+    /// Every rule other than who signed
+    fn check(&self, env: &SignIt, fingerprint: &str, revocations: &[Revocation], now: &time::Tm) -> Result<(), String> {
+        match self.rules(env, fingerprint, revocations, now).into_iter().find(|rule| !rule.passed) {
+            Some(rule) => Err(rule.text),
+            None => Ok(()),
+        }
+    }
+
+    /// The outcome of each rule other than who signed, for the signature of `env`
+    fn rules(&self, env: &SignIt, fingerprint: &str, revocations: &[Revocation], now: &time::Tm) -> Vec<Clause> {
+        let mut rules = vec![];
+
+        if let Some(allowed) = &self.namespaces {
+            let expected = allowed.join(" or ");
+            rules.push(match env.signer.namespace.as_deref() {
+                None => Clause::new(false, format!("has no namespace, expected {}", expected)),
+                Some(ns) => match allowed.iter().find(|pattern| glob_match(pattern, ns)) {
+                    Some(pattern) => Clause::new(true, format!("is for namespace {:?}, allowed by {}", ns, pattern)),
+                    None => Clause::new(false, format!("is for namespace {:?}, expected {}", ns, expected)),
+                },
+            });
+        }
+
+        if let Some(days) = self.max_age_days {
+            rules.push(match env.signer.timestamp.as_deref().map(|t| (t, parse_timestamp(t))) {
+                None => Clause::new(false, "has no timestamp, so its age is unknown".into()),
+                Some((_, Err(e))) => Clause::new(false, format!("has an invalid timestamp: {}", e)),
+                Some((timestamp, Ok(signed_at))) => {
+                    let age = (*now - signed_at).num_days();
+                    if age >= days {
+                        Clause::new(false, format!("was made at {}, more than {} days ago", timestamp, days))
+                    } else {
+                        Clause::new(true, format!("was made at {}, {} days ago, within {}", timestamp, age, days))
+                    }
+                }
+            });
+        }
+
+        for (key, allowed) in &self.metadata {
+            let expected = allowed.join(" or ");
+            rules.push(match env.signer.metadata.get(key) {
+                None => Clause::new(false, format!("has no metadata {:?}, expected {}", key, expected)),
+                Some(value) => match allowed.iter().find(|pattern| glob_match(pattern, value)) {
+                    Some(pattern) => Clause::new(true, format!("has metadata {} = {:?}, allowed by {}", key, value, pattern)),
+                    None => Clause::new(false, format!("has metadata {} = {:?}, expected {}", key, value, expected)),
+                },
+            });
+        }
+
+        if !revocations.is_empty() {
+            rules.push(match revoke::check_signature(revocations, fingerprint, env.signer.timestamp.as_deref()) {
+                Ok(()) => Clause::new(true, "is not refused by any revocation".into()),
+                Err(e) => Clause::new(false, format!("is refused: {}", e)),
+            });
+        }
+        rules
+    }
+}
+
+impl Clause {
+    fn new(passed: bool, text: String) -> Self {
+        Clause { passed, text, details: vec![] }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for clause in &self.clauses {
+            writeln!(f, "  [{}] {}", if clause.passed { "pass" } else { "FAIL" }, clause.text)?;
+            for detail in &clause.details {
+                writeln!(f, "      [{}] {}", if detail.passed { "pass" } else { "FAIL" }, detail.text)?;
+            }
+        }
+        write!(f, "Policy {}", if self.passed { "passed" } else { "failed!" })
+    }
+}
+
+fn resolve(signer: &RequiredSigner) -> Candidate {
+    let (label, keys, fingerprint) = match (&signer.key, &signer.fingerprint, &signer.github) {
+        (Some(line), _, _) => {
+            let key = keys::parse_public_key(line.as_bytes())
+                .unwrap_or_else(|| eject(&format!("Policy key {:?} is not an ed25519 public key", line)));
+            (keys::fingerprint(&key), vec![key], None)
+        }
+        (None, Some(fingerprint), _) => (fingerprint.clone(), vec![], Some(fingerprint.clone())),
+        (None, None, Some(user)) => {
+            let keys = forge::github_keys(user)
+                .unwrap_or_else(|e| eject(&format!("Failed to get github keys for {}!\nError: {}", user, e)));
+            (format!("github user {}", user), keys, None)
+        }
+        (None, None, None) => unreachable!("checked when the policy was loaded"),
+    };
+    Candidate { label: signer.name.clone().unwrap_or(label), keys, fingerprint }
+}
+
+/// The fingerprint of the key of `candidate` that made the signature of `env`, if any
+fn signed_by(env: &SignIt, candidate: &Candidate) -> Option<String> {
+    let sig = decode(&env.signer.signature).ok()?;
+
+    let embedded;
+    let keys = match &candidate.fingerprint {
+        Some(fingerprint) => {
+            let key = env.signer.key.as_ref().and_then(|k| keys::parse_openssh_line(&k.public_key))?;
+            if keys::fingerprint(&key) != *fingerprint {
+                return None;
+            }
+            embedded = vec![key];
+            &embedded
+        }
+        None => &candidate.keys,
+    };
+    keys::find_key(&env.signed_bytes(), &sig, keys).map(keys::fingerprint)
+}
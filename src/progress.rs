@@ -0,0 +1,78 @@
+//! Progress of long reads, for `verify --stream`
+//!
+//! Shown on stderr only when it is a terminal, so logs and pipes stay clean.
+
+use std::io::{self, IsTerminal, Read};
+use std::time::{Duration, Instant};
+
+const INTERVAL: Duration = Duration::from_millis(250);
+
+/// A reader that reports how much of `total` bytes, if known, has been read
+pub struct Progress<R> {
+    inner: R,
+    label: &'static str,
+    read: u64,
+    total: Option<u64>,
+    shown: Option<Instant>,
+    enabled: bool,
+}
+
+impl<R: Read> Progress<R> {
+    pub fn new(inner: R, label: &'static str, total: Option<u64>) -> Self {
+        Progress { inner, label, read: 0, total, shown: None, enabled: io::stderr().is_terminal() }
+    }
+}
+
+impl<R> Progress<R> {
+    fn show(&mut self) {
+        let line = match self.total {
+            Some(total) if total > 0 => format!(
+                "{}: {} of {} ({}%)",
+                self.label,
+                size(self.read),
+                size(total),
+                self.read * 100 / total
+            ),
+            _ => format!("{}: {}", self.label, size(self.read)),
+        };
+        // Pad over the end of a longer line before it
+        eprint!("\r{:<60}", line);
+        self.shown = Some(Instant::now());
+    }
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read += read as u64;
+        if self.enabled && self.shown.map_or(true, |shown| shown.elapsed() >= INTERVAL) {
+            self.show();
+        }
+        Ok(read)
+    }
+}
+
+impl<R> Drop for Progress<R> {
+    fn drop(&mut self) {
+        // The last update shows where reading stopped, all of it or not
+        if self.shown.is_some() {
+            self.show();
+            eprintln!();
+        }
+    }
+}
+
+/// A size like `512 B` or `1.5 GiB`
+pub fn size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
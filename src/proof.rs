@@ -0,0 +1,210 @@
+//! Proofs that a key belongs to an account, for `signit prove`
+//!
+//! A proof is an envelope over a fixed statement, `I am github:<user> at
+//! <time>`, in namespace `signit-identity-proof`, with the signing key
+//! embedded so that third parties know which key it speaks for. On its own it
+//! only shows that the key holder claims the account; the binding is
+//! confirmed by the account vouching for the key in return, for github by the
+//! key being one of the user's SSH keys and the proof being the user's gist.
+//!
+//! A profile, like a Keyoxide one, goes the other way: the key signs a list
+//! of claimed accounts, a domain, a fediverse account or a forge account, and
+//! each account vouches for the key on its own, so the key can be checked
+//! against several independent anchors.
+
+use reqwest::header::ACCEPT;
+use serde_json::Value;
+use signit::keys;
+use thrussh_keys::key::PublicKey;
+
+use crate::envelope::{self, SignIt, Signer};
+use crate::keycache;
+
+/// Namespace of identity proofs, so a proof cannot pass for any other signature
+pub const NAMESPACE: &str = "signit-identity-proof";
+
+/// File name of a proof published as a gist
+pub const GIST_FILE: &str = "signit-proof.json";
+
+/// The statement that the signer is `identity`, like `github:octocat`, as of `timestamp`
+pub fn statement(identity: &str, timestamp: &str) -> String {
+    format!("I am {} at {}", identity, timestamp)
+}
+
+/// The unsigned proof that the signer is `identity`, as of now
+pub fn new(identity: &str) -> SignIt {
+    let timestamp = envelope::timestamp();
+    SignIt {
+        message: statement(identity, &timestamp),
+        signer: Signer {
+            namespace: Some(NAMESPACE.to_string()),
+            timestamp: Some(timestamp),
+            identity: Some(identity.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Check that `proof` is a well formed proof of an identity, returning it
+pub fn identity(proof: &SignIt) -> Result<&str, String> {
+    if proof.signer.namespace.as_deref() != Some(NAMESPACE) {
+        return Err(format!("Not an identity proof, it is not in the {} namespace", NAMESPACE));
+    }
+    let identity = proof.signer.identity.as_deref().ok_or("Proof names no identity")?;
+    let timestamp = proof.signer.timestamp.as_deref().ok_or("Proof has no timestamp")?;
+    if proof.message != statement(identity, timestamp) {
+        return Err(format!("Proof does not state {:?}", statement(identity, timestamp)));
+    }
+    Ok(identity)
+}
+
+/// Namespace of identity profiles, which list the accounts a key belongs to
+pub const PROFILE_NAMESPACE: &str = "signit-identity-profile";
+
+const PROFILE_HEADER: &str = "signit identity profile";
+
+/// An account that a profile claims, and that must vouch for the key in return
+#[derive(Debug, PartialEq)]
+pub enum Claim {
+    /// A domain, with a `TXT` record holding the backlink
+    Dns(String),
+
+    /// A fediverse account, such as Mastodon's, with the backlink in its bio or profile fields
+    Fediverse { host: String, user: String },
+
+    /// A forge account, which must list the key as one of the user's SSH keys
+    Forge { host: String, user: String },
+}
+
+/// Forges that publish each user's SSH keys at `https://<host>/<user>.keys`
+const FORGES: &[&str] = &["github.com", "gitlab.com", "codeberg.org"];
+
+/// What an account shows to vouch for the key with fingerprint `fingerprint`
+pub fn backlink(fingerprint: &str) -> String {
+    format!("signit:{}", fingerprint)
+}
+
+impl Claim {
+    /// Parse `dns:<domain>`, `https://<instance>/@<user>`, or a forge profile like `https://github.com/<user>`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if let Some(domain) = text.strip_prefix("dns:") {
+            if domain.is_empty() || !domain.contains('.') {
+                return Err(format!("{:?} is not a domain", domain));
+            }
+            return Ok(Claim::Dns(domain.to_lowercase()));
+        }
+        let invalid = || format!("{:?} is not a claim like dns:example.com, https://mastodon.social/@alice or https://github.com/alice", text);
+        let rest = text.strip_prefix("https://").ok_or_else(invalid)?.trim_end_matches('/');
+        let (host, path) = rest.split_at(rest.find('/').ok_or_else(invalid)?);
+        let (host, path) = (host.to_lowercase(), &path[1..]);
+        if path.is_empty() || path.contains('/') {
+            return Err(invalid());
+        }
+        match path.strip_prefix('@') {
+            Some(user) => Ok(Claim::Fediverse { host, user: user.to_string() }),
+            None if FORGES.contains(&host.as_str()) => Ok(Claim::Forge { host, user: path.to_string() }),
+            None => Err(invalid()),
+        }
+    }
+
+    /// Check that the account vouches for the key `public`
+    pub fn check(&self, public: &PublicKey) -> Result<(), String> {
+        let backlink = backlink(&keys::fingerprint(public));
+        let found = match self {
+            Claim::Dns(domain) => dns_txt(domain)?.iter().any(|txt| txt.contains(&backlink)),
+            Claim::Fediverse { host, user } => fediverse_profile(host, user)?.contains(&backlink),
+            Claim::Forge { host, user } => {
+                let listed = keycache::fetch(&format!("https://{}/{}.keys", host, user))?;
+                keys::parse_authorized_keys(&listed).iter().any(|key| key == public)
+            }
+        };
+        match (found, self) {
+            (true, _) => Ok(()),
+            (false, Claim::Forge { .. }) => Err("the key is not one of the account's SSH keys".to_string()),
+            (false, _) => Err(format!("no {} backlink was found", backlink)),
+        }
+    }
+}
+
+impl std::fmt::Display for Claim {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Claim::Dns(domain) => write!(f, "dns:{}", domain),
+            Claim::Fediverse { host, user } => write!(f, "https://{}/@{}", host, user),
+            Claim::Forge { host, user } => write!(f, "https://{}/{}", host, user),
+        }
+    }
+}
+
+/// The unsigned profile claiming `claims` for the key `public`
+pub fn profile(public: &PublicKey, claims: &[Claim]) -> SignIt {
+    let mut message = format!("{}\nkey: {}\n", PROFILE_HEADER, keys::fingerprint(public));
+    for claim in claims {
+        message.push_str(&format!("claim: {}\n", claim));
+    }
+    SignIt {
+        message,
+        signer: Signer {
+            namespace: Some(PROFILE_NAMESPACE.to_string()),
+            timestamp: Some(envelope::timestamp()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// The key fingerprint and claims of a profile, leaving its signature to the caller
+pub fn claims(profile: &SignIt) -> Result<(String, Vec<Claim>), String> {
+    if profile.signer.namespace.as_deref() != Some(PROFILE_NAMESPACE) {
+        return Err(format!("Not an identity profile, it is not in the {} namespace", PROFILE_NAMESPACE));
+    }
+    let mut lines = profile.message.lines();
+    if lines.next() != Some(PROFILE_HEADER) {
+        return Err("Not an identity profile".to_string());
+    }
+    let fingerprint = lines
+        .next()
+        .and_then(|line| line.strip_prefix("key: "))
+        .ok_or("Identity profile names no key")?;
+    let claims = lines
+        .map(|line| line.strip_prefix("claim: ").ok_or_else(|| format!("{:?} is not a claim", line)).and_then(Claim::parse))
+        .collect::<Result<_, _>>()?;
+    Ok((fingerprint.to_string(), claims))
+}
+
+/// The `TXT` records of `domain`, looked up over DNS over HTTPS
+fn dns_txt(domain: &str) -> Result<Vec<String>, String> {
+    let mut response = reqwest::Client::new()
+        .get("https://cloudflare-dns.com/dns-query")
+        .query(&[("name", domain), ("type", "TXT")])
+        .header(ACCEPT, "application/dns-json")
+        .send()
+        .map_err(|e| format!("{:?}", e))?;
+    let body: Value = response.json().map_err(|e| format!("{:?}", e))?;
+    let answers = body.get("Answer").and_then(Value::as_array).cloned().unwrap_or_default();
+    // Long records arrive as several quoted strings, which make up one value
+    Ok(answers
+        .iter()
+        .filter_map(|answer| answer.get("data")?.as_str().map(|data| data.replace("\" \"", "").trim_matches('"').to_string()))
+        .collect())
+}
+
+/// The bio and profile fields of a fediverse account, from its ActivityPub actor
+fn fediverse_profile(host: &str, user: &str) -> Result<String, String> {
+    let mut response = reqwest::Client::new()
+        .get(&format!("https://{}/users/{}", host, user))
+        .header(ACCEPT, "application/activity+json")
+        .send()
+        .map_err(|e| format!("{:?}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} answered {} for @{}", host, response.status(), user));
+    }
+    let actor: Value = response.json().map_err(|e| format!("{:?}", e))?;
+    let mut text = actor.get("summary").and_then(Value::as_str).unwrap_or("").to_string();
+    for field in actor.get("attachment").and_then(Value::as_array).into_iter().flatten() {
+        text.push('\n');
+        text.push_str(field.get("value").and_then(Value::as_str).unwrap_or(""));
+    }
+    Ok(text)
+}
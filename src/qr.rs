@@ -0,0 +1,83 @@
+//! QR codes of envelopes, for moving signatures off air-gapped machines
+
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use qrcode::{render::unicode::Dense1x2, Color, QrCode};
+
+/// Modules of quiet zone around the code, as the QR specification asks for
+const QUIET_ZONE: usize = 4;
+
+/// Pixels per module in PNG output
+const SCALE: usize = 8;
+
+/// Render as text for a terminal, two modules per character
+///
+/// Colors are inverted, so that the code reads correctly on the usual light on
+/// dark terminal.
+pub fn terminal(data: &str) -> Result<String, String> {
+    let code = encode(data)?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// Render as a black and white PNG image
+pub fn png(data: &str) -> Result<Vec<u8>, String> {
+    let code = encode(data)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * SCALE;
+
+    // One bit per pixel, set for white, each row preceded by its filter type
+    let row_bytes = (size + 7) / 8;
+    let mut raw = Vec::with_capacity((row_bytes + 1) * size);
+    for y in 0..size {
+        raw.push(0);
+        let mut row = vec![0u8; row_bytes];
+        for x in 0..size {
+            let dark = match (x / SCALE).checked_sub(QUIET_ZONE).zip((y / SCALE).checked_sub(QUIET_ZONE)) {
+                Some((mx, my)) if mx < modules && my < modules => colors[my * modules + mx] == Color::Dark,
+                _ => false,
+            };
+            if !dark {
+                row[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        raw.extend(row);
+    }
+
+    let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+    zlib.write_all(&raw).unwrap();
+    let idat = zlib.finish().unwrap();
+
+    let mut ihdr = vec![];
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[1, 0, 0, 0, 0]); // 1 bit grayscale, no interlacing
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &ihdr);
+    chunk(&mut out, b"IDAT", &idat);
+    chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+fn encode(data: &str) -> Result<QrCode, String> {
+    QrCode::new(data.as_bytes()).map_err(|_| {
+        format!("Output is too large for a QR code ({} bytes, at most 2331 fit)", data.len())
+    })
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
@@ -0,0 +1,110 @@
+//! Revocation statements, for `signit revoke` and `verify --revocations`
+//!
+//! A revocation is an envelope in the `signit-revocation` namespace, signed by
+//! the key it revokes with that key embedded. Its message is JSON naming the
+//! key, the time it is revoked from and an optional reason. Only the holder of
+//! a key can revoke it, so a revocation needs no further trust and can be
+//! published anywhere.
+//!
+//! Signatures made from the revocation time on are refused, as are signatures
+//! without a `timestamp`, since there is no telling when they were made. The
+//! timestamp is only what the signer claims, so whoever stole a key can backdate
+//! signatures past its revocation. A revocation of a compromised key therefore
+//! refuses every signature by it, whatever time it claims.
+
+use base64::decode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::envelope::{parse_timestamp, SignIt};
+use crate::keys;
+
+/// Namespace of revocations, so no other signature can pass for one
+pub const NAMESPACE: &str = "signit-revocation";
+
+/// The signed message of a revocation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Revocation {
+    pub fingerprint: String,
+
+    /// Signatures made at or after this time are refused
+    pub revoked_at: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// Every signature by the key is refused, whatever time it claims
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub compromised: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Every revocation in `raw`, which holds one or more envelopes, or a JSON array of them
+///
+/// Fails if any of them is not a valid revocation, rather than skipping it.
+pub fn load(raw: &str) -> Result<Vec<Revocation>, String> {
+    let mut envelopes = vec![];
+    for value in serde_json::Deserializer::from_str(raw).into_iter::<Value>() {
+        match value.map_err(|e| format!("Not valid JSON\nError: {:?}", e))? {
+            Value::Array(values) => envelopes.extend(values),
+            value => envelopes.push(value),
+        }
+    }
+
+    envelopes
+        .into_iter()
+        .map(|value| {
+            let env = serde_json::from_value(value).map_err(|e| format!("Not an envelope\nError: {:?}", e))?;
+            check(&env)
+        })
+        .collect()
+}
+
+/// Fail if the key with `fingerprint` was revoked by the time the signature claims it was made
+pub fn check_signature(revocations: &[Revocation], fingerprint: &str, timestamp: Option<&str>) -> Result<(), String> {
+    for revocation in revocations.iter().filter(|r| r.fingerprint == fingerprint) {
+        let reason = revocation.reason.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default();
+        if revocation.compromised {
+            return Err(format!("Key {} was revoked as compromised{}, so none of its signatures are trusted!", fingerprint, reason));
+        }
+        let signed_at = match timestamp {
+            Some(timestamp) => parse_timestamp(timestamp)?,
+            None => {
+                return Err(format!(
+                    "Key {} was revoked as of {}{}, and the signature has no timestamp!",
+                    fingerprint, revocation.revoked_at, reason
+                ))
+            }
+        };
+        if signed_at >= parse_timestamp(&revocation.revoked_at)? {
+            return Err(format!(
+                "Key {} was revoked as of {}{}, before the signature was made!",
+                fingerprint, revocation.revoked_at, reason
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `env` is a revocation signed by the key it revokes
+fn check(env: &SignIt) -> Result<Revocation, String> {
+    if env.signer.namespace.as_deref() != Some(NAMESPACE) {
+        return Err(format!("Envelope is not in the {} namespace", NAMESPACE));
+    }
+    let embedded = env.signer.key.as_ref().ok_or("Revocation has no embedded key")?;
+    let key = keys::parse_openssh_line(&embedded.public_key).ok_or("Revocation has an invalid embedded key")?;
+    let sig = decode(&env.signer.signature).map_err(|_| "Signature not proper base64!")?;
+    keys::find_key(&env.signed_bytes(), &sig, std::slice::from_ref(&key))
+        .ok_or("Revocation was not signed by the key it revokes")?;
+
+    let revocation: Revocation = serde_json::from_str(&env.message)
+        .map_err(|e| format!("Revocation message is not valid\nError: {:?}", e))?;
+    if revocation.fingerprint != keys::fingerprint(&key) {
+        return Err(format!("Revocation of {} was signed by a different key", revocation.fingerprint));
+    }
+    parse_timestamp(&revocation.revoked_at)?;
+    Ok(revocation)
+}
@@ -0,0 +1,163 @@
+//! Moving signatures from one key to another, for `signit rotate`
+//!
+//! Every signature the old key made in a file is checked, then replaced by a
+//! signature of the new key over the same payload, or joined by one with
+//! `--cross-sign`. The rotation itself is recorded in a statement naming both
+//! keys, signed by both of them, so anyone who trusted the old key can check
+//! that its holder handed over to the new one.
+
+use base64::{decode, encode};
+use serde_json::Value;
+use thrussh_keys::{key::{KeyPair, PublicKey}, signature::Signature};
+
+use crate::envelope::{self, Bundle, Detached, SignIt, Signer};
+use crate::keys;
+
+/// Namespace of rotation statements, so they cannot pass for anything else
+pub const NAMESPACE: &str = "signit-key-rotation";
+
+/// The contents of a signature file
+pub enum Signed {
+    Envelope(SignIt),
+    Bundle(Bundle),
+    Detached(Detached),
+}
+
+impl Signed {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(raw).map_err(|e| format!("{:?}", e))?;
+        let parsed = if value.get("signatures").is_some() {
+            serde_json::from_value(value).map(Signed::Bundle)
+        } else if value.get("digest").is_some() {
+            serde_json::from_value(value).map(Signed::Detached)
+        } else {
+            serde_json::from_value(value).map(Signed::Envelope)
+        };
+        parsed.map_err(|e| format!("{:?}", e))
+    }
+
+    /// The file as `sign` would write it, in canonical form unless `pretty`
+    pub fn to_string(&self, pretty: bool) -> String {
+        match (self, pretty) {
+            (Signed::Envelope(env), true) => serde_json::to_string_pretty(env),
+            (Signed::Envelope(env), false) => envelope::canonical(env),
+            (Signed::Bundle(bundle), true) => serde_json::to_string_pretty(bundle),
+            (Signed::Bundle(bundle), false) => envelope::canonical(bundle),
+            (Signed::Detached(detached), true) => serde_json::to_string_pretty(detached),
+            (Signed::Detached(detached), false) => envelope::canonical(detached),
+        }
+        .unwrap()
+    }
+}
+
+/// The message of a statement that `old` was replaced by `new`, as of `date`
+pub fn statement(old: &PublicKey, new: &PublicKey, date: &str) -> String {
+    format!(
+        "signit key rotation\nold: {} {}\nnew: {} {}\ndate: {}\n",
+        keys::fingerprint(old),
+        keys::openssh_line(old),
+        keys::fingerprint(new),
+        keys::openssh_line(new),
+        date,
+    )
+}
+
+/// Move the signatures made by `old` over to `new`, keeping them with `cross_sign`
+///
+/// Fails if `old` made none of the signatures, leaving it to the caller to
+/// report the file.
+pub fn rotate(signed: Signed, old: &PublicKey, new: &KeyPair, comment: Option<String>, cross_sign: bool) -> Result<Signed, String> {
+    match signed {
+        Signed::Envelope(env) => {
+            if !env.chain.is_empty() {
+                return Err("Envelope has a signature chain, which re-signing would break".into());
+            }
+            check_old(&env.signed_bytes(), &env.signer, old)?;
+            let signer = resign(&env, new, comment)?;
+            if cross_sign {
+                let signatures = vec![env.signer, signer];
+                Ok(Signed::Bundle(Bundle { message: env.message, signatures, hints: env.hints }))
+            } else {
+                Ok(Signed::Envelope(SignIt { signer, ..env }))
+            }
+        }
+        Signed::Bundle(bundle) => {
+            let mut signatures = vec![];
+            let mut rotated = 0;
+            for signer in bundle.signatures {
+                let env = SignIt { message: bundle.message.clone(), signer, ..Default::default() };
+                if check_old(&env.signed_bytes(), &env.signer, old).is_err() {
+                    signatures.push(env.signer);
+                    continue;
+                }
+                let replacement = resign(&env, new, comment.clone())?;
+                if cross_sign {
+                    signatures.push(env.signer);
+                }
+                signatures.push(replacement);
+                rotated += 1;
+            }
+            if rotated == 0 {
+                return Err("No signature in the bundle was made by the old key".into());
+            }
+
+            // The new key may already have signed the bundle, and ed25519 signatures are deterministic
+            let mut unique: Vec<Signer> = vec![];
+            for signer in signatures {
+                if !unique.contains(&signer) {
+                    unique.push(signer);
+                }
+            }
+            let signatures = unique;
+            Ok(Signed::Bundle(Bundle { message: bundle.message, signatures, hints: bundle.hints }))
+        }
+        Signed::Detached(mut detached) => {
+            if cross_sign {
+                return Err("Detached signatures hold a single signature, rotate them without --cross-sign".into());
+            }
+            check_old(&detached.signed_bytes(), &detached.signer, old)?;
+            check_movable(&detached.signer)?;
+            if detached.signer.key.is_some() {
+                detached.signer.key = Some(keys::embed(&new.clone_public_key(), comment));
+            }
+            detached.signer.signature = encode(&sign(new, &detached.signed_bytes())?[..]);
+            Ok(Signed::Detached(detached))
+        }
+    }
+}
+
+fn check_old(signed: &[u8], signer: &Signer, old: &PublicKey) -> Result<(), String> {
+    let sig = decode(&signer.signature).map_err(|_| "Signature not proper base64!")?;
+    keys::find_key(signed, &sig, std::slice::from_ref(old))
+        .map(|_| ())
+        .ok_or_else(|| "Not signed by the old key".to_string())
+}
+
+/// Fail if the signer holds something that vouches for the old key alone
+fn check_movable(signer: &Signer) -> Result<(), String> {
+    if signer.certificate.is_some() {
+        return Err("Signature carries a certificate for the old key, sign again with one issued to the new key".into());
+    }
+    if signer.delegation.is_some() {
+        return Err("Signature carries a delegation to the old key, sign again with one issued to the new key".into());
+    }
+    Ok(())
+}
+
+/// The signer of `env` as it would be if `new` had signed it
+fn resign(env: &SignIt, new: &KeyPair, comment: Option<String>) -> Result<Signer, String> {
+    check_movable(&env.signer)?;
+    let mut replacement = SignIt { message: env.message.clone(), signer: env.signer.clone(), ..Default::default() };
+    if replacement.signer.key.is_some() {
+        replacement.signer.key = Some(keys::embed(&new.clone_public_key(), comment));
+    }
+    replacement.signer.signature = encode(&sign(new, &replacement.signed_bytes())?[..]);
+    Ok(replacement.signer)
+}
+
+fn sign(key: &KeyPair, bytes: &[u8]) -> Result<[u8; 64], String> {
+    match key.sign_detached(bytes) {
+        Ok(Signature::Ed25519(sig)) => Ok(sig.0),
+        _ => Err("Specified or detected key was not an Ed25519 key!".into()),
+    }
+}
@@ -0,0 +1,91 @@
+//! Signatures embedded in SBOM documents, for `signit sbom`
+//!
+//! CycloneDX JSON carries its signature in a top-level `signature` property,
+//! following the JSON Signature Format (JSF): an object naming the algorithm
+//! and public key, with `value` holding the signature. The signature covers
+//! the whole document, signature object included but without `value`, in the
+//! canonical form of RFC 8785 (JCS).
+//!
+//! serde_json keeps object keys sorted by their UTF-8 bytes, which is JCS
+//! order for every key outside the surrogate range, so only documents with
+//! fractional numbers are refused rather than canonicalized.
+//!
+//! SPDX has no embedded signature convention, so SPDX documents are signed as
+//! usual with `sign --detached`.
+
+use base64::{decode_config, encode_config, URL_SAFE_NO_PAD};
+use serde_json::{json, Map, Value};
+use thrussh_keys::key::PublicKey;
+
+use crate::keys;
+
+/// The JSF name of ed25519 signatures
+const ALGORITHM: &str = "Ed25519";
+
+/// Parse a CycloneDX JSON document, refusing other SBOM formats with the reason
+pub fn parse(raw: &str) -> Result<Map<String, Value>, String> {
+    let bom = match serde_json::from_str(raw) {
+        Ok(Value::Object(bom)) => bom,
+        Ok(_) => return Err("SBOM is not a JSON object".to_string()),
+        Err(e) => return Err(format!("SBOM is not JSON\nError: {:?}", e)),
+    };
+    if bom.contains_key("spdxVersion") {
+        return Err("SPDX has no embedded signatures, sign it with sign --detached instead".to_string());
+    }
+    if bom.get("bomFormat").and_then(Value::as_str) != Some("CycloneDX") {
+        return Err("Not a CycloneDX JSON document, bomFormat is not CycloneDX".to_string());
+    }
+    Ok(bom)
+}
+
+/// The bytes to sign once the signature object without its value is in `bom`
+pub fn prepare(bom: &mut Map<String, Value>, public: &PublicKey) -> Result<Vec<u8>, String> {
+    let raw = keys::raw_ed25519(public).ok_or("Specified or detected key was not an Ed25519 key!")?;
+    let signature = json!({
+        "algorithm": ALGORITHM,
+        "keyId": keys::fingerprint(public),
+        "publicKey": { "kty": "OKP", "crv": "Ed25519", "x": encode_config(&raw[..], URL_SAFE_NO_PAD) },
+    });
+    bom.insert("signature".to_string(), signature);
+    canonical(bom)
+}
+
+/// Put `signature` into the signature object that `prepare` added
+pub fn finish(bom: &mut Map<String, Value>, signature: &[u8; 64]) {
+    if let Some(Value::Object(jsf)) = bom.get_mut("signature") {
+        jsf.insert("value".to_string(), encode_config(&signature[..], URL_SAFE_NO_PAD).into());
+    }
+}
+
+/// The bytes a signed document's signature covers, and the signature
+pub fn signature(bom: &Map<String, Value>) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut unsigned = bom.clone();
+    let jsf = match unsigned.get_mut("signature") {
+        Some(Value::Object(jsf)) => jsf,
+        Some(_) => return Err("SBOM signature is not a single JSF signature object".to_string()),
+        None => return Err("SBOM has no signature".to_string()),
+    };
+    if jsf.get("algorithm").and_then(Value::as_str) != Some(ALGORITHM) {
+        return Err(format!("SBOM signature algorithm is {}, only {} is supported", jsf.get("algorithm").unwrap_or(&Value::Null), ALGORITHM));
+    }
+    let value = jsf.remove("value").and_then(|v| v.as_str().map(String::from)).ok_or("SBOM signature has no value")?;
+    let signature = decode_config(&value, URL_SAFE_NO_PAD).map_err(|_| "SBOM signature value is not base64url")?;
+    Ok((canonical(&unsigned)?, signature))
+}
+
+/// RFC 8785 canonical JSON
+fn canonical(bom: &Map<String, Value>) -> Result<Vec<u8>, String> {
+    fn check(value: &Value) -> Result<(), String> {
+        match value {
+            Value::Number(n) if !n.is_i64() && !n.is_u64() => {
+                Err(format!("SBOM holds the fractional number {}, which cannot be canonicalized here", n))
+            }
+            Value::Array(values) => values.iter().try_for_each(check),
+            Value::Object(object) => object.values().try_for_each(check),
+            _ => Ok(()),
+        }
+    }
+    let value = Value::Object(bom.clone());
+    check(&value)?;
+    Ok(serde_json::to_vec(&value).unwrap())
+}
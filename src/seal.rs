@@ -0,0 +1,186 @@
+//! Encryption of signed envelopes to the ed25519 keys of their recipients
+//!
+//! A random file key encrypts the payload with ChaCha20-Poly1305. The file key is
+//! then wrapped once per recipient, with a key derived from an ephemeral X25519
+//! exchange against the recipient's converted ed25519 key.
+//!
+//! The inner envelope names its recipients in signed metadata, so whoever
+//! opens it cannot seal it again to someone else as if it had been sent to them.
+
+use base64::{decode, encode};
+use openssl::rand::rand_bytes;
+use openssl::sha::Sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use thrussh_keys::key::{KeyPair, PublicKey};
+use zeroize::Zeroizing;
+
+use crate::envelope::SignIt;
+use crate::keys::fingerprint;
+use signit::x25519;
+
+const DOMAIN: &[u8] = b"signit-seal-v1";
+const TAG_LEN: usize = 16;
+
+/// Signed metadata of the inner envelope, listing the fingerprints it was sealed to
+pub const RECIPIENTS: &str = "sealed-to";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sealed {
+    pub version: u32,
+    pub recipients: Vec<Recipient>,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recipient {
+    /// Fingerprint of the recipient's ed25519 key, so `open` knows which stanza to try
+    pub fingerprint: String,
+    pub ephemeral: String,
+    pub wrapped_key: String,
+}
+
+/// Encrypt `plaintext` so that any of the ed25519 `recipients` can open it
+pub fn seal(plaintext: &[u8], recipients: &[PublicKey]) -> Result<Sealed, String> {
+    let mut file_key = Zeroizing::new([0u8; 32]);
+    rand_bytes(&mut file_key[..]).unwrap();
+
+    let mut stanzas = vec![];
+    for key in recipients {
+        let ed = match key {
+            PublicKey::Ed25519(ed) => ed,
+            _ => continue,
+        };
+        let their_x = x25519::public_key(&ed.key)
+            .ok_or_else(|| format!("Recipient key {} is not usable for encryption", fingerprint(key)))?;
+
+        let (eph_secret, eph_public) = x25519::ephemeral();
+        let eph_secret = Zeroizing::new(eph_secret);
+        let shared = x25519::diffie_hellman(&eph_secret, &their_x)
+            .ok_or_else(|| format!("Recipient key {} is not usable for encryption", fingerprint(key)))?;
+        let wrap_key = wrap_key(&shared, &eph_public, &their_x);
+
+        stanzas.push(Recipient {
+            fingerprint: fingerprint(key),
+            ephemeral: encode(&eph_public),
+            wrapped_key: encode(&aead_seal(&wrap_key[..], &[0u8; 12], &file_key[..])),
+        });
+    }
+
+    if stanzas.is_empty() {
+        return Err("No ed25519 keys found for the recipient!".into());
+    }
+
+    let mut nonce = [0u8; 12];
+    rand_bytes(&mut nonce).unwrap();
+
+    Ok(Sealed {
+        version: 1,
+        recipients: stanzas,
+        nonce: encode(&nonce),
+        ciphertext: encode(&aead_seal(&file_key[..], &nonce, plaintext)),
+    })
+}
+
+/// The `sealed-to` metadata for the ed25519 keys among `recipients`
+pub fn recipients(recipients: &[PublicKey]) -> String {
+    recipients
+        .iter()
+        .filter(|key| matches!(key, PublicKey::Ed25519(_)))
+        .map(fingerprint)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Check that the signed inner envelope was sealed to the key opening it
+pub fn check_recipient(env: &SignIt, secret: &KeyPair) -> Result<(), String> {
+    let ours = fingerprint(&secret.clone_public_key());
+    match env.signer.metadata.get(RECIPIENTS) {
+        Some(list) if list.split(',').any(|fp| fp == ours) => Ok(()),
+        Some(_) => Err("Message was signed for other recipients, and sealed again to this key!".into()),
+        None => Err("Message does not say who it was sealed to!".into()),
+    }
+}
+
+/// Decrypt a sealed payload with the recipient's ed25519 private key
+pub fn open(sealed: &Sealed, secret: &KeyPair) -> Result<Vec<u8>, String> {
+    let seed = match secret {
+        KeyPair::Ed25519(sk) => &sk.key[..32],
+        _ => return Err("Specified or detected key was not an Ed25519 key!".into()),
+    };
+    if sealed.version != 1 {
+        return Err(format!("Unsupported sealed envelope version {}", sealed.version));
+    }
+
+    let our_secret = Zeroizing::new(x25519::secret_key(seed));
+    let our_public = secret.clone_public_key();
+    let our_x = match &our_public {
+        PublicKey::Ed25519(ed) => x25519::public_key(&ed.key),
+        _ => None,
+    }
+    .ok_or("Private key is not usable for decryption")?;
+    let our_fingerprint = fingerprint(&our_public);
+
+    let stanza = sealed
+        .recipients
+        .iter()
+        .find(|r| r.fingerprint == our_fingerprint)
+        .ok_or("Message was not sealed for this key!")?;
+
+    let eph_public = decode_array(&stanza.ephemeral)?;
+    let shared = x25519::diffie_hellman(&our_secret, &eph_public)
+        .ok_or("Sealed envelope has an invalid ephemeral key")?;
+    let wrap_key = wrap_key(&shared, &eph_public, &our_x);
+
+    let wrapped = decode(&stanza.wrapped_key).map_err(|_| "Wrapped key not proper base64!")?;
+    let file_key = Zeroizing::new(
+        aead_open(&wrap_key[..], &[0u8; 12], &wrapped).ok_or("Failed to unwrap file key!")?,
+    );
+    if file_key.len() != 32 {
+        return Err("Failed to unwrap file key!".into());
+    }
+
+    let nonce = decode(&sealed.nonce).map_err(|_| "Nonce not proper base64!")?;
+    if nonce.len() != 12 {
+        return Err("Nonce has the wrong length!".into());
+    }
+    let ciphertext = decode(&sealed.ciphertext).map_err(|_| "Ciphertext not proper base64!")?;
+
+    aead_open(&file_key, &nonce, &ciphertext).ok_or_else(|| "Decryption failed!".into())
+}
+
+fn wrap_key(shared: &[u8; 32], eph_public: &[u8; 32], recipient: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update(shared);
+    hasher.update(eph_public);
+    hasher.update(recipient);
+    Zeroizing::new(hasher.finish())
+}
+
+fn aead_seal(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut tag = [0u8; TAG_LEN];
+    let mut out = encrypt_aead(Cipher::chacha20_poly1305(), key, Some(nonce), DOMAIN, plaintext, &mut tag)
+        .expect("ChaCha20-Poly1305 encryption failed");
+    out.extend_from_slice(&tag);
+    out
+}
+
+fn aead_open(key: &[u8], nonce: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_LEN {
+        return None;
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    decrypt_aead(Cipher::chacha20_poly1305(), key, Some(nonce), DOMAIN, ciphertext, tag).ok()
+}
+
+fn decode_array(b64: &str) -> Result<[u8; 32], String> {
+    let raw = decode(b64).map_err(|_| "Ephemeral key not proper base64!")?;
+    if raw.len() != 32 {
+        return Err("Ephemeral key has the wrong length!".into());
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&raw);
+    Ok(out)
+}
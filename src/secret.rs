@@ -0,0 +1,110 @@
+use std::ops::Deref;
+use thrussh_keys::key::KeyPair;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::keyfile;
+
+/// A private key that is wiped from memory when dropped
+///
+/// The key lives in its own heap allocation which, on unix, is locked into RAM
+/// so it cannot be swapped to disk. The file contents read while loading are
+/// wiped as well. Intermediate buffers allocated inside thrussh while decoding
+/// are outside of our control.
+pub struct SecretKey {
+    inner: Box<KeyPair>,
+}
+
+impl SecretKey {
+    /// Decode a private key in any encoding `keyfile` understands
+    pub fn decode(contents: &[u8], passphrase: Option<&[u8]>) -> Result<Self, thrussh_keys::Error> {
+        Ok(Self::new(keyfile::decode_key(contents, passphrase)?))
+    }
+
+    pub fn new(key: KeyPair) -> Self {
+        let inner = Box::new(key);
+        lock(&inner);
+        SecretKey { inner }
+    }
+}
+
+impl Deref for SecretKey {
+    type Target = KeyPair;
+
+    fn deref(&self) -> &KeyPair {
+        &self.inner
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        // RSA keys are held by OpenSSL, which clears them when freed
+        if let KeyPair::Ed25519(ref mut key) = *self.inner {
+            key.key.zeroize();
+        }
+        unlock(&self.inner);
+    }
+}
+
+/// Read a passphrase from the terminal without echoing it
+#[cfg(unix)]
+pub fn prompt_passphrase(prompt: &str) -> std::io::Result<Zeroizing<String>> {
+    use std::fs::OpenOptions;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+    let mut term = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut term) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let saved = term;
+    term.c_lflag &= !libc::ECHO;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &term) };
+
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+    let mut line = Zeroizing::new(String::new());
+    let read = BufReader::new(&tty).read_line(&mut line);
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    writeln!(tty)?;
+    read?;
+
+    let len = line.trim_end_matches(|c| c == '\r' || c == '\n').len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Read a passphrase from stdin, as there is no portable way to hide the input
+#[cfg(not(unix))]
+pub fn prompt_passphrase(prompt: &str) -> std::io::Result<Zeroizing<String>> {
+    eprint!("{}", prompt);
+    let mut line = Zeroizing::new(String::new());
+    std::io::stdin().read_line(&mut line)?;
+
+    let len = line.trim_end_matches(|c| c == '\r' || c == '\n').len();
+    line.truncate(len);
+    Ok(line)
+}
+
+#[cfg(unix)]
+fn lock(key: &KeyPair) {
+    // Best effort: locking fails without privileges or when over RLIMIT_MEMLOCK
+    unsafe {
+        libc::mlock(key as *const KeyPair as *const libc::c_void, std::mem::size_of::<KeyPair>());
+    }
+}
+
+#[cfg(unix)]
+fn unlock(key: &KeyPair) {
+    unsafe {
+        libc::munlock(key as *const KeyPair as *const libc::c_void, std::mem::size_of::<KeyPair>());
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_key: &KeyPair) {}
+
+#[cfg(not(unix))]
+fn unlock(_key: &KeyPair) {}
@@ -0,0 +1,134 @@
+//! Human readable description of envelopes, for `signit show`
+//!
+//! Nothing here verifies anything. It only reports what a file claims to be, so
+//! that a failing verification can be debugged.
+
+use std::fmt::Write;
+
+use base64::decode;
+use serde_json::Value;
+use thrussh_keys::parse_public_key_base64;
+
+use crate::envelope::{self, SignIt};
+use crate::keys::fingerprint;
+use crate::seal::Sealed;
+
+/// Describe a signed envelope, bundle or sealed message
+pub fn describe(raw: &str) -> Result<String, String> {
+    let value: Value = serde_json::from_str(raw)
+        .map_err(|e| format!("Not a JSON document\nError: {:?}", e))?;
+
+    let mut out = String::new();
+    if value.get("recipients").is_some() {
+        let sealed: Sealed = serde_json::from_value(value)
+            .map_err(|e| format!("Failed to parse sealed message\nError: {:?}", e))?;
+        describe_sealed(&mut out, &sealed);
+        return Ok(out);
+    }
+
+    let envelopes = envelope::parse_envelopes(raw)
+        .map_err(|e| format!("Failed to parse envelope\nError: {:?}", e))?;
+
+    if value.get("signatures").is_some() {
+        writeln!(out, "Format:     signit bundle").unwrap();
+        writeln!(out, "Signatures: {}", envelopes.len()).unwrap();
+    } else {
+        writeln!(out, "Format:     signit envelope").unwrap();
+    }
+    if let Some(first) = envelopes.first() {
+        describe_message(&mut out, &first.message);
+        for (key, value) in &first.hints {
+            writeln!(out, "Hint:       {} = {} (not covered by any signature)", key.escape_debug(), value.escape_debug()).unwrap();
+        }
+    }
+
+    for (i, env) in envelopes.iter().enumerate() {
+        writeln!(out).unwrap();
+        if envelopes.len() > 1 {
+            writeln!(out, "Signature {}:", i + 1).unwrap();
+        }
+        describe_signer(&mut out, env);
+    }
+
+    Ok(out.trim_end().to_string())
+}
+
+fn describe_message(out: &mut String, message: &str) {
+    let first_line = message.lines().next().unwrap_or("");
+    let preview: String = first_line.chars().take(60).collect();
+    let ellipsis = if preview.len() < message.trim_end().len() { "..." } else { "" };
+
+    writeln!(out, "Message:    {} bytes, {} lines", message.len(), message.lines().count()).unwrap();
+    writeln!(out, "Preview:    {:?}{}", preview, ellipsis).unwrap();
+}
+
+fn describe_signer(out: &mut String, env: &SignIt) {
+    let signer = &env.signer;
+    let legacy = env.signed_bytes() == env.message.as_bytes();
+
+    writeln!(out, "Payload:    {}", if legacy { "raw message (legacy)" } else { "SIGNIT-PAYLOAD-V1" }).unwrap();
+    writeln!(out, "Algorithm:  {}", signature_algorithm(&signer.signature)).unwrap();
+    writeln!(out, "Namespace:  {}", signer.namespace.as_deref().unwrap_or("(none)")).unwrap();
+    writeln!(out, "Nonce:      {}", signer.nonce.as_deref().unwrap_or("(none)")).unwrap();
+    writeln!(out, "Signed at:  {}", signer.timestamp.as_deref().unwrap_or("(none)")).unwrap();
+    writeln!(out, "Identity:   {}", signer.identity.as_deref().unwrap_or("(none)")).unwrap();
+    if signer.metadata.is_empty() {
+        writeln!(out, "Metadata:   (none)").unwrap();
+    }
+    for (key, value) in &signer.metadata {
+        writeln!(out, "Metadata:   {} = {}", key.escape_debug(), value.escape_debug()).unwrap();
+    }
+    writeln!(out, "Github:     {} (not covered by the signature)", signer.github_user.as_deref().unwrap_or("(none)")).unwrap();
+
+    match &signer.key {
+        Some(key) => {
+            writeln!(out, "Embedded:   {}", key.fingerprint).unwrap();
+            if let Some(comment) = &key.comment {
+                writeln!(out, "Comment:    {}", comment).unwrap();
+            }
+        }
+        None => writeln!(out, "Embedded:   (no key)").unwrap(),
+    }
+
+    if signer.countersign {
+        let inner = envelope::parse_envelopes(&env.message)
+            .map(|inner| format!("{} signature(s)", inner.len()))
+            .unwrap_or_else(|_| "unparseable".to_string());
+        writeln!(out, "Countersig: yes, over an envelope with {}", inner).unwrap();
+    }
+
+    if !env.chain.is_empty() {
+        writeln!(out, "Chain:      {} link(s)", env.chain.len()).unwrap();
+        for (i, link) in env.chain.iter().enumerate() {
+            let key = parse_public_key_base64(&link.public_key)
+                .map(|k| fingerprint(&k))
+                .unwrap_or_else(|_| "invalid key".to_string());
+            match &link.github_user {
+                Some(user) => writeln!(out, "  {}. {} (github: {})", i + 1, key, user).unwrap(),
+                None => writeln!(out, "  {}. {}", i + 1, key).unwrap(),
+            }
+        }
+    }
+}
+
+fn describe_sealed(out: &mut String, sealed: &Sealed) {
+    let ciphertext = decode(&sealed.ciphertext).map(|c| c.len()).unwrap_or(0);
+
+    writeln!(out, "Format:     signit sealed message").unwrap();
+    writeln!(out, "Version:    {}", sealed.version).unwrap();
+    writeln!(out, "Algorithm:  X25519, ChaCha20-Poly1305").unwrap();
+    writeln!(out, "Ciphertext: {} bytes", ciphertext).unwrap();
+    writeln!(out, "Recipients: {}", sealed.recipients.len()).unwrap();
+    for recipient in &sealed.recipients {
+        writeln!(out, "  {}", recipient.fingerprint).unwrap();
+    }
+    out.truncate(out.trim_end().len());
+}
+
+fn signature_algorithm(signature: &str) -> String {
+    match decode(signature) {
+        Ok(raw) if raw.len() == 64 => "ed25519".to_string(),
+        Ok(raw) => format!("unknown ({} byte signature)", raw.len()),
+        Err(_) => "unknown (signature not proper base64)".to_string(),
+    }
+}
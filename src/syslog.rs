@@ -0,0 +1,97 @@
+//! Audit records sent to the system log, for `--log-to`
+//!
+//! `syslog` writes an RFC 3164 line to `/dev/log`, with the record as JSON for
+//! rsyslog's `mmjsonparse` or syslog-ng's `json-parser`. `journald` uses the
+//! native journal protocol, so every field of the record is a separate journal
+//! field, such as `SIGNIT_KEY_FINGERPRINT`, that `journalctl` can filter on.
+//! Both go to the `authpriv` facility, at `info` for successes and `warning`
+//! for anything else.
+
+use serde_json::Value;
+
+use crate::audit::Record;
+
+/// Values accepted by `--log-to`
+pub const TARGETS: &[&str] = &["syslog", "journald"];
+
+const SYSLOG_SOCKET: &str = "/dev/log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+const FACILITY_AUTHPRIV: u8 = 10;
+const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_INFO: u8 = 6;
+
+pub fn send(target: &str, record: &Record) -> Result<(), String> {
+    let severity = match record.result {
+        "signed" | "verified" => SEVERITY_INFO,
+        _ => SEVERITY_WARNING,
+    };
+    let (socket, datagram) = match target {
+        "syslog" => (SYSLOG_SOCKET, syslog_line(record, severity)),
+        _ => (JOURNALD_SOCKET, journal_fields(record, severity)),
+    };
+    sendto(socket, &datagram)
+}
+
+fn syslog_line(record: &Record, severity: u8) -> Vec<u8> {
+    // RFC 3164 timestamps are local time, without the year
+    let timestamp = time::strftime("%b %e %H:%M:%S", &time::now()).unwrap();
+    format!(
+        "<{}>{} signit[{}]: {}",
+        FACILITY_AUTHPRIV * 8 + severity,
+        timestamp,
+        std::process::id(),
+        serde_json::to_string(record).unwrap(),
+    )
+    .into_bytes()
+}
+
+/// The journal's native framing, which is binary safe: name, newline, little endian length, value, newline
+fn journal_fields(record: &Record, severity: u8) -> Vec<u8> {
+    let message = format!(
+        "signit {} {}{}",
+        record.operation,
+        record.result,
+        record.key_fingerprint.as_ref().map(|fp| format!(" by {}", fp)).unwrap_or_default(),
+    );
+    let mut fields = vec![
+        ("MESSAGE".to_string(), message),
+        ("PRIORITY".to_string(), severity.to_string()),
+        ("SYSLOG_FACILITY".to_string(), FACILITY_AUTHPRIV.to_string()),
+        ("SYSLOG_IDENTIFIER".to_string(), "signit".to_string()),
+    ];
+    if let Value::Object(object) = serde_json::to_value(record).unwrap() {
+        for (name, value) in object {
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            fields.push((format!("SIGNIT_{}", name.to_uppercase()), value));
+        }
+    }
+
+    let mut datagram = vec![];
+    for (name, value) in fields {
+        datagram.extend_from_slice(name.as_bytes());
+        datagram.push(b'\n');
+        datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    }
+    datagram
+}
+
+#[cfg(unix)]
+fn sendto(socket: &str, datagram: &[u8]) -> Result<(), String> {
+    let unbound = std::os::unix::net::UnixDatagram::unbound()
+        .map_err(|e| format!("Failed to open a socket for {}\nError: {:?}", socket, e))?;
+    unbound
+        .send_to(datagram, socket)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to log to {}\nError: {:?}", socket, e))
+}
+
+#[cfg(not(unix))]
+fn sendto(socket: &str, _datagram: &[u8]) -> Result<(), String> {
+    Err(format!("Logging to {} is only available on unix", socket))
+}
@@ -0,0 +1,102 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::eject;
+
+/// Restricts which namespaces each key may sign
+///
+/// ```json
+/// {
+///   "keys": { "SHA256:bg+AEuD0...": ["ci/*"] },
+///   "default": ["release", "docs/*"]
+/// }
+/// ```
+///
+/// Keys not listed in `keys` fall back to `default`. If there is no `default`,
+/// unlisted keys are unrestricted.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UsagePolicy {
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+
+    default: Option<Vec<String>>,
+}
+
+impl UsagePolicy {
+    pub fn load(path: &Path) -> Self {
+        let raw = read_to_string(path)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to read usage policy {:?}\nError: {:?}", path, e));
+            });
+        serde_json::from_str(&raw)
+            .unwrap_or_else(|e| {
+                eject(&format!("Failed to parse usage policy {:?}\nError: {:?}", path, e));
+            })
+    }
+
+    /// Returns an explanation if `fingerprint` may not sign `namespace`
+    pub fn check(&self, fingerprint: &str, namespace: Option<&str>) -> Result<(), String> {
+        let allowed = match self.keys.get(fingerprint).or(self.default.as_ref()) {
+            Some(allowed) => allowed,
+            None => return Ok(()),
+        };
+
+        let namespace = match namespace {
+            Some(ns) => ns,
+            None => {
+                return Err(format!(
+                    "Key {} may only sign within a namespace ({}), but none was given",
+                    fingerprint,
+                    allowed.join(", ")
+                ));
+            }
+        };
+
+        if allowed.iter().any(|pattern| glob_match(pattern, namespace)) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Key {} is not permitted to sign namespace {:?} (allowed: {})",
+                fingerprint,
+                namespace,
+                allowed.join(", ")
+            ))
+        }
+    }
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+/// and `?` matches exactly one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp + 1;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,307 @@
+//! Structural checks of envelopes, for `signit validate`
+//!
+//! Envelopes are checked as plain JSON rather than through serde, so that every
+//! problem can be reported with its location instead of stopping at the first.
+
+use base64::decode;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::envelope::{parse_identity, parse_timestamp};
+use crate::keys::{fingerprint, parse_openssh_line};
+
+#[derive(Serialize)]
+pub struct Report {
+    pub valid: bool,
+
+    /// `envelope`, `bundle`, `sealed`, or `unknown`
+    pub kind: &'static str,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize)]
+pub struct Diagnostic {
+    /// `error` for problems that stop verification, `warning` for the rest
+    pub severity: &'static str,
+
+    /// JSON pointer to the offending value
+    pub path: String,
+    pub message: String,
+}
+
+const ENVELOPE_FIELDS: &[&str] =
+    &["message", "namespace", "nonce", "timestamp", "identity", "key", "metadata", "countersign", "signature", "github_user", "chain", "hints"];
+const SIGNER_FIELDS: &[&str] =
+    &["namespace", "nonce", "timestamp", "identity", "key", "metadata", "countersign", "signature", "github_user"];
+const KEY_FIELDS: &[&str] = &["public_key", "comment", "fingerprint"];
+const LINK_FIELDS: &[&str] = &["public_key", "signature", "github_user"];
+const BUNDLE_FIELDS: &[&str] = &["message", "signatures", "hints"];
+const SEALED_FIELDS: &[&str] = &["version", "recipients", "nonce", "ciphertext"];
+const RECIPIENT_FIELDS: &[&str] = &["fingerprint", "ephemeral", "wrapped_key"];
+
+/// Check an envelope, bundle or sealed message without needing any keys
+pub fn validate(raw: &str) -> Report {
+    let mut checker = Checker { diagnostics: vec![] };
+
+    let kind = match serde_json::from_str::<Value>(raw) {
+        Err(e) => {
+            checker.error("", format!("Not valid JSON: {}", e));
+            "unknown"
+        }
+        Ok(Value::Object(obj)) => {
+            if obj.contains_key("recipients") {
+                checker.sealed(&obj);
+                "sealed"
+            } else if obj.contains_key("signatures") {
+                checker.bundle(&obj);
+                "bundle"
+            } else {
+                checker.envelope("", &obj);
+                "envelope"
+            }
+        }
+        Ok(_) => {
+            checker.error("", "Expected a JSON object".into());
+            "unknown"
+        }
+    };
+
+    Report {
+        valid: !checker.diagnostics.iter().any(|d| d.severity == "error"),
+        kind,
+        diagnostics: checker.diagnostics,
+    }
+}
+
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn error(&mut self, path: &str, message: String) {
+        self.diagnostics.push(Diagnostic { severity: "error", path: path.to_string(), message });
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.diagnostics.push(Diagnostic { severity: "warning", path: path.to_string(), message });
+    }
+
+    fn envelope(&mut self, path: &str, obj: &Map<String, Value>) {
+        self.fields(path, obj, ENVELOPE_FIELDS);
+        if let Some(message) = self.string(path, obj, "message", true) {
+            if obj.get("countersign") == Some(&Value::Bool(true)) {
+                self.countersigned(path, message);
+            }
+        }
+        self.signer(path, obj);
+        self.strings(path, obj, "hints");
+
+        match obj.get("chain") {
+            None => (),
+            Some(Value::Array(links)) => {
+                for (i, link) in links.iter().enumerate() {
+                    let link_path = format!("{}/chain/{}", path, i);
+                    match link {
+                        Value::Object(link) => self.link(&link_path, link),
+                        _ => self.error(&link_path, "Expected an object".into()),
+                    }
+                }
+            }
+            Some(_) => self.error(&format!("{}/chain", path), "Expected an array".into()),
+        }
+    }
+
+    fn bundle(&mut self, obj: &Map<String, Value>) {
+        self.fields("", obj, BUNDLE_FIELDS);
+        self.string("", obj, "message", true);
+        self.strings("", obj, "hints");
+
+        match obj.get("signatures") {
+            Some(Value::Array(signers)) => {
+                if signers.is_empty() {
+                    self.warning("/signatures", "Bundle holds no signatures".into());
+                }
+                for (i, signer) in signers.iter().enumerate() {
+                    let signer_path = format!("/signatures/{}", i);
+                    match signer {
+                        Value::Object(signer) => {
+                            self.fields(&signer_path, signer, SIGNER_FIELDS);
+                            self.signer(&signer_path, signer);
+                        }
+                        _ => self.error(&signer_path, "Expected an object".into()),
+                    }
+                }
+            }
+            _ => self.error("/signatures", "Expected an array".into()),
+        }
+    }
+
+    fn signer(&mut self, path: &str, obj: &Map<String, Value>) {
+        self.string(path, obj, "namespace", false);
+        self.string(path, obj, "nonce", false);
+        if let Some(timestamp) = self.string(path, obj, "timestamp", false) {
+            if let Err(e) = parse_timestamp(timestamp) {
+                self.error(&format!("{}/timestamp", path), e);
+            }
+        }
+        if let Some(identity) = self.string(path, obj, "identity", false) {
+            if let Err(e) = parse_identity(identity) {
+                self.error(&format!("{}/identity", path), e);
+            }
+        }
+        self.string(path, obj, "github_user", false);
+        if let Some(signature) = self.string(path, obj, "signature", true) {
+            self.base64(&format!("{}/signature", path), signature, Some(64));
+        }
+
+        match obj.get("countersign") {
+            None | Some(Value::Bool(_)) => (),
+            Some(_) => self.error(&format!("{}/countersign", path), "Expected a boolean".into()),
+        }
+
+        match obj.get("key") {
+            None => (),
+            Some(Value::Object(key)) => self.embedded_key(&format!("{}/key", path), key),
+            Some(_) => self.error(&format!("{}/key", path), "Expected an object".into()),
+        }
+
+        self.strings(path, obj, "metadata");
+    }
+
+    fn embedded_key(&mut self, path: &str, obj: &Map<String, Value>) {
+        self.fields(path, obj, KEY_FIELDS);
+        self.string(path, obj, "comment", false);
+        let claimed = self.string(path, obj, "fingerprint", true);
+
+        if let Some(line) = self.string(path, obj, "public_key", true) {
+            match parse_openssh_line(line) {
+                None => self.error(&format!("{}/public_key", path), "Not a valid OpenSSH public key".into()),
+                Some(key) => {
+                    let actual = fingerprint(&key);
+                    if claimed.map_or(false, |claimed| claimed != actual) {
+                        self.error(
+                            &format!("{}/fingerprint", path),
+                            format!("Does not match the embedded key, which is {}", actual),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn link(&mut self, path: &str, obj: &Map<String, Value>) {
+        self.fields(path, obj, LINK_FIELDS);
+        self.string(path, obj, "github_user", false);
+        if let Some(key) = self.string(path, obj, "public_key", true) {
+            if parse_openssh_line(key).is_none() {
+                self.error(&format!("{}/public_key", path), "Not a valid OpenSSH public key".into());
+            }
+        }
+        if let Some(signature) = self.string(path, obj, "signature", true) {
+            self.base64(&format!("{}/signature", path), signature, Some(64));
+        }
+    }
+
+    fn countersigned(&mut self, path: &str, message: &str) {
+        let inner_path = format!("{}/message", path);
+        match serde_json::from_str::<Value>(message) {
+            Ok(Value::Object(_)) => {
+                // Report problems in the countersigned envelope below its message
+                for mut diagnostic in validate(message).diagnostics {
+                    diagnostic.path = format!("{}{}", inner_path, diagnostic.path);
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+            _ => self.error(&inner_path, "Countersigned message is not an envelope".into()),
+        }
+    }
+
+    fn sealed(&mut self, obj: &Map<String, Value>) {
+        self.fields("", obj, SEALED_FIELDS);
+
+        match obj.get("version") {
+            Some(Value::Number(n)) if n.as_u64() == Some(1) => (),
+            Some(Value::Number(n)) => self.error("/version", format!("Unsupported version {}", n)),
+            _ => self.error("/version", "Expected a number".into()),
+        }
+        if let Some(nonce) = self.string("", obj, "nonce", true) {
+            self.base64("/nonce", nonce, Some(12));
+        }
+        if let Some(ciphertext) = self.string("", obj, "ciphertext", true) {
+            self.base64("/ciphertext", ciphertext, None);
+        }
+
+        match obj.get("recipients") {
+            Some(Value::Array(recipients)) => {
+                if recipients.is_empty() {
+                    self.error("/recipients", "Sealed for no recipients".into());
+                }
+                for (i, recipient) in recipients.iter().enumerate() {
+                    let path = format!("/recipients/{}", i);
+                    let recipient = match recipient {
+                        Value::Object(recipient) => recipient,
+                        _ => {
+                            self.error(&path, "Expected an object".into());
+                            continue;
+                        }
+                    };
+                    self.fields(&path, recipient, RECIPIENT_FIELDS);
+                    self.string(&path, recipient, "fingerprint", true);
+                    if let Some(ephemeral) = self.string(&path, recipient, "ephemeral", true) {
+                        self.base64(&format!("{}/ephemeral", path), ephemeral, Some(32));
+                    }
+                    if let Some(wrapped) = self.string(&path, recipient, "wrapped_key", true) {
+                        self.base64(&format!("{}/wrapped_key", path), wrapped, Some(48));
+                    }
+                }
+            }
+            _ => self.error("/recipients", "Expected an array".into()),
+        }
+    }
+
+    fn fields(&mut self, path: &str, obj: &Map<String, Value>, known: &[&str]) {
+        for field in obj.keys().filter(|k| !known.contains(&k.as_str())) {
+            self.warning(&format!("{}/{}", path, field), "Unknown field, it is ignored and not signed".into());
+        }
+    }
+
+    fn string<'a>(&mut self, path: &str, obj: &'a Map<String, Value>, field: &str, required: bool) -> Option<&'a str> {
+        let path = format!("{}/{}", path, field);
+        match obj.get(field) {
+            Some(Value::String(s)) => Some(s),
+            None | Some(Value::Null) if !required => None,
+            None => {
+                self.error(&path, "Missing required field".into());
+                None
+            }
+            Some(_) => {
+                self.error(&path, "Expected a string".into());
+                None
+            }
+        }
+    }
+
+    /// Check that `field`, if present, is an object of strings
+    fn strings(&mut self, path: &str, obj: &Map<String, Value>, field: &str) {
+        let path = format!("{}/{}", path, field);
+        match obj.get(field) {
+            None => (),
+            Some(Value::Object(strings)) => {
+                for key in strings.keys() {
+                    self.string(&path, strings, key, true);
+                }
+            }
+            Some(_) => self.error(&path, "Expected an object".into()),
+        }
+    }
+
+    fn base64(&mut self, path: &str, value: &str, len: Option<usize>) {
+        match (decode(value), len) {
+            (Err(_), _) => self.error(path, "Not proper base64".into()),
+            (Ok(raw), Some(len)) if raw.len() != len => {
+                self.error(path, format!("Expected {} bytes, found {}", len, raw.len()))
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Warnings about signatures that verified, for `verify`
+//!
+//! A warning does not fail verification unless `--deny-warnings` is given.
+//! Each has a fixed `kind` for scripts to match on, and a message for people.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::Duration;
+
+use crate::envelope::parse_timestamp;
+
+#[derive(Debug, Serialize)]
+pub struct Warning {
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// What `verify --json` prints in place of its status lines
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub verified: bool,
+    pub signers: &'a [String],
+
+    /// The signed metadata of each signer that has any, by fingerprint
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: &'a BTreeMap<String, BTreeMap<String, String>>,
+
+    /// The hints of the envelope, which no signature covers
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub hints: &'a BTreeMap<String, String>,
+    pub failures: &'a [String],
+    pub warnings: &'a [Warning],
+}
+
+impl Warning {
+    /// Keys were fetched from `source`, so whoever serves it decides who may sign
+    pub fn network_keys(source: &str) -> Self {
+        Warning {
+            kind: "network-keys",
+            message: format!("Keys were fetched from {} and not pinned, so it decides who may sign", source),
+        }
+    }
+
+    /// The signature claims `user`, which was not checked against their keys
+    pub fn unchecked_github_user(fingerprint: &str, user: &str) -> Self {
+        Warning {
+            kind: "unchecked-github-user",
+            message: format!("Signature by {} claims github user {}, which was not checked, verify with -g to check it", fingerprint, user),
+        }
+    }
+
+    /// A warning if the signature with `timestamp` is older than `max_age`, give or take `skew`, or has no timestamp
+    pub fn age(fingerprint: &str, timestamp: Option<&str>, max_age: Duration, skew: Duration) -> Option<Self> {
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => {
+                return Some(Warning {
+                    kind: "no-timestamp",
+                    message: format!("Signature by {} has no timestamp, so its age is unknown", fingerprint),
+                })
+            }
+        };
+        let age = parse_timestamp(timestamp).map(|signed_at| time::now_utc() - signed_at);
+        match age {
+            Ok(age) if age - skew < max_age => None,
+            Ok(age) => Some(Warning {
+                kind: "old-signature",
+                message: format!("Signature by {} was made at {}, {} ago", fingerprint, timestamp, describe(age)),
+            }),
+            Err(e) => Some(Warning { kind: "no-timestamp", message: format!("Signature by {}: {}", fingerprint, e) }),
+        }
+    }
+
+    /// A warning if the signature claims to be made more than `skew` after now, which clocks do not explain
+    pub fn future(fingerprint: &str, timestamp: Option<&str>, skew: Duration) -> Option<Self> {
+        let signed_at = parse_timestamp(timestamp?).ok()?;
+        let ahead = signed_at - time::now_utc();
+        if ahead <= skew {
+            return None;
+        }
+        Some(Warning {
+            kind: "future-timestamp",
+            message: format!(
+                "Signature by {} claims to be made at {}, {} from now, check the signer's clock",
+                fingerprint,
+                timestamp.unwrap(),
+                describe(ahead)
+            ),
+        })
+    }
+}
+
+/// An age like `3 days`, `5 hours` or `40 seconds`, in its largest whole unit
+fn describe(age: Duration) -> String {
+    let (count, unit) = if age.num_days() != 0 {
+        (age.num_days(), "day")
+    } else if age.num_hours() != 0 {
+        (age.num_hours(), "hour")
+    } else if age.num_minutes() != 0 {
+        (age.num_minutes(), "minute")
+    } else {
+        (age.num_seconds(), "second")
+    };
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
@@ -0,0 +1,169 @@
+//! Finding files to sign for `signit watch`
+//!
+//! The watched directory is polled rather than subscribed to, which works the
+//! same on every platform. A file is due for signing when its signature is
+//! missing or older than the file, so nothing is re-signed after a restart and
+//! a file that was signed while still being written is signed again once the
+//! writes stop.
+//!
+//! Paths matching a `.signitignore` file at the top of the watched directory,
+//! or an `--exclude` pattern, are never signed. Patterns follow gitignore:
+//! `*` and `?` stay within a path segment, `**` spans any number of them, a
+//! leading `/` or a `/` inside a pattern anchors it to the top, a trailing
+//! `/` only matches directories, and `!` re-includes what an earlier pattern
+//! excluded. The last matching pattern wins, and `--exclude` patterns come
+//! after the file's. An excluded directory is not descended into.
+
+use std::fs::{self, metadata};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::usage::glob_match;
+
+/// Appended to the file name to name its signature
+pub const SUFFIX: &str = ".signit.json";
+
+/// Patterns of paths not to sign, read from the top of the watched directory
+pub const IGNORE_FILE: &str = ".signitignore";
+
+/// A file that needs a new signature
+pub struct Pending {
+    pub file: PathBuf,
+    pub signature: PathBuf,
+
+    /// The path relative to the watched directory, for messages
+    pub name: PathBuf,
+}
+
+/// Every file below `dir`, except those ignored, whose signature in `out` is missing or out of date
+///
+/// The ignore file is read again on every call, so edits to it apply to the next scan.
+pub fn pending(dir: &Path, out: &Path, excludes: &[String]) -> io::Result<Vec<Pending>> {
+    let ignore = Ignore::load(dir, excludes)?;
+    let mut found = vec![];
+    walk(dir, dir, out, &ignore, &mut found)?;
+    Ok(found)
+}
+
+fn walk(root: &Path, dir: &Path, out: &Path, ignore: &Ignore, found: &mut Vec<Pending>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let kind = entry.file_type()?;
+        let name = path.strip_prefix(root).unwrap().to_path_buf();
+
+        if ignore.ignored(&name, kind.is_dir()) {
+            continue;
+        }
+        if kind.is_dir() {
+            // Signatures written into the watched directory are not signed themselves
+            if path != out {
+                walk(root, &path, out, ignore, found)?;
+            }
+            continue;
+        }
+        if !kind.is_file() || path.to_string_lossy().ends_with(SUFFIX) {
+            continue;
+        }
+
+        let mut signature = out.join(&name).into_os_string();
+        signature.push(SUFFIX);
+        let signature = PathBuf::from(signature);
+
+        let modified = entry.metadata()?.modified()?;
+        let signed = metadata(&signature).and_then(|m| m.modified()).ok();
+        if signed.map_or(true, |signed| signed < modified) {
+            found.push(Pending { file: path, signature, name });
+        }
+    }
+
+    Ok(())
+}
+
+struct Ignore {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    /// Path segments to match, where `**` matches any number of them
+    segments: Vec<String>,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl Ignore {
+    fn load(dir: &Path, excludes: &[String]) -> io::Result<Self> {
+        let mut lines = match fs::read_to_string(dir.join(IGNORE_FILE)) {
+            Ok(text) => text.lines().map(String::from).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        lines.extend(excludes.iter().cloned());
+
+        Ok(Ignore { rules: lines.iter().filter_map(|line| Rule::parse(line)).collect() })
+    }
+
+    /// Whether `name`, relative to the watched directory, should not be signed
+    fn ignored(&self, name: &Path, is_dir: bool) -> bool {
+        let segments: Vec<String> = name
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            if (is_dir || !rule.dir_only) && matches(&rule.segments, &segments) {
+                ignored = !rule.negate;
+            }
+        }
+        ignored
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+
+        // Without a slash, a pattern matches a name at any depth
+        let anchored = line.contains('/');
+        let mut segments: Vec<String> = line
+            .trim_start_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        if segments.is_empty() {
+            return None;
+        }
+        if !anchored {
+            segments.insert(0, "**".to_string());
+        }
+
+        Some(Rule { segments, negate, dir_only })
+    }
+}
+
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        // A trailing `**` matches what is inside a directory, not the directory itself
+        Some((first, rest)) if first == "**" && rest.is_empty() => !path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path)) => glob_match(first, segment) && matches(rest, path),
+            None => false,
+        },
+    }
+}
@@ -0,0 +1,155 @@
+//! Conversion of ed25519 keys to their X25519 (Montgomery form) counterparts
+//!
+//! This is the same birational map used by libsodium's
+//! `crypto_sign_ed25519_pk_to_curve25519`, and by age for `ssh-ed25519` recipients.
+
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::derive::Deriver;
+use openssl::error::ErrorStack;
+use openssl::pkey::{Id, PKey};
+use openssl::sha::sha512;
+
+/// Convert an ed25519 public key to an X25519 public key
+///
+/// Returns `None` for points that have no Montgomery form.
+pub fn public_key(ed25519: &[u8; 32]) -> Option<[u8; 32]> {
+    // u = (1 + y) / (1 - y) mod p, with y encoded little endian and the
+    // sign of x in the top bit
+    let mut y_be = *ed25519;
+    y_be[31] &= 0x7F;
+    y_be.reverse();
+
+    let convert = || -> Result<Option<[u8; 32]>, ErrorStack> {
+        let mut ctx = BigNumContext::new()?;
+        let one = BigNum::from_u32(1)?;
+        let mut p = BigNum::new()?;
+        p.lshift(&one, 255)?;
+        p.sub_word(19)?;
+
+        let y = BigNum::from_slice(&y_be)?;
+
+        let mut num = BigNum::new()?;
+        num.mod_add(&one, &y, &p, &mut ctx)?;
+        let mut den = BigNum::new()?;
+        den.mod_sub(&one, &y, &p, &mut ctx)?;
+        if den.num_bits() == 0 {
+            return Ok(None);
+        }
+
+        let mut inv = BigNum::new()?;
+        inv.mod_inverse(&den, &p, &mut ctx)?;
+        let mut u = BigNum::new()?;
+        u.mod_mul(&num, &inv, &p, &mut ctx)?;
+
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&u.to_vec_padded(32)?);
+        out.reverse();
+        Ok(Some(out))
+    };
+
+    convert().ok().flatten()
+}
+
+/// Convert the 32 byte seed of an ed25519 secret key to an X25519 secret key
+pub fn secret_key(seed: &[u8]) -> [u8; 32] {
+    let hash = sha512(seed);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hash[..32]);
+    out[0] &= 248;
+    out[31] &= 127;
+    out[31] |= 64;
+    out
+}
+
+/// Perform an X25519 key agreement
+///
+/// Returns `None` if the peer key is a low order point.
+pub fn diffie_hellman(secret: &[u8; 32], public: &[u8; 32]) -> Option<[u8; 32]> {
+    let derive = || -> Result<Vec<u8>, ErrorStack> {
+        let secret = PKey::private_key_from_raw_bytes(secret, Id::X25519)?;
+        let public = PKey::public_key_from_raw_bytes(public, Id::X25519)?;
+        let mut deriver = Deriver::new(&secret)?;
+        deriver.set_peer(&public)?;
+        deriver.derive_to_vec()
+    };
+
+    let shared = derive().ok()?;
+    if shared.len() != 32 || shared.iter().all(|&b| b == 0) {
+        return None;
+    }
+
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&shared);
+    Some(out)
+}
+
+/// Generate an ephemeral X25519 key pair, as `(secret, public)`
+pub fn ephemeral() -> ([u8; 32], [u8; 32]) {
+    let key = PKey::generate_x25519().expect("Failed to generate X25519 key");
+    let mut secret = [0u8; 32];
+    let mut public = [0u8; 32];
+    secret.copy_from_slice(&key.raw_private_key().unwrap());
+    public.copy_from_slice(&key.raw_public_key().unwrap());
+    (secret, public)
+}
+
+/// Encode an X25519 public key as an age recipient (`age1...`)
+pub fn age_recipient(public: &[u8; 32]) -> String {
+    bech32("age", public)
+}
+
+/// Encode an X25519 secret key as an age identity (`AGE-SECRET-KEY-1...`)
+pub fn age_identity(secret: &[u8; 32]) -> String {
+    bech32("age-secret-key-", secret).to_uppercase()
+}
+
+/// Minimal BIP 173 bech32 encoding, as used by age
+fn bech32(hrp: &str, data: &[u8]) -> String {
+    const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GEN: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+
+    // Regroup 8 bit bytes into 5 bit words
+    let mut words = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    for &b in data {
+        acc = (acc << 8) | u32::from(b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            words.push(((acc >> bits) & 31) as u8);
+        }
+    }
+    if bits > 0 {
+        words.push(((acc << (5 - bits)) & 31) as u8);
+    }
+
+    let polymod = |values: &[u8]| {
+        let mut chk = 1u32;
+        for &v in values {
+            let top = chk >> 25;
+            chk = ((chk & 0x1ff_ffff) << 5) ^ u32::from(v);
+            for (i, g) in GEN.iter().enumerate() {
+                if (top >> i) & 1 == 1 {
+                    chk ^= g;
+                }
+            }
+        }
+        chk
+    };
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values.extend(&words);
+    values.extend(&[0; 6]);
+    let checksum = polymod(&values) ^ 1;
+
+    let mut out = format!("{}1", hrp);
+    for w in words {
+        out.push(CHARSET[w as usize] as char);
+    }
+    for i in 0..6 {
+        out.push(CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char);
+    }
+    out
+}